@@ -12,7 +12,72 @@ curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Acc
 
 curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX forum_description_index ON forum FIELDS description SEARCH ANALYZER english_analyzer BM25;" http://localhost:4003/sql
 
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX forum_parent_index ON forum FIELDS parent;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX forum_owner_index ON forum FIELDS owner;" http://localhost:4003/sql
+
 
 curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX post_title_index ON post FIELDS title SEARCH ANALYZER english_analyzer BM25;" http://localhost:4003/sql
 
 curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX post_content_index ON post FIELDS content SEARCH ANALYZER english_analyzer BM25;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX moderates_unique_index ON moderates FIELDS in, out UNIQUE;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX forum_ban_unique_index ON forum_ban FIELDS user, forum UNIQUE;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX mod_log_forum_index ON mod_log FIELDS forum;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX member_of_unique_index ON member_of FIELDS in, out UNIQUE;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX forum_join_request_unique_index ON forum_join_request FIELDS user, forum UNIQUE;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX wiki_page_title_index ON wiki_page FIELDS title SEARCH ANALYZER english_analyzer BM25;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX wiki_page_content_index ON wiki_page FIELDS content SEARCH ANALYZER english_analyzer BM25;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX wiki_page_slug_unique_index ON wiki_page FIELDS forum, slug UNIQUE;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX wiki_revision_page_index ON wiki_revision FIELDS page, number UNIQUE;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX emoji_name_unique_index ON emoji FIELDS forum, name UNIQUE;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX modmail_thread_forum_index ON modmail_thread FIELDS forum;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX modmail_thread_user_index ON modmail_thread FIELDS user;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX modmail_message_thread_index ON modmail_message FIELDS thread;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX vote_unique_index ON vote FIELDS in, out UNIQUE;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX post_hot_rank_index ON post FIELDS hot_rank;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX post_controversial_rank_index ON post FIELDS controversial_rank;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX post_rising_rank_index ON post FIELDS rising_rank;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX post_score_index ON post FIELDS score;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX post_ranks_stale_index ON post FIELDS ranks_stale;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX post_pinned_index ON post FIELDS forum, pinned;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX post_forum_hot_rank_index ON post FIELDS forum, pinned, hot_rank;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX post_forum_controversial_rank_index ON post FIELDS forum, pinned, controversial_rank;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX post_forum_rising_rank_index ON post FIELDS forum, pinned, rising_rank;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX post_forum_score_index ON post FIELDS forum, pinned, score;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX revision_target_index ON revision FIELDS target;" http://localhost:4003/sql
+
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "DEFINE INDEX revision_forum_index ON revision FIELDS forum;" http://localhost:4003/sql
+
+# Accounts created before email_verified existed all went through the signup code.
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "UPDATE user SET email_verified = true WHERE bot = false AND email_verified = NONE;" http://localhost:4003/sql
+
+# Counts from before removed, held and deleted submissions stopped counting.
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "UPDATE forum SET post_count = array::len((SELECT VALUE id FROM post WHERE forum = \$parent.id AND removed != true AND held != true AND deleted != true)), comment_count = array::len((SELECT VALUE id FROM comment WHERE post.forum = \$parent.id AND removed != true AND held != true));" http://localhost:4003/sql
+
+# Posts from before the sorts existed get ranked on the next refresh.
+curl -X POST -u "root:root" -H "Surreal-NS: dev" -H "Surreal-DB: rtwalk" -H "Accept: application/json" -d "UPDATE post SET ranks_stale = true WHERE hot_rank = NONE;" http://localhost:4003/sql
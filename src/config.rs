@@ -3,3 +3,23 @@ pub const VERIFICATION_CODE_EXPIERY_SECONDS: u64 = 5 * 60; // 5 minutes
 pub const SESSION_EXPIERY_SECONDS: u64 = 30 * 24 * 60 * 60; // 30 days
 pub const BOT_SESSION_EXPIERY: u64 = 2 * 60 * 60; // 2 hours
 pub const MAX_UPLOAD_SIZE: u64 = 1024 * 1024 * 16; // 16mb
+pub const CHALLENGE_EXPIERY_SECONDS: u64 = 10 * 60; // 10 minutes
+pub const CHALLENGE_BASE_DIFFICULTY: u32 = 18; // leading zero bits
+pub const CHALLENGE_MAX_DIFFICULTY: u32 = 26;
+pub const SIGNUP_RATE_WINDOW_SECONDS: u64 = 60 * 60; // 1 hour
+pub const FORUM_TRANSFER_EXPIERY_SECONDS: u64 = 7 * 24 * 60 * 60; // 7 days
+pub const FORUM_DELETION_GRACE_SECONDS: u64 = 7 * 24 * 60 * 60; // 7 days
+pub const SUBMISSION_RATE_WINDOW_SECONDS: u64 = 60 * 60; // 1 hour
+pub const MAX_FORUM_DEPTH: usize = 5; // root forum included
+pub const STATS_MAX_WINDOW_DAYS: u32 = 90;
+pub const STATS_RETENTION_SECONDS: u64 = (STATS_MAX_WINDOW_DAYS as u64 + 1) * 24 * 60 * 60;
+pub const MAX_EMOJI_SIZE: u64 = 256 * 1024; // 256kb
+pub const MAX_EMOJIS_PER_FORUM: u32 = 100;
+pub const EMOJI_MIME_TYPES: [&str; 4] = ["image/png", "image/gif", "image/webp", "image/jpeg"];
+pub const MAX_OPEN_MODMAIL_THREADS: usize = 3; // per user and forum
+pub const MAX_PINNED_POSTS: usize = 3; // per forum
+pub const SCORE_EVENT_THROTTLE_SECONDS: u64 = 2;
+pub const HOT_RANK_EPOCH: i64 = 1_704_067_200; // 2024-01-01
+pub const HOT_RANK_DECAY_SECONDS: f64 = 45_000.0; // 12.5 hours per 10x score
+pub const RISING_WINDOW_SECONDS: u64 = 24 * 60 * 60; // 1 day
+pub const MAX_WIKI_DIFF_LINES: usize = 1_000; // changed lines per side
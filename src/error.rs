@@ -48,6 +48,92 @@ pub enum RtwalkError {
     PostNotFound,
     #[error("Comment not found")]
     CommentNotFound,
+    #[error("Registration is not allowed with this email domain")]
+    EmailDomainNotAllowed,
+    #[error("Registration challenge expired or invalid")]
+    InvalidChallenge,
+    #[error("Invalid registration challenge solution")]
+    InvalidChallengeSolution,
+    #[error("User is already a moderator")]
+    AlreadyModerator,
+    #[error("User is not a moderator")]
+    NotModerator,
+    #[error("You are banned from this forum")]
+    ForumBanned,
+    #[error("Ban duration is too long")]
+    InvalidBanDuration,
+    #[error("Forum ownership can't be transferred to this user")]
+    InvalidOwnershipTransfer,
+    #[error("No pending ownership transfer")]
+    NoPendingTransfer,
+    #[error("Forum is locked")]
+    ForumLocked,
+    #[error("Post is locked")]
+    PostLocked,
+    #[error("You must join this forum first")]
+    NotForumMember,
+    #[error("Join request not found")]
+    JoinRequestNotFound,
+    #[error("Forum rule not found")]
+    RuleNotFound,
+    #[error("Flair not found")]
+    FlairNotFound,
+    #[error("Ids in the list must be unique")]
+    DuplicateId,
+    #[error("Posts in this forum need a flair")]
+    FlairRequired,
+    #[error("Your account is too new to post in this forum")]
+    AccountTooNew,
+    #[error("You don't have enough karma to post in this forum")]
+    NotEnoughKarma,
+    #[error("A verified email is required to post in this forum")]
+    EmailNotVerified,
+    #[error("Attachments aren't allowed in this forum")]
+    AttachmentsNotAllowed,
+    #[error("Too many attachments for this forum")]
+    TooManyAttachments,
+    #[error("Attachment type isn't allowed in this forum")]
+    AttachmentTypeNotAllowed,
+    #[error("You are posting too often, try again later")]
+    PostRateLimited,
+    #[error("You are commenting too often, try again later")]
+    CommentRateLimited,
+    #[error("AutoModerator rule \"{0}\" is invalid")]
+    InvalidAutomodRule(String),
+    #[error("AutoModerator needs a bot account that moderates the forum")]
+    InvalidAutomodBot,
+    #[error("Category not found")]
+    CategoryNotFound,
+    #[error("Forum can't be placed under that parent")]
+    InvalidForumParent,
+    #[error("Wiki page not found")]
+    WikiPageNotFound,
+    #[error("Wiki revision not found")]
+    WikiRevisionNotFound,
+    #[error("Wiki page was edited since the revision you started from")]
+    WikiEditConflict,
+    #[error("Too many lines changed between the revisions to diff them")]
+    WikiDiffTooLarge,
+    #[error("Emoji not found")]
+    EmojiNotFound,
+    #[error("Forum already has an emoji with this name")]
+    EmojiAlreadyExists,
+    #[error("Forum has reached the maximum number of emoji")]
+    TooManyEmojis,
+    #[error("Emoji must be a PNG, GIF, WebP or JPEG image of at most 256kb")]
+    InvalidEmojiImage,
+    #[error("Modmail thread not found")]
+    ModmailThreadNotFound,
+    #[error("You have too many open modmail threads with this forum")]
+    TooManyModmailThreads,
+    #[error("Post was deleted")]
+    PostDeleted,
+    #[error("You can't vote on your own post")]
+    CannotVoteOwnPost,
+    #[error("This forum already has the maximum number of pinned posts")]
+    TooManyPinnedPosts,
+    #[error("Filter by at least one forum, post, author, modmail thread or event type")]
+    EmptyRteFilter,
 }
 
 impl ErrorExtensions for RtwalkError {
@@ -122,6 +208,178 @@ impl ErrorExtensions for RtwalkError {
                 trace!("{}", self);
                 e.set("tp", "COMMENT_NOT_FOUND");
             }
+            RtwalkError::EmailDomainNotAllowed => {
+                trace!("{}", self);
+                e.set("tp", "EMAIL_DOMAIN_NOT_ALLOWED");
+            }
+            RtwalkError::InvalidChallenge => {
+                trace!("{}", self);
+                e.set("tp", "INVALID_CHALLENGE");
+            }
+            RtwalkError::InvalidChallengeSolution => {
+                trace!("{}", self);
+                e.set("tp", "INVALID_CHALLENGE_SOLUTION");
+            }
+            RtwalkError::AlreadyModerator => {
+                trace!("{}", self);
+                e.set("tp", "ALREADY_MODERATOR");
+            }
+            RtwalkError::NotModerator => {
+                trace!("{}", self);
+                e.set("tp", "NOT_MODERATOR");
+            }
+            RtwalkError::ForumBanned => {
+                trace!("{}", self);
+                e.set("tp", "FORUM_BANNED");
+            }
+            RtwalkError::InvalidBanDuration => {
+                trace!("{}", self);
+                e.set("tp", "INVALID_BAN_DURATION");
+            }
+            RtwalkError::InvalidOwnershipTransfer => {
+                trace!("{}", self);
+                e.set("tp", "INVALID_OWNERSHIP_TRANSFER");
+            }
+            RtwalkError::NoPendingTransfer => {
+                trace!("{}", self);
+                e.set("tp", "NO_PENDING_TRANSFER");
+            }
+            RtwalkError::ForumLocked => {
+                trace!("{}", self);
+                e.set("tp", "FORUM_LOCKED");
+            }
+            RtwalkError::PostLocked => {
+                trace!("{}", self);
+                e.set("tp", "POST_LOCKED");
+            }
+            RtwalkError::NotForumMember => {
+                trace!("{}", self);
+                e.set("tp", "NOT_FORUM_MEMBER");
+            }
+            RtwalkError::JoinRequestNotFound => {
+                trace!("{}", self);
+                e.set("tp", "JOIN_REQUEST_NOT_FOUND");
+            }
+            RtwalkError::RuleNotFound => {
+                trace!("{}", self);
+                e.set("tp", "RULE_NOT_FOUND");
+            }
+            RtwalkError::FlairNotFound => {
+                trace!("{}", self);
+                e.set("tp", "FLAIR_NOT_FOUND");
+            }
+            RtwalkError::DuplicateId => {
+                trace!("{}", self);
+                e.set("tp", "DUPLICATE_ID");
+            }
+            RtwalkError::FlairRequired => {
+                trace!("{}", self);
+                e.set("tp", "FLAIR_REQUIRED");
+            }
+            RtwalkError::AccountTooNew => {
+                trace!("{}", self);
+                e.set("tp", "ACCOUNT_TOO_NEW");
+            }
+            RtwalkError::NotEnoughKarma => {
+                trace!("{}", self);
+                e.set("tp", "NOT_ENOUGH_KARMA");
+            }
+            RtwalkError::EmailNotVerified => {
+                trace!("{}", self);
+                e.set("tp", "EMAIL_NOT_VERIFIED");
+            }
+            RtwalkError::AttachmentsNotAllowed => {
+                trace!("{}", self);
+                e.set("tp", "ATTACHMENTS_NOT_ALLOWED");
+            }
+            RtwalkError::TooManyAttachments => {
+                trace!("{}", self);
+                e.set("tp", "TOO_MANY_ATTACHMENTS");
+            }
+            RtwalkError::AttachmentTypeNotAllowed => {
+                trace!("{}", self);
+                e.set("tp", "ATTACHMENT_TYPE_NOT_ALLOWED");
+            }
+            RtwalkError::PostRateLimited => {
+                trace!("{}", self);
+                e.set("tp", "POST_RATE_LIMITED");
+            }
+            RtwalkError::CommentRateLimited => {
+                trace!("{}", self);
+                e.set("tp", "COMMENT_RATE_LIMITED");
+            }
+            RtwalkError::InvalidAutomodRule(_) => {
+                trace!("{}", self);
+                e.set("tp", "INVALID_AUTOMOD_RULE");
+            }
+            RtwalkError::InvalidAutomodBot => {
+                trace!("{}", self);
+                e.set("tp", "INVALID_AUTOMOD_BOT");
+            }
+            RtwalkError::CategoryNotFound => {
+                trace!("{}", self);
+                e.set("tp", "CATEGORY_NOT_FOUND");
+            }
+            RtwalkError::InvalidForumParent => {
+                trace!("{}", self);
+                e.set("tp", "INVALID_FORUM_PARENT");
+            }
+            RtwalkError::WikiPageNotFound => {
+                trace!("{}", self);
+                e.set("tp", "WIKI_PAGE_NOT_FOUND");
+            }
+            RtwalkError::WikiRevisionNotFound => {
+                trace!("{}", self);
+                e.set("tp", "WIKI_REVISION_NOT_FOUND");
+            }
+            RtwalkError::WikiEditConflict => {
+                trace!("{}", self);
+                e.set("tp", "WIKI_EDIT_CONFLICT");
+            }
+            RtwalkError::WikiDiffTooLarge => {
+                trace!("{}", self);
+                e.set("tp", "WIKI_DIFF_TOO_LARGE");
+            }
+            RtwalkError::EmojiNotFound => {
+                trace!("{}", self);
+                e.set("tp", "EMOJI_NOT_FOUND");
+            }
+            RtwalkError::EmojiAlreadyExists => {
+                trace!("{}", self);
+                e.set("tp", "EMOJI_ALREADY_EXISTS");
+            }
+            RtwalkError::TooManyEmojis => {
+                trace!("{}", self);
+                e.set("tp", "TOO_MANY_EMOJIS");
+            }
+            RtwalkError::InvalidEmojiImage => {
+                trace!("{}", self);
+                e.set("tp", "INVALID_EMOJI_IMAGE");
+            }
+            RtwalkError::ModmailThreadNotFound => {
+                trace!("{}", self);
+                e.set("tp", "MODMAIL_THREAD_NOT_FOUND");
+            }
+            RtwalkError::TooManyModmailThreads => {
+                trace!("{}", self);
+                e.set("tp", "TOO_MANY_MODMAIL_THREADS");
+            }
+            RtwalkError::PostDeleted => {
+                trace!("{}", self);
+                e.set("tp", "POST_DELETED");
+            }
+            RtwalkError::CannotVoteOwnPost => {
+                trace!("{}", self);
+                e.set("tp", "CANNOT_VOTE_OWN_POST");
+            }
+            RtwalkError::TooManyPinnedPosts => {
+                trace!("{}", self);
+                e.set("tp", "TOO_MANY_PINNED_POSTS");
+            }
+            RtwalkError::EmptyRteFilter => {
+                trace!("{}", self);
+                e.set("tp", "EMPTY_RTE_FILTER");
+            }
         })
     }
 }
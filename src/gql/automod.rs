@@ -0,0 +1,409 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use regex::{Regex, RegexBuilder};
+use surrealdb::RecordId;
+
+use crate::{
+    error::RtwalkError,
+    gql::{
+        comments, moderators, modlog,
+        posts::{self, Submission},
+        users,
+    },
+    models::{
+        automod::{AutomodAction, AutomodRule, AutomodTarget, DBAutomod, TextCondition},
+        comment::DBComment,
+        forum::DBForum,
+        modlog::ModAction,
+        post::DBPost,
+        user::User,
+        Key,
+    },
+    state::State,
+};
+
+/// Keeps a single rule from getting huge, compiled regexes stay in [`REGEX_CACHE`] until
+/// the config changes.
+const REGEX_SIZE_LIMIT: usize = 1 << 16;
+
+/// Title and content regexes of each rule, in rule order.
+type CompiledRegexes = Vec<(Option<Regex>, Option<Regex>)>;
+
+/// Compiled regexes per forum config, replaced when the config's `updated_at` changes.
+type RegexCache = HashMap<RecordId, (DateTime<Utc>, Arc<CompiledRegexes>)>;
+
+static REGEX_CACHE: LazyLock<Mutex<RegexCache>> = LazyLock::new(Default::default);
+
+/// The parts of a post or comment rules are matched against.
+pub struct AutomodSubject<'a> {
+    pub kind: Submission,
+    pub title: Option<&'a str>,
+    pub content: Option<&'a str>,
+    pub tags: &'a [String],
+    /// Only known for fresh uploads, edits can't add attachments.
+    pub attachment_types: &'a [String],
+}
+
+pub async fn fetch_automod(
+    state: &State,
+    forum: &RecordId,
+) -> Result<Option<DBAutomod>, RtwalkError> {
+    Ok(state.db.select(("automod", forum.key().to_owned())).await?)
+}
+
+/// Replaces the forum's config after checking every rule can actually run.
+pub async fn set_automod(
+    state: &State,
+    forum: &DBForum,
+    bot: Option<Key>,
+    rules: Vec<AutomodRule>,
+    updated_by: Key,
+) -> Result<DBAutomod, RtwalkError> {
+    for rule in &rules {
+        let invalid = || RtwalkError::InvalidAutomodRule(rule.name.clone());
+
+        let regexes = [&rule.title, &rule.content]
+            .into_iter()
+            .flatten()
+            .filter_map(|c| c.regex.as_ref());
+        for regex in regexes {
+            build_regex(regex).map_err(|_| invalid())?;
+        }
+
+        match rule.action {
+            AutomodAction::Flair => {
+                let flair = rule.flair_id.as_ref().ok_or_else(invalid)?;
+                forum.flair(flair).ok_or(RtwalkError::FlairNotFound)?;
+            }
+            AutomodAction::Reply if rule.reply.is_none() => return Err(invalid()),
+            AutomodAction::Reply if bot.is_none() => return Err(RtwalkError::InvalidAutomodBot),
+            _ => {}
+        }
+    }
+
+    if let Some(bot) = &bot {
+        let mut res = state
+            .db
+            .query("SELECT VALUE bot FROM ONLY $user")
+            .bind(("user", RecordId::from_table_key("user", bot.0.clone())))
+            .await?;
+        let is_bot: Option<bool> = res.take(0)?;
+        if is_bot != Some(true)
+            || moderators::fetch_moderator(state, &forum.id, bot.clone())
+                .await?
+                .is_none()
+        {
+            return Err(RtwalkError::InvalidAutomodBot);
+        }
+    }
+
+    let automod = DBAutomod::new(Key(forum.id.key().to_owned()), bot, rules, updated_by);
+
+    let res: Option<DBAutomod> = state.db.upsert(&automod.id).content(automod).await?;
+
+    Ok(res.expect("Automod was just saved"))
+}
+
+fn build_regex(regex: &str) -> Result<regex::Regex, regex::Error> {
+    RegexBuilder::new(regex)
+        .case_insensitive(true)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+}
+
+fn compiled_regexes(automod: &DBAutomod) -> Arc<CompiledRegexes> {
+    let mut cache = REGEX_CACHE.lock().unwrap();
+    if let Some((updated_at, regexes)) = cache.get(&automod.id) {
+        if *updated_at == automod.updated_at {
+            return regexes.clone();
+        }
+    }
+
+    let compile = |c: &Option<TextCondition>| {
+        c.as_ref()
+            .and_then(|c| c.regex.as_ref())
+            .and_then(|r| build_regex(r).ok())
+    };
+    let regexes: Arc<CompiledRegexes> = Arc::new(
+        automod
+            .rules
+            .iter()
+            .map(|r| (compile(&r.title), compile(&r.content)))
+            .collect(),
+    );
+    cache.insert(automod.id.clone(), (automod.updated_at, regexes.clone()));
+
+    regexes
+}
+
+fn text_matches(condition: &TextCondition, regex: Option<&Regex>, text: Option<&str>) -> bool {
+    let Some(text) = text else {
+        return false;
+    };
+    let lowercase = text.to_lowercase();
+
+    condition
+        .keywords
+        .iter()
+        .any(|k| lowercase.contains(&k.to_lowercase()))
+        || regex.is_some_and(|r| r.is_match(text))
+}
+
+fn rule_matches(
+    rule: &AutomodRule,
+    (title_regex, content_regex): &(Option<Regex>, Option<Regex>),
+    subject: &AutomodSubject<'_>,
+    account_age: i64,
+    karma: i64,
+) -> bool {
+    let is_post = matches!(subject.kind, Submission::Post);
+
+    let targeted = match rule.applies_to {
+        AutomodTarget::All => true,
+        AutomodTarget::Posts => is_post,
+        AutomodTarget::Comments => !is_post,
+    };
+
+    targeted
+        && rule
+            .title
+            .as_ref()
+            .is_none_or(|c| is_post && text_matches(c, title_regex.as_ref(), subject.title))
+        && rule
+            .content
+            .as_ref()
+            .is_none_or(|c| text_matches(c, content_regex.as_ref(), subject.content))
+        && (rule.tags.is_empty()
+            || subject
+                .tags
+                .iter()
+                .any(|t| rule.tags.iter().any(|r| r.eq_ignore_ascii_case(t))))
+        && rule
+            .account_age_below
+            .is_none_or(|a| account_age < a as i64)
+        && rule.karma_below.is_none_or(|k| karma < k)
+        && (rule.attachment_types.is_empty()
+            || subject.attachment_types.iter().any(|t| {
+                rule.attachment_types.iter().any(|r| {
+                    r.eq_ignore_ascii_case(t)
+                        || (r.ends_with('/') && t.to_lowercase().starts_with(&r.to_lowercase()))
+                })
+            }))
+}
+
+/// Rules of the forum that match the subject. Moderators are never checked.
+async fn matching_rules(
+    state: &State,
+    forum: &DBForum,
+    author: &User,
+    subject: &AutomodSubject<'_>,
+) -> Result<Option<(DBAutomod, Vec<AutomodRule>)>, RtwalkError> {
+    let Some(automod) = fetch_automod(state, &forum.id).await? else {
+        return Ok(None);
+    };
+    if automod.rules.is_empty()
+        || moderators::fetch_permissions(state, author, &forum.id)
+            .await?
+            .is_some()
+    {
+        return Ok(None);
+    }
+
+    let karma = if automod.rules.iter().any(|r| r.karma_below.is_some()) {
+        users::fetch_karma(state, &author.id).await?
+    } else {
+        0
+    };
+    let account_age = Utc::now().timestamp() - author.created_at;
+
+    let regexes = compiled_regexes(&automod);
+    let matched: Vec<AutomodRule> = automod
+        .rules
+        .iter()
+        .zip(regexes.iter())
+        .filter(|(r, regexes)| rule_matches(r, regexes, subject, account_age, karma))
+        .map(|(r, _)| r.clone())
+        .collect();
+
+    Ok((!matched.is_empty()).then_some((automod, matched)))
+}
+
+/// Logs the rule as taken by the bot, or the forum owner when there's no bot.
+async fn log_action(
+    state: &State,
+    forum: &DBForum,
+    automod: &DBAutomod,
+    rule: &AutomodRule,
+    target: &RecordId,
+) -> Result<(), RtwalkError> {
+    let actor = Key(automod
+        .bot
+        .as_ref()
+        .unwrap_or(&forum.owner)
+        .key()
+        .to_owned());
+    let action = match rule.action {
+        AutomodAction::Remove => ModAction::AutomodRemove,
+        AutomodAction::Hold => ModAction::AutomodHold,
+        AutomodAction::Flair => ModAction::AutomodFlair,
+        AutomodAction::Lock => ModAction::AutomodLock,
+        AutomodAction::Reply => ModAction::AutomodReply,
+    };
+
+    modlog::record(
+        state,
+        &forum.id,
+        &actor,
+        action,
+        Some(target.clone()),
+        Some(rule.name.clone()),
+    )
+    .await
+}
+
+/// Returns whether it replied, configs saved before `REPLY` rules needed a bot can't.
+async fn reply(
+    state: &State,
+    forum: &DBForum,
+    automod: &DBAutomod,
+    rule: &AutomodRule,
+    post: &RecordId,
+) -> Result<bool, RtwalkError> {
+    let (Some(bot), Some(content)) = (&automod.bot, &rule.reply) else {
+        return Ok(false);
+    };
+
+    comments::create_comment(
+        state,
+        Some(content.clone()),
+        vec![],
+        Key(bot.key().to_owned()),
+        Key(post.key().to_owned()),
+        &forum.id,
+    )
+    .await?;
+
+    Ok(true)
+}
+
+/// Runs the forum's rules on a new or edited post, saving it if any of them changed it.
+/// `REPLY` rules only reply to new posts, not every edit.
+pub async fn check_post(
+    state: &State,
+    forum: &DBForum,
+    author: &User,
+    mut post: DBPost,
+    attachment_types: &[String],
+    edit: bool,
+) -> Result<DBPost, RtwalkError> {
+    let subject = AutomodSubject {
+        kind: Submission::Post,
+        title: Some(&post.title),
+        content: post.content.as_deref(),
+        tags: &post.tags,
+        attachment_types,
+    };
+    let Some((automod, rules)) = matching_rules(state, forum, author, &subject).await? else {
+        return Ok(post);
+    };
+    let was_counted = post.is_counted();
+
+    for rule in &rules {
+        match rule.action {
+            AutomodAction::Remove => {
+                post.removed = true;
+                post.removal_reason = Some(rule.name.clone());
+            }
+            AutomodAction::Hold => post.held = true,
+            AutomodAction::Flair => {
+                post.flair = rule
+                    .flair_id
+                    .as_ref()
+                    .and_then(|f| forum.flair(f))
+                    .cloned()
+                    .or(post.flair);
+            }
+            AutomodAction::Lock => {
+                post.locked = true;
+                post.lock_reason = Some(rule.name.clone());
+            }
+            AutomodAction::Reply if edit => continue,
+            AutomodAction::Reply => {
+                if !reply(state, forum, &automod, rule, &post.id).await? {
+                    continue;
+                }
+            }
+        }
+        log_action(state, forum, &automod, rule, &post.id).await?;
+    }
+
+    posts::save_post(
+        state,
+        post,
+        was_counted,
+        &[
+            "removed",
+            "removal_reason",
+            "held",
+            "flair",
+            "locked",
+            "lock_reason",
+        ],
+    )
+    .await
+}
+
+/// Like [`check_post`] for comments. `LOCK` locks the post the comment is on.
+pub async fn check_comment(
+    state: &State,
+    forum: &DBForum,
+    author: &User,
+    mut comment: DBComment,
+    attachment_types: &[String],
+    edit: bool,
+) -> Result<DBComment, RtwalkError> {
+    let subject = AutomodSubject {
+        kind: Submission::Comment,
+        title: None,
+        content: comment.content.as_deref(),
+        tags: &[],
+        attachment_types,
+    };
+    let Some((automod, rules)) = matching_rules(state, forum, author, &subject).await? else {
+        return Ok(comment);
+    };
+    let was_counted = comment.is_counted();
+
+    for rule in &rules {
+        match rule.action {
+            AutomodAction::Remove => {
+                comment.removed = true;
+                comment.removal_reason = Some(rule.name.clone());
+            }
+            AutomodAction::Hold => comment.held = true,
+            AutomodAction::Flair => continue,
+            AutomodAction::Lock => {
+                state
+                    .db
+                    .query("UPDATE $post SET locked = true, lock_reason = $reason")
+                    .bind(("post", comment.post.clone()))
+                    .bind(("reason", rule.name.clone()))
+                    .await?
+                    .check()?;
+            }
+            AutomodAction::Reply if edit => continue,
+            AutomodAction::Reply => {
+                if !reply(state, forum, &automod, rule, &comment.post).await? {
+                    continue;
+                }
+            }
+        }
+        log_action(state, forum, &automod, rule, &comment.id).await?;
+    }
+
+    comments::save_comment(state, comment, &forum.id, was_counted).await
+}
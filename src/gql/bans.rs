@@ -0,0 +1,129 @@
+use chrono::{TimeDelta, Utc};
+use surrealdb::RecordId;
+
+use crate::{
+    error::RtwalkError,
+    models::{ban::DBForumBan, forum::DBForum, Key},
+    state::State,
+};
+
+/// Replaces any previous ban of the user in the forum. `duration` is in seconds,
+/// `None` bans permanently.
+pub async fn ban_user(
+    state: &State,
+    forum: &DBForum,
+    user: Key,
+    reason: Option<String>,
+    duration: Option<u64>,
+    issued_by: Key,
+    modmail_thread: Option<RecordId>,
+) -> Result<DBForumBan, RtwalkError> {
+    if forum.owner.key() == &user.0 {
+        return Err(RtwalkError::UnauhorizedRequest);
+    }
+
+    let expires_at = match duration {
+        Some(d) => Some(
+            i64::try_from(d)
+                .ok()
+                .and_then(TimeDelta::try_seconds)
+                .and_then(|d| Utc::now().checked_add_signed(d))
+                .ok_or(RtwalkError::InvalidBanDuration)?,
+        ),
+        None => None,
+    };
+    let ban = DBForumBan::new(
+        user,
+        Key(forum.id.key().to_owned()),
+        reason,
+        expires_at,
+        issued_by,
+        modmail_thread,
+    );
+
+    let mut res = state
+        .db
+        .query("SELECT 1 FROM ONLY $user")
+        .bind(("user", ban.user.clone()))
+        .await?;
+    let user_exists: Option<u64> = res.take((0, "1"))?;
+    if user_exists.is_none() {
+        return Err(RtwalkError::UserNotFound);
+    }
+
+    state
+        .db
+        .query("BEGIN TRANSACTION")
+        .query("DELETE forum_ban WHERE user = $user AND forum = $forum")
+        .query("CREATE forum_ban CONTENT $ban")
+        .query("COMMIT TRANSACTION")
+        .bind(("user", ban.user.clone()))
+        .bind(("forum", ban.forum.clone()))
+        .bind(("ban", ban.clone()))
+        .await?;
+
+    Ok(ban)
+}
+
+/// Returns false if the user wasn't banned.
+pub async fn unban_user(state: &State, forum: &RecordId, user: Key) -> Result<bool, RtwalkError> {
+    let mut res = state
+        .db
+        .query("DELETE forum_ban WHERE user = $user AND forum = $forum RETURN BEFORE")
+        .bind(("user", RecordId::from_table_key("user", user.0)))
+        .bind(("forum", forum.clone()))
+        .await?;
+
+    let removed: Vec<DBForumBan> = res.take(0)?;
+
+    Ok(removed.iter().any(|b| b.is_active()))
+}
+
+/// Latest ban of the user in the forum, expired or not.
+pub async fn fetch_ban(
+    state: &State,
+    forum: &RecordId,
+    user: &Key,
+) -> Result<Option<DBForumBan>, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT * FROM forum_ban WHERE user = $user AND forum = $forum")
+        .bind(("user", RecordId::from_table_key("user", user.0.clone())))
+        .bind(("forum", forum.clone()))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+pub async fn ensure_not_banned(
+    state: &State,
+    forum: &RecordId,
+    user: &Key,
+) -> Result<(), RtwalkError> {
+    if fetch_ban(state, forum, user)
+        .await?
+        .is_some_and(|b| b.is_active())
+    {
+        return Err(RtwalkError::ForumBanned);
+    }
+    Ok(())
+}
+
+pub async fn fetch_bans(
+    state: &State,
+    forum: &RecordId,
+    page: u32,
+    per_page: u32,
+) -> Result<Vec<DBForumBan>, RtwalkError> {
+    let mut res = state
+        .db
+        .query(
+            "SELECT * FROM forum_ban WHERE forum = $forum ORDER BY created_at DESC LIMIT $limit START $start",
+        )
+        .bind(("forum", forum.clone()))
+        .bind(("limit", per_page))
+        .bind(("start", (page.max(1) - 1).saturating_mul(per_page)))
+        .await?;
+
+    Ok(res.take(0)?)
+}
@@ -0,0 +1,97 @@
+use surrealdb::RecordId;
+
+use crate::{
+    error::RtwalkError,
+    models::{category::DBCategory, Key},
+    state::State,
+};
+
+pub async fn create_category(
+    state: &State,
+    name: String,
+    description: Option<String>,
+    position: u32,
+    created_by: Key,
+) -> Result<DBCategory, RtwalkError> {
+    let category = DBCategory::new(name, description, position, created_by);
+
+    state
+        .db
+        .query("CREATE category CONTENT $category")
+        .bind(("category", category.clone()))
+        .await?
+        .check()?;
+
+    Ok(category)
+}
+
+pub async fn fetch_category(
+    state: &State,
+    category: Key,
+) -> Result<Option<DBCategory>, RtwalkError> {
+    Ok(state.db.select(("category", category.0)).await?)
+}
+
+pub async fn fetch_categories(state: &State) -> Result<Vec<DBCategory>, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT * FROM category ORDER BY position ASC, name ASC")
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+pub async fn update_category(
+    state: &State,
+    category: DBCategory,
+) -> Result<DBCategory, RtwalkError> {
+    let res: Option<DBCategory> = state.db.update(&category.id).content(category).await?;
+
+    res.ok_or(RtwalkError::CategoryNotFound)
+}
+
+/// Forums in the category are left uncategorized. Returns false if it didn't exist.
+pub async fn delete_category(state: &State, category: Key) -> Result<bool, RtwalkError> {
+    let Some(category) = fetch_category(state, category).await? else {
+        return Ok(false);
+    };
+
+    state
+        .db
+        .query("BEGIN TRANSACTION")
+        .query("UPDATE forum SET category = NONE WHERE category = $category")
+        .query("DELETE $category")
+        .query("COMMIT TRANSACTION")
+        .bind(("category", category.id))
+        .await?
+        .check()?;
+
+    Ok(true)
+}
+
+/// `None` takes the forum out of its category.
+pub async fn set_forum_category(
+    state: &State,
+    forum: &RecordId,
+    category: Option<Key>,
+) -> Result<(), RtwalkError> {
+    let category = match category {
+        Some(key) => Some(
+            fetch_category(state, key)
+                .await?
+                .ok_or(RtwalkError::CategoryNotFound)?
+                .id,
+        ),
+        None => None,
+    };
+
+    state
+        .db
+        .query("UPDATE $forum SET category = $category")
+        .bind(("forum", forum.clone()))
+        .bind(("category", category))
+        .await?
+        .check()?;
+
+    Ok(())
+}
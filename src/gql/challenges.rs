@@ -0,0 +1,103 @@
+use std::net::IpAddr;
+
+use cuid2::cuid;
+use rustis::{
+    client::BatchPreparedCommand,
+    commands::{ExpireOption, GenericCommands, SetCondition, SetExpiration, StringCommands},
+};
+use sha2::{Digest, Sha256};
+
+use crate::{config, error::RtwalkError, state::State};
+
+fn signup_rate_key(ip: Option<IpAddr>) -> String {
+    match ip {
+        Some(ip) => format!("signup_rate:{}", ip),
+        None => "signup_rate:unknown".to_string(),
+    }
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        if *byte == 0 {
+            bits += 8;
+        } else {
+            bits += byte.leading_zeros();
+            break;
+        }
+    }
+    bits
+}
+
+/// Every recent signup from the ip adds a bit of difficulty, doubling the expected work.
+async fn current_difficulty(state: &State, ip: Option<IpAddr>) -> Result<u32, RtwalkError> {
+    let recent_signups: Option<u32> = state.redis.get(signup_rate_key(ip)).await?;
+
+    Ok(
+        (config::CHALLENGE_BASE_DIFFICULTY + recent_signups.unwrap_or(0))
+            .min(config::CHALLENGE_MAX_DIFFICULTY),
+    )
+}
+
+/// Creates a hashcash style challenge for the ip's current difficulty. The ip is stored
+/// with it so signups since then still count when it's solved.
+pub async fn create_challenge(
+    state: &State,
+    ip: Option<IpAddr>,
+) -> Result<(String, u32), RtwalkError> {
+    let difficulty = current_difficulty(state, ip).await?;
+    let nonce = cuid();
+    let issued_to = ip.map_or("unknown".to_string(), |ip| ip.to_string());
+
+    state
+        .redis
+        .set_with_options(
+            format!("challenge:{}", &nonce),
+            format!("{} {}", difficulty, issued_to),
+            SetCondition::None,
+            SetExpiration::Ex(config::CHALLENGE_EXPIERY_SECONDS),
+            false,
+        )
+        .await?;
+
+    Ok((nonce, difficulty))
+}
+
+/// A solution is valid if `sha256("{nonce}:{solution}")` has at least `difficulty`
+/// leading zero bits. Challenges can only be used once, and prefetched challenges don't
+/// get around the signup rate: the difficulty is at least what the issuing ip would get now.
+pub async fn verify_challenge(
+    state: &State,
+    nonce: &str,
+    solution: &str,
+) -> Result<(), RtwalkError> {
+    let challenge: Option<String> = state.redis.getdel(format!("challenge:{}", nonce)).await?;
+    let parsed = challenge.as_deref().and_then(|c| {
+        let (difficulty, ip) = c.split_once(' ')?;
+        Some((difficulty.parse::<u32>().ok()?, ip.parse::<IpAddr>().ok()))
+    });
+
+    if let Some((stored, ip)) = parsed {
+        let difficulty = stored.max(current_difficulty(state, ip).await?);
+        let hash = Sha256::digest(format!("{}:{}", nonce, solution));
+        if leading_zero_bits(&hash) >= difficulty {
+            return Ok(());
+        }
+        return Err(RtwalkError::InvalidChallengeSolution);
+    }
+
+    Err(RtwalkError::InvalidChallenge)
+}
+
+pub async fn record_signup(state: &State, ip: Option<IpAddr>) -> Result<(), RtwalkError> {
+    let key = signup_rate_key(ip);
+
+    let mut pipeline = state.redis.create_pipeline();
+    pipeline.incr(&key).forget();
+    pipeline
+        .expire(&key, config::SIGNUP_RATE_WINDOW_SECONDS, ExpireOption::Nx)
+        .forget();
+    pipeline.execute::<()>().await?;
+
+    Ok(())
+}
@@ -1,7 +1,7 @@
 use crate::{
     error::RtwalkError,
-    gql::PageInfo,
-    models::{comment::DBComment, file::File, Key},
+    gql::{forums, posts::Submission, stats, PageInfo},
+    models::{comment::DBComment, file::File, user::User, Key},
     state::State,
 };
 use surrealdb::RecordId;
@@ -14,36 +14,101 @@ pub async fn create_comment(
     attachments: Vec<File>,
     commenter: Key,
     post: Key,
+    forum: &RecordId,
 ) -> Result<DBComment, RtwalkError> {
-    let comment = DBComment::new(content, attachments, commenter, post);
+    let comment = DBComment::new(content, attachments, commenter.clone(), post);
 
     state
         .db
+        .query("BEGIN TRANSACTION")
         .query("CREATE comment CONTENT $comment")
+        .query("UPDATE $forum SET comment_count += 1")
+        .query("COMMIT TRANSACTION")
         .bind(("comment", comment.clone()))
-        .await?;
+        .bind(("forum", forum.clone()))
+        .await?
+        .check()?;
+
+    stats::record(state, forum, &commenter, Submission::Comment).await?;
 
     Ok(comment)
 }
 
+/// Like [`posts::save_post`](super::posts::save_post) for the forum's `comment_count`.
+pub async fn save_comment(
+    state: &State,
+    comment: DBComment,
+    forum: &RecordId,
+    was_counted: bool,
+) -> Result<DBComment, RtwalkError> {
+    let delta = comment.is_counted() as i64 - was_counted as i64;
+
+    state
+        .db
+        .query("BEGIN TRANSACTION")
+        .query("UPDATE $id CONTENT $comment")
+        .query("IF $delta != 0 { UPDATE $forum SET comment_count += $delta }")
+        .query("COMMIT TRANSACTION")
+        .bind(("id", comment.id.clone()))
+        .bind(("forum", forum.clone()))
+        .bind(("comment", comment.clone()))
+        .bind(("delta", delta))
+        .await?
+        .check()?;
+
+    let res: Option<DBComment> = state.db.select(&comment.id).await?;
+
+    res.ok_or(RtwalkError::CommentNotFound)
+}
+
+/// Comments waiting for approval, oldest first.
+pub async fn fetch_held_comments(
+    state: &State,
+    forum: &RecordId,
+    page: u32,
+    per_page: u32,
+) -> Result<Vec<DBComment>, RtwalkError> {
+    let mut res = state
+        .db
+        .query(
+            "SELECT * FROM comment WHERE post.forum = $forum AND held = true ORDER BY created_at ASC LIMIT $limit START $start",
+        )
+        .bind(("forum", forum.clone()))
+        .bind(("limit", per_page))
+        .bind(("start", (page.max(1) - 1).saturating_mul(per_page)))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
 pub async fn fetch_comments(
     state: &State,
     criteria: MultipleCommentSelectCriteria,
+    viewer: Option<&User>,
     page_info: &PageInfo,
 ) -> Result<Vec<DBComment>, RtwalkError> {
+    // Comments are hidden along with the forum they're in.
+    let visible = format!(
+        "post.forum.deleted_at = NONE AND {} AND {}",
+        forums::visibility_condition("post.forum", viewer),
+        forums::moderation_condition("commenter", "post.forum", viewer)
+    );
+
     let comments: Vec<DBComment> = match criteria {
         MultipleCommentSelectCriteria::Post(id) => {
-            let mut query = state
-                .db
-                .query("SELECT * FROM comment WHERE post.id = $post_id LIMIT $limit START $start");
+            let mut query = state.db.query(format!(
+                "SELECT * FROM comment WHERE post.id = $post_id AND {visible} LIMIT $limit START $start"
+            ));
 
             if page_info.needs_page_info {
-                query =
-                    query.query("SELECT count() as total FROM comment WHERE post.id = $forum_id");
+                query = query.query(format!(
+                    "SELECT count() as total FROM comment WHERE post.id = $post_id AND {visible} GROUP ALL"
+                ));
             }
 
             let mut res = query
                 .bind(("post_id", RecordId::from_table_key("post", id.0)))
+                .bind(forums::viewer_access(state, viewer).await?)
                 .bind(("limit", page_info.per_page))
                 .bind(("start", (page_info.page - 1) * page_info.per_page))
                 .await?;
@@ -64,14 +129,17 @@ pub async fn fetch_comments(
             res.take(0)?
         }
         MultipleCommentSelectCriteria::Search(search) => {
-            let mut query = state
-                .db
-                .query("SELECT * FROM comment WHERE content @1@ $query ORDER BY created_at ASC LIMIT $limit START $start");
+            let mut query = state.db.query(format!(
+                "SELECT * FROM comment WHERE content @1@ $query AND {visible} ORDER BY created_at ASC LIMIT $limit START $start"
+            ));
             if page_info.needs_page_info {
-                query = query.query("SELECT count() as total FROM comment WHERE content @1@ $query")
+                query = query.query(format!(
+                    "SELECT count() as total FROM comment WHERE content @1@ $query AND {visible} GROUP ALL"
+                ))
             }
             let mut res = query
                 .bind(("query", search))
+                .bind(forums::viewer_access(state, viewer).await?)
                 .bind(("limit", page_info.per_page))
                 .bind(("start", (page_info.page - 1) * page_info.per_page))
                 .await?;
@@ -0,0 +1,123 @@
+use std::{collections::HashSet, sync::LazyLock};
+
+use crate::{
+    error::RtwalkError,
+    models::{
+        email_domain::{DBEmailDomain, EmailDomainListKind},
+        Key,
+    },
+    state::State,
+};
+
+static DISPOSABLE_DOMAINS: LazyLock<HashSet<&'static str>> = LazyLock::new(|| {
+    include_str!("../../assets/disposable_email_domains.txt")
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect()
+});
+
+/// Returns the domain of the email and all of its parent domains,
+/// `a.b.com` gives `["a.b.com", "b.com", "com"]`.
+fn candidate_domains(email: &str) -> Vec<String> {
+    let domain = email
+        .rsplit_once('@')
+        .map(|(_, d)| d)
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let mut candidates = vec![];
+    let mut rest = domain.as_str();
+    while !rest.is_empty() {
+        candidates.push(rest.to_string());
+        rest = rest.split_once('.').map(|(_, r)| r).unwrap_or_default();
+    }
+    candidates
+}
+
+/// Checks the email against the admin managed allow/deny lists and the bundled
+/// disposable domain list. An explicit allow entry overrides the bundled list.
+pub async fn check_email_domain(state: &State, email: &str) -> Result<(), RtwalkError> {
+    let candidates = candidate_domains(email);
+
+    let mut res = state
+        .db
+        .query("SELECT * FROM email_domain WHERE domain IN $candidates")
+        .query("SELECT count() as total FROM email_domain WHERE kind = 'Allow' GROUP ALL")
+        .bind(("candidates", candidates.clone()))
+        .await?;
+
+    let matched: Vec<DBEmailDomain> = res.take(0)?;
+    let allowlist_size: Option<u64> = res.take((1, "total"))?;
+
+    if matched.iter().any(|d| d.kind == EmailDomainListKind::Deny) {
+        return Err(RtwalkError::EmailDomainNotAllowed);
+    }
+    if matched.iter().any(|d| d.kind == EmailDomainListKind::Allow) {
+        return Ok(());
+    }
+    // Allowlist in use, only listed domains can register.
+    if allowlist_size.unwrap_or(0) > 0 {
+        return Err(RtwalkError::EmailDomainNotAllowed);
+    }
+    if candidates
+        .iter()
+        .any(|d| DISPOSABLE_DOMAINS.contains(d.as_str()))
+    {
+        return Err(RtwalkError::EmailDomainNotAllowed);
+    }
+
+    Ok(())
+}
+
+pub async fn add_email_domain(
+    state: &State,
+    domain: String,
+    kind: EmailDomainListKind,
+    added_by: Key,
+) -> Result<DBEmailDomain, RtwalkError> {
+    let entry = DBEmailDomain::new(domain.to_lowercase(), kind, added_by);
+
+    let res: Option<DBEmailDomain> = state.db.upsert(&entry.id).content(entry).await?;
+
+    res.ok_or(RtwalkError::ImpossibleError(
+        "Upsert always returns the record",
+        None,
+    ))
+}
+
+pub async fn remove_email_domain(state: &State, domain: String) -> Result<bool, RtwalkError> {
+    let res: Option<DBEmailDomain> = state
+        .db
+        .delete(("email_domain", domain.to_lowercase()))
+        .await?;
+
+    Ok(res.is_some())
+}
+
+pub async fn fetch_email_domains(
+    state: &State,
+    kind: Option<EmailDomainListKind>,
+) -> Result<Vec<DBEmailDomain>, RtwalkError> {
+    let domains: Vec<DBEmailDomain> = match kind {
+        Some(kind) => {
+            let mut res = state
+                .db
+                .query("SELECT * FROM email_domain WHERE kind = $kind ORDER BY domain ASC")
+                .bind(("kind", kind))
+                .await?;
+
+            res.take(0)?
+        }
+        None => {
+            let mut res = state
+                .db
+                .query("SELECT * FROM email_domain ORDER BY domain ASC")
+                .await?;
+
+            res.take(0)?
+        }
+    };
+
+    Ok(domains)
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use async_graphql::UploadValue;
+use cuid2::cuid;
+use surrealdb::RecordId;
+
+use crate::{
+    config,
+    error::RtwalkError,
+    models::{
+        emoji::{ContentToken, DBEmoji},
+        file::{File, FileOps},
+        Key,
+    },
+    state::State,
+};
+
+/// Ordered by name. There are at most [`config::MAX_EMOJIS_PER_FORUM`] so they're not paged.
+pub async fn fetch_emojis(state: &State, forum: &RecordId) -> Result<Vec<DBEmoji>, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT * FROM emoji WHERE forum = $forum ORDER BY name ASC")
+        .bind(("forum", forum.clone()))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+/// Emoji of every forum in `forums`, ordered by name.
+pub async fn fetch_emojis_of(
+    state: &State,
+    forums: &[RecordId],
+) -> Result<Vec<DBEmoji>, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT * FROM emoji WHERE forum IN $forums ORDER BY name ASC")
+        .bind(("forums", forums.to_vec()))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+pub async fn fetch_emoji(
+    state: &State,
+    forum: &RecordId,
+    name: String,
+) -> Result<Option<DBEmoji>, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT * FROM emoji WHERE forum = $forum AND name = $name")
+        .bind(("forum", forum.clone()))
+        .bind(("name", name))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+/// Checks the image against the emoji limits before saving it under the forum's files.
+pub async fn create_emoji(
+    state: &State,
+    forum: &RecordId,
+    name: String,
+    mut image: UploadValue,
+    created_by: Key,
+) -> Result<DBEmoji, RtwalkError> {
+    let size = image
+        .size()
+        .map_err(|e| RtwalkError::InternalError(e.into()))?;
+    let allowed_type = image
+        .content_type
+        .as_ref()
+        .is_some_and(|t| config::EMOJI_MIME_TYPES.contains(&t.to_lowercase().as_str()));
+    if size > config::MAX_EMOJI_SIZE || !allowed_type {
+        return Err(RtwalkError::InvalidEmojiImage);
+    }
+
+    if fetch_emoji(state, forum, name.clone()).await?.is_some() {
+        return Err(RtwalkError::EmojiAlreadyExists);
+    }
+    let mut res = state
+        .db
+        .query("SELECT count() AS total FROM emoji WHERE forum = $forum GROUP ALL")
+        .bind(("forum", forum.clone()))
+        .await?;
+    let total: Option<u32> = res.take((0, "total"))?;
+    if total.unwrap_or(0) >= config::MAX_EMOJIS_PER_FORUM {
+        return Err(RtwalkError::TooManyEmojis);
+    }
+
+    let file = File {
+        loc: format!("{}/emoji/{}-{}", forum, cuid(), image.filename),
+    };
+    file.save(&state.op, &mut image).await?;
+
+    let emoji = DBEmoji::new(forum.clone(), name, file, created_by);
+
+    let res = state
+        .db
+        .query("CREATE emoji CONTENT $emoji")
+        .bind(("emoji", emoji.clone()))
+        .await?
+        .check();
+    if res.is_err() {
+        // Most likely lost a race for the name against another upload.
+        emoji.image.delete(&state.op).await?;
+        return Err(RtwalkError::EmojiAlreadyExists);
+    }
+
+    Ok(emoji)
+}
+
+pub async fn delete_emoji(state: &State, emoji: &DBEmoji) -> Result<(), RtwalkError> {
+    let _: Option<DBEmoji> = state.db.delete(&emoji.id).await?;
+    emoji.image.delete(&state.op).await?;
+
+    Ok(())
+}
+
+/// Splits content into text and the forum's emoji. Shortcodes of unknown emoji stay text.
+pub fn tokenize(content: &str, emojis: &[DBEmoji]) -> Vec<ContentToken> {
+    let emojis: HashMap<&str, &DBEmoji> = emojis.iter().map(|e| (e.name.as_str(), e)).collect();
+
+    let mut tokens = vec![];
+    let mut text = String::new();
+    let mut rest = content;
+
+    while let Some(start) = rest.find(':') {
+        let after = &rest[start + 1..];
+        let emoji = after
+            .find(':')
+            .and_then(|end| emojis.get(&after[..end]).map(|e| (end, e)));
+
+        match emoji {
+            Some((end, emoji)) => {
+                text.push_str(&rest[..start]);
+                if !text.is_empty() {
+                    tokens.push(ContentToken {
+                        text: std::mem::take(&mut text),
+                        emoji: None,
+                    });
+                }
+                tokens.push(ContentToken {
+                    text: format!(":{}:", emoji.name),
+                    emoji: Some((*emoji).clone().into()),
+                });
+                rest = &after[end + 1..];
+            }
+            None => {
+                text.push_str(&rest[..=start]);
+                rest = after;
+            }
+        }
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        tokens.push(ContentToken { text, emoji: None });
+    }
+
+    tokens
+}
@@ -1,9 +1,20 @@
 use crate::{
+    config,
     error::RtwalkError,
-    gql::PageInfo,
-    models::{forum::DBForum, Key},
+    gql::{members, moderators, PageInfo},
+    models::{
+        file::{File, FileOps},
+        forum::{DBForum, DBForumTransfer, ForumVisibility},
+        moderator::{DBModerator, ModPermission},
+        modlog::{DBModLogEntry, ModAction},
+        user::User,
+        Key,
+    },
     state::State,
 };
+use chrono::{DateTime, TimeDelta, Utc};
+use rustis::commands::{GenericCommands, SetCondition, SetExpiration, StringCommands};
+use serde::Serialize;
 use surrealdb::RecordId;
 
 use super::resolvers::forums::{ForumSelectCriteria, MultipleForumSelectCriteria};
@@ -49,137 +60,577 @@ pub async fn fetch_forum(
         }
     };
 
-    Ok(forum)
+    // Deleted forums are hidden until they're restored or purged.
+    Ok(forum.filter(|f| f.deleted_at.is_none()))
+}
+
+/// SurrealQL array of `$forum` and the parents above it, `NONE` past the top.
+pub fn ancestor_chain() -> String {
+    let mut paths = vec!["$forum".to_string()];
+    for _ in 1..config::MAX_FORUM_DEPTH {
+        paths.push(format!("{}.parent", paths.last().expect("Not empty")));
+    }
+
+    format!("[{}]", paths.join(", "))
 }
 
+/// Parents of the forum, nearest first, fetched in a single query. Deleted parents are
+/// still included.
+pub async fn fetch_ancestors(state: &State, forum: &DBForum) -> Result<Vec<DBForum>, RtwalkError> {
+    let Some(parent) = forum.parent.clone() else {
+        return Ok(vec![]);
+    };
+
+    let mut res = state
+        .db
+        .query(format!(
+            "SELECT * FROM {} WHERE id != NONE",
+            ancestor_chain()
+        ))
+        .bind(("forum", parent.clone()))
+        .await?;
+    let fetched: Vec<DBForum> = res.take(0)?;
+
+    // Walk the links instead of trusting the result order.
+    let mut ancestors: Vec<DBForum> = vec![];
+    let mut next = Some(parent);
+    while let Some(id) = next.take() {
+        let Some(forum) = fetched.iter().find(|f| f.id == id) else {
+            break;
+        };
+        next = forum.parent.clone();
+        ancestors.push(forum.clone());
+    }
+
+    Ok(ancestors)
+}
+
+/// Ids of every forum below this one, level by level. Deleted forums and their
+/// children are left out.
+pub async fn fetch_descendant_ids(
+    state: &State,
+    forum: &RecordId,
+) -> Result<Vec<Vec<RecordId>>, RtwalkError> {
+    let mut levels: Vec<Vec<RecordId>> = vec![];
+    let mut current = vec![forum.clone()];
+
+    while levels.len() < config::MAX_FORUM_DEPTH {
+        let mut res = state
+            .db
+            .query("SELECT VALUE id FROM forum WHERE parent IN $parents AND deleted_at = NONE")
+            .bind(("parents", current))
+            .await?;
+        let children: Vec<RecordId> = res.take(0)?;
+        if children.is_empty() {
+            break;
+        }
+        current = children.clone();
+        levels.push(children);
+    }
+
+    Ok(levels)
+}
+
+/// Moves the forum under `parent`, or to the top level. The parent can't be the forum
+/// itself or one of its sub-forums, and the tree can't get deeper than
+/// [`config::MAX_FORUM_DEPTH`].
+pub async fn set_parent(
+    state: &State,
+    forum: &DBForum,
+    parent: Option<&DBForum>,
+) -> Result<DBForum, RtwalkError> {
+    if let Some(parent) = parent {
+        if parent.id == forum.id || parent.deleted_at.is_some() {
+            return Err(RtwalkError::InvalidForumParent);
+        }
+        let ancestors = fetch_ancestors(state, parent).await?;
+        if ancestors.iter().any(|a| a.id == forum.id) {
+            return Err(RtwalkError::InvalidForumParent);
+        }
+        let subtree_height = fetch_descendant_ids(state, &forum.id).await?.len();
+        // Parent and its ancestors, the forum, then its sub-forums.
+        if ancestors.len() + 2 + subtree_height > config::MAX_FORUM_DEPTH {
+            return Err(RtwalkError::InvalidForumParent);
+        }
+    }
+
+    let mut res = state
+        .db
+        .query("UPDATE $forum SET parent = $parent")
+        .bind(("forum", forum.id.clone()))
+        .bind(("parent", parent.map(|p| p.id.clone())))
+        .await?;
+    let updated: Option<DBForum> = res.take(0)?;
+
+    updated.ok_or(RtwalkError::ForumNotFound)
+}
+
+/// SurrealQL condition that's true when the viewer can read the forum at `forum`, a field
+/// holding a forum record or `id` when selecting from the forum table. Expects the
+/// query to be bound to the viewer's [`ViewerAccess`].
+pub fn visibility_condition(forum: &str, viewer: Option<&User>) -> String {
+    if viewer.is_some_and(|v| v.admin) {
+        return "true".to_string();
+    }
+
+    let prefix = if forum == "id" {
+        String::new()
+    } else {
+        format!("{forum}.")
+    };
+
+    format!(
+        "({prefix}settings.visibility != 'Private' OR {prefix}owner = $viewer \
+        OR {forum} IN $readable)"
+    )
+}
+
+/// SurrealQL condition that hides removed and held posts or comments from everyone but
+/// their author and the forum's moderators. `author` is the field holding the author,
+/// `forum` is like in [`visibility_condition`].
+pub fn moderation_condition(author: &str, forum: &str, viewer: Option<&User>) -> String {
+    if viewer.is_some_and(|v| v.admin) {
+        return "true".to_string();
+    }
+
+    format!(
+        "((removed != true AND held != true) OR {author} = $viewer OR {forum}.owner = $viewer \
+        OR {forum} IN $moderated)"
+    )
+}
+
+/// Forums the viewer is a member or moderator of, bound as `$viewer`, `$readable` and
+/// `$moderated` for [`visibility_condition`] and [`moderation_condition`]. Looked up once
+/// per query instead of once per row. Owned forums and sub-forums that inherit moderators
+/// count as moderated.
+#[derive(Serialize, Default)]
+pub struct ViewerAccess {
+    viewer: Option<RecordId>,
+    readable: Vec<RecordId>,
+    moderated: Vec<RecordId>,
+}
+
+pub async fn viewer_access(
+    state: &State,
+    viewer: Option<&User>,
+) -> Result<ViewerAccess, RtwalkError> {
+    // Admins pass both conditions without looking at the bindings.
+    let Some(viewer) = viewer.filter(|v| !v.admin) else {
+        return Ok(ViewerAccess::default());
+    };
+    let viewer = RecordId::from_table_key("user", viewer.id.0.clone());
+
+    let mut res = state
+        .db
+        .query("SELECT VALUE out FROM moderates WHERE in = $viewer")
+        .query("SELECT VALUE id FROM forum WHERE owner = $viewer")
+        .query("SELECT VALUE out FROM member_of WHERE in = $viewer")
+        .bind(("viewer", viewer.clone()))
+        .await?;
+    let mut moderated: Vec<RecordId> = res.take(0)?;
+    let owned: Vec<RecordId> = res.take(1)?;
+    moderated.extend(owned);
+
+    // Sub-forums that inherit moderators, the same rule `moderators::fetch_permissions`
+    // follows upwards.
+    let mut frontier = moderated.clone();
+    for _ in 1..config::MAX_FORUM_DEPTH {
+        if frontier.is_empty() {
+            break;
+        }
+        let mut children = state
+            .db
+            .query(
+                "SELECT VALUE id FROM forum WHERE parent IN $parents \
+                AND settings.inherit_moderators != false",
+            )
+            .bind(("parents", frontier))
+            .await?;
+        frontier = children.take::<Vec<RecordId>>(0)?;
+        frontier.retain(|f| !moderated.contains(f));
+        moderated.extend(frontier.iter().cloned());
+    }
+
+    let mut readable: Vec<RecordId> = res.take(2)?;
+    readable.extend(moderated.iter().cloned());
+
+    Ok(ViewerAccess {
+        viewer: Some(viewer),
+        readable,
+        moderated,
+    })
+}
+
+/// Private forums can only be read by their members and moderators.
+pub async fn can_read(
+    state: &State,
+    viewer: Option<&User>,
+    forum: &DBForum,
+) -> Result<bool, RtwalkError> {
+    if forum.settings.visibility != ForumVisibility::Private {
+        return Ok(true);
+    }
+    let Some(viewer) = viewer else {
+        return Ok(false);
+    };
+
+    Ok(members::is_member(
+        state,
+        &forum.id,
+        &RecordId::from_table_key("user", viewer.id.0.clone()),
+    )
+    .await?
+        || moderators::fetch_permissions(state, viewer, &forum.id)
+            .await?
+            .is_some())
+}
+
+/// Private forums the viewer can't read are left out.
 pub async fn fetch_forums(
     state: &State,
     criteria: MultipleForumSelectCriteria,
+    viewer: Option<&User>,
     page_info: &PageInfo,
 ) -> Result<Vec<DBForum>, RtwalkError> {
-    let forums: Vec<DBForum> = match criteria {
-        MultipleForumSelectCriteria::Ids(ids) => {
-            let mut query = state
-                .db
-                .query("SELECT * FROM $ids LIMIT $limit START $start");
+    let visible = visibility_condition("id", viewer);
+    let mut conditions = vec!["deleted_at = NONE", visible.as_str()];
+    let mut ids = vec![];
+    let mut names = vec![];
+    let mut parent = None;
+    let mut category = None;
+    let mut search = None;
 
-            if page_info.needs_page_info {
-                query = query.query("SELECT count() as total FROM $ids");
+    let from = match criteria {
+        MultipleForumSelectCriteria::Ids(keys) => {
+            ids = keys
+                .into_iter()
+                .map(|x| RecordId::from_table_key("forum", x))
+                .collect();
+            "$ids"
+        }
+        MultipleForumSelectCriteria::Names(n) => {
+            conditions.push("name IN $names");
+            names = n;
+            "forum"
+        }
+        MultipleForumSelectCriteria::Parent(key) => {
+            conditions.push("parent = $parent");
+            parent = Some(RecordId::from_table_key("forum", key));
+            "forum"
+        }
+        MultipleForumSelectCriteria::Category(key) => {
+            conditions.push("category = $category");
+            category = Some(RecordId::from_table_key("category", key));
+            "forum"
+        }
+        MultipleForumSelectCriteria::Search(query) => {
+            if query != "*" {
+                conditions
+                    .push("(name @0@ $query OR display_name @1@ $query OR description @2@ $query)");
+                search = Some(query);
             }
+            "forum"
+        }
+    };
 
-            let mut res = query
-                .bind((
-                    "ids",
-                    ids.into_iter()
-                        .map(|x| RecordId::from_table_key("forum", x))
-                        .collect::<Vec<_>>(),
-                ))
-                .bind(("limit", page_info.per_page))
-                .bind(("start", (page_info.page - 1) * page_info.per_page))
-                .await?;
+    let where_clause = conditions.join(" AND ");
+    // Results of `$ids` keep the order they were asked for.
+    let order = if from == "$ids" {
+        ""
+    } else {
+        " ORDER BY created_at ASC"
+    };
 
-            if page_info.needs_page_info {
-                if let Some(total) = res.take((1, "total"))? {
-                    page_info
-                        .total
-                        .0
-                        .store(total, std::sync::atomic::Ordering::Relaxed);
-                    page_info.has_next_page.0.store(
-                        total > (page_info.page - 1) * page_info.per_page + page_info.per_page,
-                        std::sync::atomic::Ordering::Relaxed,
-                    );
-                }
-            }
+    let mut query = state.db.query(format!(
+        "SELECT * FROM {from} WHERE {where_clause}{order} LIMIT $limit START $start"
+    ));
 
-            res.take(0)?
+    if page_info.needs_page_info {
+        query = query.query(format!(
+            "SELECT count() as total FROM {from} WHERE {where_clause} GROUP ALL"
+        ));
+    }
+
+    let mut res = query
+        .bind(("ids", ids))
+        .bind(("names", names))
+        .bind(("parent", parent))
+        .bind(("category", category))
+        .bind(("query", search))
+        .bind(viewer_access(state, viewer).await?)
+        .bind(("limit", page_info.per_page))
+        .bind((
+            "start",
+            (page_info.page - 1).saturating_mul(page_info.per_page),
+        ))
+        .await?;
+
+    if page_info.needs_page_info {
+        if let Some(total) = res.take((1, "total"))? {
+            page_info.set_total(total);
         }
-        MultipleForumSelectCriteria::Names(names) => {
-            let mut query = state
-                .db
-                .query("SELECT * FROM forum WHERE name IN $names LIMIT $limit START $start");
+    }
 
-            if page_info.needs_page_info {
-                query = query.query("SELECT count() as total FROM forum WHERE name IN $names");
-            }
+    Ok(res.take(0)?)
+}
 
-            let mut res = query
-                .bind(("names", names))
-                .bind(("limit", page_info.per_page))
-                .bind(("start", (page_info.page - 1) * page_info.per_page))
-                .await?;
+/// For actions that cite a rule, the rule has to be one of the forum's.
+pub async fn ensure_rule_exists(
+    state: &State,
+    forum: &RecordId,
+    rule: &str,
+) -> Result<(), RtwalkError> {
+    let forum: Option<DBForum> = state.db.select(forum).await?;
 
-            if page_info.needs_page_info {
-                if let Some(total) = res.take((1, "total"))? {
-                    page_info
-                        .total
-                        .0
-                        .store(total, std::sync::atomic::Ordering::Relaxed);
-                    page_info.has_next_page.0.store(
-                        total > (page_info.page - 1) * page_info.per_page + page_info.per_page,
-                        std::sync::atomic::Ordering::Relaxed,
-                    );
-                }
-            }
-            res.take(0)?
-        }
-        MultipleForumSelectCriteria::Search(search) => match search.as_str() {
-            "*" => {
-                let mut query = state
-                    .db
-                    .query("SELECT * FROM forum ORDER BY created_at ASC LIMIT $limit START $start");
-                if page_info.needs_page_info {
-                    query = query.query("SELECT count() as total FROM forum")
-                }
-                let mut res = query
-                    .bind(("limit", page_info.per_page))
-                    .bind(("start", (page_info.page - 1) * page_info.per_page))
-                    .await?;
-
-                if page_info.needs_page_info {
-                    if let Some(total) = res.take((1, "total"))? {
-                        page_info
-                            .total
-                            .0
-                            .store(total, std::sync::atomic::Ordering::Relaxed);
-                        page_info.has_next_page.0.store(
-                            total > (page_info.page - 1) * page_info.per_page + page_info.per_page,
-                            std::sync::atomic::Ordering::Relaxed,
-                        );
-                    }
-                }
-
-                res.take(0)?
-            }
-            _ => {
-                let mut query = state
-                .db
-                .query("SELECT * FROM forum WHERE name @0@ $query OR display_name @1@ $query OR description @2@ $query ORDER BY created_at ASC LIMIT $limit START $start");
-                if page_info.needs_page_info {
-                    query = query.query("SELECT count() as total FROM forum WHERE name @0@ $query OR display_name @1@ $query OR description @2@ $query")
-                }
-                let mut res = query
-                    .bind(("query", search))
-                    .bind(("limit", page_info.per_page))
-                    .bind(("start", (page_info.page - 1) * page_info.per_page))
-                    .await?;
-
-                if page_info.needs_page_info {
-                    if let Some(total) = res.take((1, "total"))? {
-                        page_info
-                            .total
-                            .0
-                            .store(total, std::sync::atomic::Ordering::Relaxed);
-                        page_info.has_next_page.0.store(
-                            total > (page_info.page - 1) * page_info.per_page + page_info.per_page,
-                            std::sync::atomic::Ordering::Relaxed,
-                        );
-                    }
-                }
-
-                res.take(0)?
-            }
-        },
+    match forum {
+        Some(forum) if forum.rule(rule).is_some() => Ok(()),
+        Some(_) => Err(RtwalkError::RuleNotFound),
+        None => Err(RtwalkError::ForumNotFound),
+    }
+}
+
+fn transfer_key(forum: &RecordId) -> String {
+    format!("forum_transfer:{}", forum.key())
+}
+
+/// Offers the forum to `to`, replacing any earlier offer. Nothing changes until they accept.
+pub async fn start_transfer(
+    state: &State,
+    forum: &DBForum,
+    to: Key,
+) -> Result<DBForumTransfer, RtwalkError> {
+    if forum.owner.key() == &to.0 {
+        return Err(RtwalkError::InvalidOwnershipTransfer);
+    }
+
+    let mut res = state
+        .db
+        .query("SELECT VALUE bot FROM ONLY $user")
+        .bind(("user", RecordId::from_table_key("user", to.0.clone())))
+        .await?;
+    let bot: Option<bool> = res.take(0)?;
+    match bot {
+        None => return Err(RtwalkError::UserNotFound),
+        // Bots can moderate but not own forums.
+        Some(true) => return Err(RtwalkError::InvalidOwnershipTransfer),
+        Some(false) => {}
+    }
+
+    let transfer = DBForumTransfer {
+        forum: forum.id.key().to_string(),
+        from: forum.owner.key().to_string(),
+        to: to.to_string(),
+        expires_at: Utc::now() + TimeDelta::seconds(config::FORUM_TRANSFER_EXPIERY_SECONDS as i64),
+    };
+
+    state
+        .redis
+        .set_with_options(
+            transfer_key(&forum.id),
+            serde_json::to_string(&transfer).map_err(|e| {
+                RtwalkError::ImpossibleError(
+                    "Serialization of DBForumTransfer can't fail",
+                    Some(e.into()),
+                )
+            })?,
+            SetCondition::None,
+            SetExpiration::Ex(config::FORUM_TRANSFER_EXPIERY_SECONDS),
+            false,
+        )
+        .await?;
+
+    Ok(transfer)
+}
+
+pub async fn fetch_transfer(
+    state: &State,
+    forum: &RecordId,
+) -> Result<Option<DBForumTransfer>, RtwalkError> {
+    let transfer: Option<String> = state.redis.get(transfer_key(forum)).await?;
+    let Some(transfer) = transfer else {
+        return Ok(None);
     };
 
-    Ok(forums)
+    serde_json::from_str(&transfer).map(Some).map_err(|e| {
+        RtwalkError::ImpossibleError(
+            "Deserialization of DBForumTransfer can't fail",
+            Some(e.into()),
+        )
+    })
+}
+
+/// Returns false if there was no pending transfer.
+pub async fn cancel_transfer(state: &State, forum: &RecordId) -> Result<bool, RtwalkError> {
+    let removed: usize = state.redis.del(transfer_key(forum)).await?;
+
+    Ok(removed > 0)
+}
+
+/// Makes `user` the owner if the forum was offered to them. The previous owner
+/// stays on as a moderator with every permission.
+pub async fn accept_transfer(
+    state: &State,
+    mut forum: DBForum,
+    user: &Key,
+) -> Result<DBForum, RtwalkError> {
+    let transfer = fetch_transfer(state, &forum.id)
+        .await?
+        .ok_or(RtwalkError::NoPendingTransfer)?;
+
+    // The offer is stale if ownership changed some other way since it was made.
+    if transfer.to != user.to_string() || transfer.from != forum.owner.key().to_string() {
+        return Err(RtwalkError::NoPendingTransfer);
+    }
+    // Whoever deletes the key gets to accept it.
+    if !cancel_transfer(state, &forum.id).await? {
+        return Err(RtwalkError::NoPendingTransfer);
+    }
+
+    let previous_owner = Key(forum.owner.key().to_owned());
+    let forum_key = Key(forum.id.key().to_owned());
+    let moderator = DBModerator::new(
+        previous_owner.clone(),
+        forum_key,
+        previous_owner.clone(),
+        ModPermission::ALL.to_vec(),
+    );
+    let new_owner = RecordId::from_table_key("user", user.0.clone());
+    let entry = DBModLogEntry::new(
+        forum.id.clone(),
+        previous_owner,
+        ModAction::TransferOwnership,
+        Some(new_owner.clone()),
+        None,
+    );
+
+    state
+        .db
+        .query("BEGIN TRANSACTION")
+        .query("UPDATE $forum SET owner = $new_owner")
+        // The owner has every permission anyway, and can't be banned.
+        .query("DELETE moderates WHERE in = $new_owner AND out = $forum")
+        .query("DELETE forum_ban WHERE user = $new_owner AND forum = $forum")
+        .query("INSERT RELATION INTO moderates $moderator")
+        .query("CREATE mod_log CONTENT $entry")
+        .query("COMMIT TRANSACTION")
+        .bind(("forum", forum.id.clone()))
+        .bind(("new_owner", new_owner.clone()))
+        .bind(("moderator", moderator))
+        .bind(("entry", entry))
+        .await?
+        .check()?;
+
+    forum.owner = new_owner;
+
+    Ok(forum)
+}
+
+/// Hides the forum, it's purged for good once `FORUM_DELETION_GRACE_SECONDS` have passed
+/// unless it's restored before that. Cancels any pending ownership transfer.
+pub async fn delete_forum(state: &State, mut forum: DBForum) -> Result<DBForum, RtwalkError> {
+    forum.deleted_at = Some(Utc::now());
+
+    let res: Option<DBForum> = state.db.update(&forum.id).content(forum).await?;
+    let forum = res.ok_or(RtwalkError::ForumNotFound)?;
+
+    cancel_transfer(state, &forum.id).await?;
+
+    Ok(forum)
+}
+
+pub async fn restore_forum(state: &State, mut forum: DBForum) -> Result<DBForum, RtwalkError> {
+    match forum.deleted_at {
+        None => return Ok(forum),
+        // Too late, the purge job is about to pick it up.
+        Some(deleted_at) if is_purgeable(deleted_at) => return Err(RtwalkError::ForumNotFound),
+        Some(_) => {}
+    }
+
+    forum.deleted_at = None;
+
+    let res: Option<DBForum> = state.db.update(&forum.id).content(forum).await?;
+
+    res.ok_or(RtwalkError::ForumNotFound)
+}
+
+fn is_purgeable(deleted_at: DateTime<Utc>) -> bool {
+    deleted_at + TimeDelta::seconds(config::FORUM_DELETION_GRACE_SECONDS as i64) <= Utc::now()
+}
+
+/// Deleted forums whose grace period is over.
+pub async fn fetch_purgeable_forums(state: &State) -> Result<Vec<DBForum>, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT * FROM forum WHERE deleted_at != NONE")
+        .await?;
+
+    let forums: Vec<DBForum> = res.take(0)?;
+
+    Ok(forums
+        .into_iter()
+        .filter(|f| f.deleted_at.is_some_and(is_purgeable))
+        .collect())
+}
+
+/// Removes the forum with everything in it, including its files.
+/// Safe to run again if it fails halfway.
+pub async fn purge_forum(state: &State, forum: &DBForum) -> Result<(), RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT VALUE attachments FROM post WHERE forum = $forum")
+        .query("SELECT VALUE attachments FROM comment WHERE post.forum = $forum")
+        .query("SELECT VALUE attachments FROM revision WHERE forum = $forum")
+        .bind(("forum", forum.id.clone()))
+        .await?;
+
+    let post_attachments: Vec<Vec<File>> = res.take(0)?;
+    let comment_attachments: Vec<Vec<File>> = res.take(1)?;
+    let revision_attachments: Vec<Vec<File>> = res.take(2)?;
+
+    let mut attachments: Vec<File> = post_attachments
+        .into_iter()
+        .chain(comment_attachments)
+        .chain(revision_attachments)
+        .flatten()
+        .collect();
+    // Revisions share files with each other and the current version.
+    attachments.sort_by(|a, b| a.loc.cmp(&b.loc));
+    attachments.dedup();
+    for attachment in attachments {
+        attachment.delete(&state.op).await?;
+    }
+    // Icons, banners and emoji.
+    state.op.remove_all(&format!("{}/", forum.id)).await?;
+
+    state
+        .db
+        .query("BEGIN TRANSACTION")
+        .query("DELETE revision WHERE forum = $forum")
+        .query("DELETE comment WHERE post.forum = $forum")
+        .query("DELETE vote WHERE out.forum = $forum")
+        .query("DELETE post WHERE forum = $forum")
+        .query("DELETE moderates WHERE out = $forum")
+        .query("DELETE member_of WHERE out = $forum")
+        .query("DELETE forum_join_request WHERE forum = $forum")
+        .query("DELETE forum_ban WHERE forum = $forum")
+        .query("DELETE mod_log WHERE forum = $forum")
+        .query("DELETE $automod")
+        .query("DELETE wiki_revision WHERE page.forum = $forum")
+        .query("DELETE wiki_page WHERE forum = $forum")
+        .query("DELETE emoji WHERE forum = $forum")
+        .query("DELETE modmail_message WHERE thread.forum = $forum")
+        .query("DELETE modmail_thread WHERE forum = $forum")
+        // Sub-forums outlive their parent as top level forums.
+        .query("UPDATE forum SET parent = NONE WHERE parent = $forum")
+        .query("DELETE $forum")
+        .query("COMMIT TRANSACTION")
+        .bind(("forum", forum.id.clone()))
+        .bind((
+            "automod",
+            RecordId::from_table_key("automod", forum.id.key().to_owned()),
+        ))
+        .await?
+        .check()?;
+
+    Ok(())
 }
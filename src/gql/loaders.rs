@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use async_graphql::{dataloader::Loader, ErrorExtensions};
+use surrealdb::RecordId;
+
+use crate::{
+    gql::{emojis, posts, votes},
+    models::emoji::DBEmoji,
+    state::State,
+};
+
+/// Custom emoji by forum, forums without any map to an empty list.
+pub struct EmojiLoader(pub State);
+
+impl Loader<RecordId> for EmojiLoader {
+    type Value = Vec<DBEmoji>;
+    type Error = async_graphql::Error;
+
+    // Record ids are never mutated while they're keys.
+    #[allow(clippy::mutable_key_type)]
+    async fn load(&self, keys: &[RecordId]) -> Result<HashMap<RecordId, Self::Value>, Self::Error> {
+        let emojis = emojis::fetch_emojis_of(&self.0, keys)
+            .await
+            .map_err(|e| e.extend())?;
+
+        let mut by_forum: HashMap<RecordId, Self::Value> =
+            keys.iter().map(|k| (k.clone(), vec![])).collect();
+        for emoji in emojis {
+            by_forum.entry(emoji.forum.clone()).or_default().push(emoji);
+        }
+
+        Ok(by_forum)
+    }
+}
+
+/// Forum of a post.
+pub struct PostForumLoader(pub State);
+
+impl Loader<RecordId> for PostForumLoader {
+    type Value = RecordId;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[RecordId]) -> Result<HashMap<RecordId, Self::Value>, Self::Error> {
+        let forums = posts::post_forums(&self.0, keys)
+            .await
+            .map_err(|e| e.extend())?;
+
+        Ok(forums.into_iter().collect())
+    }
+}
+
+/// Vote value of a `(user, post)` pair, missing if the user didn't vote.
+pub struct VoteLoader(pub State);
+
+impl Loader<(RecordId, RecordId)> for VoteLoader {
+    type Value = i64;
+    type Error = async_graphql::Error;
+
+    async fn load(
+        &self,
+        keys: &[(RecordId, RecordId)],
+    ) -> Result<HashMap<(RecordId, RecordId), Self::Value>, Self::Error> {
+        let users: Vec<RecordId> = keys.iter().map(|(u, _)| u.clone()).collect();
+        let posts: Vec<RecordId> = keys.iter().map(|(_, p)| p.clone()).collect();
+        let votes = votes::fetch_votes(&self.0, &users, &posts)
+            .await
+            .map_err(|e| e.extend())?;
+
+        Ok(votes
+            .into_iter()
+            .map(|v| ((v.user, v.post), v.value))
+            .filter(|(k, _)| keys.contains(k))
+            .collect())
+    }
+}
@@ -0,0 +1,198 @@
+use surrealdb::RecordId;
+
+use crate::{
+    error::RtwalkError,
+    models::{
+        forum::DBForum,
+        member::{DBJoinRequest, DBMember},
+        Key,
+    },
+    state::State,
+};
+
+/// Returns false if the user was already a member.
+pub async fn join_forum(state: &State, forum: &DBForum, user: Key) -> Result<bool, RtwalkError> {
+    let member = DBMember::new(user, Key(forum.id.key().to_owned()));
+
+    if is_member(state, &member.forum, &member.user).await? {
+        return Ok(false);
+    }
+
+    state
+        .db
+        .query("BEGIN TRANSACTION")
+        .query("INSERT RELATION INTO member_of $member")
+        .query("UPDATE $forum SET member_count += 1")
+        .query("COMMIT TRANSACTION")
+        .bind(("forum", member.forum.clone()))
+        .bind(("member", member))
+        .await?
+        .check()?;
+
+    Ok(true)
+}
+
+/// Returns false if the user wasn't a member. Also withdraws a pending join request.
+pub async fn leave_forum(state: &State, forum: &RecordId, user: Key) -> Result<bool, RtwalkError> {
+    let user = RecordId::from_table_key("user", user.0);
+    let was_member = is_member(state, forum, &user).await?;
+
+    // The count goes down by what was actually deleted, so concurrent leaves can't make
+    // it drift.
+    state
+        .db
+        .query("BEGIN TRANSACTION")
+        .query("LET $removed = (DELETE member_of WHERE in = $user AND out = $forum RETURN BEFORE)")
+        .query("IF $removed != [] { UPDATE $forum SET member_count -= array::len($removed) }")
+        .query("DELETE forum_join_request WHERE user = $user AND forum = $forum")
+        .query("COMMIT TRANSACTION")
+        .bind(("user", user))
+        .bind(("forum", forum.clone()))
+        .await?
+        .check()?;
+
+    Ok(was_member)
+}
+
+pub async fn is_member(
+    state: &State,
+    forum: &RecordId,
+    user: &RecordId,
+) -> Result<bool, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT 1 FROM member_of WHERE in = $user AND out = $forum")
+        .bind(("user", user.clone()))
+        .bind(("forum", forum.clone()))
+        .await?;
+
+    let member: Option<u64> = res.take((0, "1"))?;
+
+    Ok(member.is_some())
+}
+
+pub async fn fetch_members(
+    state: &State,
+    forum: &RecordId,
+    page: u32,
+    per_page: u32,
+) -> Result<Vec<DBMember>, RtwalkError> {
+    let mut res = state
+        .db
+        .query(
+            "SELECT * FROM member_of WHERE out = $forum ORDER BY created_at ASC LIMIT $limit START $start",
+        )
+        .bind(("forum", forum.clone()))
+        .bind(("limit", per_page))
+        .bind(("start", (page.max(1) - 1).saturating_mul(per_page)))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+/// Most recently joined first, deleted forums are left out.
+pub async fn fetch_joined_forums(
+    state: &State,
+    user: &Key,
+    page: u32,
+    per_page: u32,
+) -> Result<Vec<DBForum>, RtwalkError> {
+    let mut res = state
+        .db
+        .query(
+            "SELECT * FROM member_of WHERE in = $user AND out.deleted_at = NONE ORDER BY created_at DESC LIMIT $limit START $start",
+        )
+        .bind(("user", RecordId::from_table_key("user", user.0.clone())))
+        .bind(("limit", per_page))
+        .bind(("start", (page.max(1) - 1).saturating_mul(per_page)))
+        .await?;
+
+    let memberships: Vec<DBMember> = res.take(0)?;
+
+    let mut res = state
+        .db
+        .query("SELECT * FROM $forums")
+        .bind((
+            "forums",
+            memberships.into_iter().map(|m| m.forum).collect::<Vec<_>>(),
+        ))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+/// Returns the existing request if the user already asked to join.
+pub async fn request_to_join(
+    state: &State,
+    forum: &DBForum,
+    user: Key,
+    message: Option<String>,
+) -> Result<DBJoinRequest, RtwalkError> {
+    if let Some(request) = fetch_join_request(state, &forum.id, &user).await? {
+        return Ok(request);
+    }
+
+    let request = DBJoinRequest::new(user, Key(forum.id.key().to_owned()), message);
+
+    state
+        .db
+        .query("CREATE forum_join_request CONTENT $request")
+        .bind(("request", request.clone()))
+        .await?
+        .check()?;
+
+    Ok(request)
+}
+
+pub async fn fetch_join_request(
+    state: &State,
+    forum: &RecordId,
+    user: &Key,
+) -> Result<Option<DBJoinRequest>, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT * FROM forum_join_request WHERE user = $user AND forum = $forum")
+        .bind(("user", RecordId::from_table_key("user", user.0.clone())))
+        .bind(("forum", forum.clone()))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+/// Oldest requests first.
+pub async fn fetch_join_requests(
+    state: &State,
+    forum: &RecordId,
+    page: u32,
+    per_page: u32,
+) -> Result<Vec<DBJoinRequest>, RtwalkError> {
+    let mut res = state
+        .db
+        .query(
+            "SELECT * FROM forum_join_request WHERE forum = $forum ORDER BY created_at ASC LIMIT $limit START $start",
+        )
+        .bind(("forum", forum.clone()))
+        .bind(("limit", per_page))
+        .bind(("start", (page.max(1) - 1).saturating_mul(per_page)))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+/// Removes the request, returns false if there was none.
+pub async fn remove_join_request(
+    state: &State,
+    forum: &RecordId,
+    user: &Key,
+) -> Result<bool, RtwalkError> {
+    let mut res = state
+        .db
+        .query("DELETE forum_join_request WHERE user = $user AND forum = $forum RETURN BEFORE")
+        .bind(("user", RecordId::from_table_key("user", user.0.clone())))
+        .bind(("forum", forum.clone()))
+        .await?;
+
+    let removed: Vec<DBJoinRequest> = res.take(0)?;
+
+    Ok(!removed.is_empty())
+}
@@ -2,24 +2,47 @@ use std::sync::atomic::{AtomicBool, AtomicU32};
 
 use crate::{
     error::{Result, RtwalkError},
-    models::RtEvent,
-    state::Auth,
+    models::{
+        forum::DBForum, moderator::ModPermission, modmail::ModmailThread, user::User, Key, RtEvent,
+        RtEventData, RtEventType,
+    },
+    state::{Auth, State},
 };
 use async_graphql::{
-    scalar, Context, ErrorExtensions, Guard, MergedObject, Object, ResultExt, SimpleObject,
-    Subscription,
+    scalar, Context, ErrorExtensions, Guard, InputObject, MergedObject, Object, ResultExt,
+    SimpleObject, Subscription,
 };
 use async_stream::stream;
 use bytes::Buf;
 use futures::{Stream, StreamExt};
-use rustis::commands::{PubSubCommands, StringCommands};
+use rustis::{
+    client::ClientPreparedCommand,
+    commands::{PubSubCommands, StringCommands},
+};
 use serde_json;
+use surrealdb::RecordId;
+use tracing::error;
 
+pub mod automod;
+pub mod bans;
+pub mod categories;
+pub mod challenges;
 pub mod comments;
+pub mod email_domains;
+pub mod emojis;
 pub mod forums;
+pub mod loaders;
+pub mod members;
+pub mod moderators;
+pub mod modlog;
+pub mod modmail;
 pub mod posts;
 pub mod resolvers;
+pub mod revisions;
+pub mod stats;
 pub mod users;
+pub mod votes;
+pub mod wiki;
 
 macro_rules! state {
     ($ctx: expr) => {{
@@ -72,39 +95,51 @@ enum Role {
     Admin,           // Only admin
 }
 
+/// Resolves the user of the current session, if any. Unlike [`Role`] this never fails
+/// the request for a missing session, use it on fields that work for everyone but
+/// behave differently for logged in users.
+pub(crate) async fn viewer(ctx: &Context<'_>) -> Result<Option<crate::models::user::User>> {
+    let state = state!(ctx);
+    let Some(cookeis) = ctx.data_opt::<tower_cookies::Cookies>() else {
+        return Ok(None);
+    };
+    let jar = cookeis.signed(&state.cookie_key);
+    let token = jar.get("session");
+    if let Some(token) = token {
+        let user: Option<String> = state
+            .redis
+            .get(format!("auth_session:{}", token.value()))
+            .await
+            .map_err(RtwalkError::RedisError)
+            .extend_err(|_, _| {})?;
+        if let Some(user) = user {
+            let user: crate::models::user::User = serde_json::from_str(&user)
+                .map_err(|e| {
+                    RtwalkError::ImpossibleError(
+                        "Deserialization of User can't fail",
+                        Some(e.into()),
+                    )
+                })
+                .extend_err(|_, _| {})?;
+            return Ok(Some(user));
+        }
+    }
+    Ok(None)
+}
+
 impl Guard for Role {
     async fn check(&self, ctx: &Context<'_>) -> Result<()> {
-        let state = state!(ctx);
-        let cookeis = ctx.data_unchecked::<tower_cookies::Cookies>();
-        let jar = cookeis.signed(&state.cookie_key);
-        let token = jar.get("session");
-        if let Some(token) = token {
-            let user: Option<String> = state
-                .redis
-                .get(format!("auth_session:{}", token.value()))
-                .await
-                .map_err(|e| RtwalkError::RedisError(e))
-                .extend_err(|_, _| {})?;
-            if let Some(user) = user {
-                let user: crate::models::user::User = serde_json::from_str(&user)
-                    .map_err(|e| {
-                        RtwalkError::ImpossibleError(
-                            "Deserialization of User can't fail",
-                            Some(e.into()),
-                        )
-                    })
-                    .extend_err(|_, _| {})?;
-                let permitted = match self {
-                    Self::Admin => user.admin,
-                    Self::Bot => user.bot,
-                    Self::Human => !user.bot,
-                    Self::Authenticated => true,
-                    Self::UnAuthenticated => false,
-                };
-                if permitted {
-                    *ctx.data_unchecked::<Auth>().0.lock().unwrap() = Some(user);
-                    return Ok(());
-                }
+        if let Some(user) = viewer(ctx).await? {
+            let permitted = match self {
+                Self::Admin => user.admin,
+                Self::Bot => user.bot,
+                Self::Human => !user.bot,
+                Self::Authenticated => true,
+                Self::UnAuthenticated => false,
+            };
+            if permitted {
+                *ctx.data_unchecked::<Auth>().0.lock().unwrap() = Some(user);
+                return Ok(());
             }
         }
         if *self == Self::UnAuthenticated {
@@ -116,6 +151,38 @@ impl Guard for Role {
     }
 }
 
+/// Only lets users with `permission` in `forum` through, the forum owner and admins
+/// have every permission. Use [`moderators::ensure_permission`] when the forum isn't an argument.
+struct ForumGuard {
+    forum: Key,
+    permission: ModPermission,
+}
+
+impl ForumGuard {
+    fn new(forum: Key, permission: ModPermission) -> Self {
+        Self { forum, permission }
+    }
+}
+
+impl Guard for ForumGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        let state = state!(ctx);
+        if let Some(user) = viewer(ctx).await? {
+            moderators::ensure_permission(
+                state,
+                &user,
+                &RecordId::from_table_key("forum", self.forum.0.clone()),
+                self.permission,
+            )
+            .await
+            .extend_err(|_, _| {})?;
+            *ctx.data_unchecked::<Auth>().0.lock().unwrap() = Some(user);
+            return Ok(());
+        }
+        Err(RtwalkError::UnauthenticatedRequest.extend())
+    }
+}
+
 #[derive(SimpleObject)]
 #[graphql(complex, serial)]
 pub struct Page {
@@ -135,6 +202,18 @@ pub struct PageInfo {
     has_next_page: HasNextPage,
 }
 
+impl PageInfo {
+    pub fn set_total(&self, total: u32) {
+        self.total
+            .0
+            .store(total, std::sync::atomic::Ordering::Relaxed);
+        self.has_next_page.0.store(
+            total > (self.page - 1) * self.per_page + self.per_page,
+            std::sync::atomic::Ordering::Relaxed,
+        );
+    }
+}
+
 #[derive(Serialize, Deserialize, Default)]
 pub struct TotalCount(pub AtomicU32);
 #[derive(Serialize, Deserialize, Default)]
@@ -187,26 +266,301 @@ impl QueryRoot {
     }
 }
 
+/// Shared channel of every event of a type, for subscribers that don't filter by forum
+/// or post.
+fn type_channel(ty: RtEventType) -> &'static str {
+    match ty {
+        RtEventType::PostCreate => "rte-post-create",
+        RtEventType::PostEdit => "rte-post-update",
+        RtEventType::CommentCreate => "rte-comment-create",
+        RtEventType::CommentEdit => "rte-comment-update",
+        RtEventType::ForumLock => "rte-forum-lock",
+        RtEventType::PostLock => "rte-post-lock",
+        RtEventType::PostDelete => "rte-post-delete",
+        RtEventType::PostRemove => "rte-post-remove",
+        RtEventType::PostScore => "rte-post-score",
+        RtEventType::PostPin => "rte-post-pin",
+        RtEventType::ModmailMessage => "rte-modmail-message",
+        RtEventType::ModmailThread => "rte-modmail-thread",
+    }
+}
+
+const EVENT_TYPES: [RtEventType; 12] = [
+    RtEventType::PostCreate,
+    RtEventType::PostEdit,
+    RtEventType::CommentCreate,
+    RtEventType::CommentEdit,
+    RtEventType::ForumLock,
+    RtEventType::PostLock,
+    RtEventType::PostDelete,
+    RtEventType::PostRemove,
+    RtEventType::PostScore,
+    RtEventType::PostPin,
+    RtEventType::ModmailMessage,
+    RtEventType::ModmailThread,
+];
+
+fn forum_channel(forum: &Key) -> String {
+    format!("rte-forum:{}", forum.to_string())
+}
+
+/// Carries the post's own events and those of its comments.
+fn post_channel(post: &Key) -> String {
+    format!("rte-post:{}", post.to_string())
+}
+
+fn modmail_channel(thread: &Key) -> String {
+    format!("rte-modmail:{}", thread.to_string())
+}
+
+/// Post the event is about, comment events belong to the post they're on.
+fn event_post(event: &RtEvent) -> Option<&Key> {
+    match &event.event_data {
+        RtEventData::PostCreate(e) => Some(&e.data.id),
+        RtEventData::PostEdit(e) => Some(&e.new.id),
+        RtEventData::CommentCreate(e) => Some(&e.data.post_id),
+        RtEventData::CommentEdit(e) => Some(&e.new.post_id),
+        RtEventData::PostLock(e) => Some(&e.post_id),
+        RtEventData::PostDelete(e) => Some(&e.post_id),
+        RtEventData::PostRemove(e) => Some(&e.post_id),
+        RtEventData::PostScore(e) => Some(&e.post_id),
+        RtEventData::PostPin(e) => Some(&e.post_id),
+        RtEventData::ForumLock(_)
+        | RtEventData::ModmailMessage(_)
+        | RtEventData::ModmailThread(_) => None,
+    }
+}
+
+fn event_modmail_thread(event: &RtEvent) -> Option<&ModmailThread> {
+    match &event.event_data {
+        RtEventData::ModmailMessage(e) => Some(&e.thread),
+        RtEventData::ModmailThread(e) => Some(&e.data),
+        _ => None,
+    }
+}
+
+fn event_author(event: &RtEvent) -> Option<&Key> {
+    match &event.event_data {
+        RtEventData::PostCreate(e) => Some(&e.data.poster_id),
+        RtEventData::PostEdit(e) => Some(&e.new.poster_id),
+        RtEventData::CommentCreate(e) => Some(&e.data.commenter_id),
+        RtEventData::CommentEdit(e) => Some(&e.new.commenter_id),
+        RtEventData::ModmailMessage(e) => Some(&e.message.author_id),
+        RtEventData::ForumLock(_)
+        | RtEventData::PostLock(_)
+        | RtEventData::PostDelete(_)
+        | RtEventData::PostRemove(_)
+        | RtEventData::PostScore(_)
+        | RtEventData::PostPin(_)
+        | RtEventData::ModmailThread(_) => None,
+    }
+}
+
+/// Publishes a real-time event without waiting for redis to acknowledge it. Events are
+/// best effort, a failure here shouldn't fail the mutation that caused it.
+///
+/// A subscription only listens on one kind of channel, see [`RteFilter::channels`], so
+/// the event goes to every kind it can be matched by: its type channel for unfiltered and
+/// type filtered subscriptions, its forum channel, and its post or modmail thread channel
+/// when it has one.
+pub(crate) fn publish_event(state: &State, forum: &Key, event: &RtEvent) {
+    let payload = serde_json::to_vec(event).expect("Cant fail to serialize self constructed data");
+
+    let mut channels = vec![type_channel(event.ty).to_string(), forum_channel(forum)];
+    if let Some(post) = event_post(event) {
+        channels.push(post_channel(post));
+    }
+    if let Some(thread) = event_modmail_thread(event) {
+        channels.push(modmail_channel(&thread.id));
+    }
+
+    for channel in channels {
+        if let Err(e) = state.redis.spublish(&channel, payload.clone()).forget() {
+            error!("Failed to publish {} event: {:?}", channel, e);
+        }
+    }
+}
+
+/// Narrows down `rte`. Each list matches any of its entries, every non-empty list has to
+/// match. At least one list has to be given.
+#[derive(InputObject, Default)]
+pub struct RteFilter {
+    #[graphql(default, validator(max_items = 50))]
+    pub forum_ids: Vec<Key>,
+    /// Events of these posts, including their comments.
+    #[graphql(default, validator(max_items = 50))]
+    pub post_ids: Vec<Key>,
+    /// Posts and comments by these users. Lock events have no author so they never match.
+    #[graphql(default, validator(max_items = 50))]
+    pub author_ids: Vec<Key>,
+    #[graphql(default, validator(max_items = 50))]
+    pub modmail_thread_ids: Vec<Key>,
+    #[graphql(default)]
+    pub types: Vec<RtEventType>,
+}
+
+impl RteFilter {
+    fn is_empty(&self) -> bool {
+        self.forum_ids.is_empty()
+            && self.post_ids.is_empty()
+            && self.author_ids.is_empty()
+            && self.modmail_thread_ids.is_empty()
+            && self.types.is_empty()
+    }
+
+    /// The fewest channels that carry every event the filter can match.
+    fn channels(&self) -> Vec<String> {
+        if !self.post_ids.is_empty() {
+            self.post_ids.iter().map(post_channel).collect()
+        } else if !self.modmail_thread_ids.is_empty() {
+            self.modmail_thread_ids
+                .iter()
+                .map(modmail_channel)
+                .collect()
+        } else if !self.forum_ids.is_empty() {
+            self.forum_ids.iter().map(forum_channel).collect()
+        } else {
+            let types: &[RtEventType] = if self.types.is_empty() {
+                &EVENT_TYPES
+            } else {
+                &self.types
+            };
+            types.iter().map(|t| type_channel(*t).to_string()).collect()
+        }
+    }
+
+    /// Forums are checked against the forum the event was looked up in.
+    fn matches(&self, event: &RtEvent, forum: &DBForum) -> bool {
+        (self.types.is_empty() || self.types.contains(&event.ty))
+            && (self.forum_ids.is_empty() || self.forum_ids.iter().any(|f| **f == *forum.id.key()))
+            && (self.post_ids.is_empty()
+                || event_post(event).is_some_and(|p| self.post_ids.contains(p)))
+            && (self.author_ids.is_empty()
+                || event_author(event).is_some_and(|a| self.author_ids.contains(a)))
+            && (self.modmail_thread_ids.is_empty()
+                || event_modmail_thread(event)
+                    .is_some_and(|t| self.modmail_thread_ids.contains(&t.id)))
+    }
+}
+
+/// Modmail only goes to the thread's user and the moderator team, internal notes only to
+/// the team. Everything else goes to whoever can read the forum.
+async fn can_receive(
+    state: &State,
+    viewer: Option<&User>,
+    event: &RtEvent,
+    forum: &DBForum,
+) -> std::result::Result<bool, RtwalkError> {
+    let Some(thread) = event_modmail_thread(event) else {
+        return forums::can_read(state, viewer, forum).await;
+    };
+    let Some(viewer) = viewer else {
+        return Ok(false);
+    };
+    let internal =
+        matches!(&event.event_data, RtEventData::ModmailMessage(e) if e.message.internal);
+
+    Ok((!internal && thread.user_id == viewer.id)
+        || moderators::has_permission(state, viewer, &forum.id, ModPermission::ManageModmail)
+            .await?)
+}
+
+/// Forum the event happened in, `None` if it's gone by now.
+async fn event_forum(
+    state: &State,
+    event: &RtEvent,
+) -> std::result::Result<Option<DBForum>, RtwalkError> {
+    let forum = match &event.event_data {
+        RtEventData::PostCreate(e) => RecordId::from_table_key("forum", e.data.forum_id.0.clone()),
+        RtEventData::PostEdit(e) => RecordId::from_table_key("forum", e.new.forum_id.0.clone()),
+        RtEventData::ForumLock(e) => RecordId::from_table_key("forum", e.forum_id.0.clone()),
+        RtEventData::PostLock(e) => RecordId::from_table_key("forum", e.forum_id.0.clone()),
+        RtEventData::PostDelete(e) => RecordId::from_table_key("forum", e.forum_id.0.clone()),
+        RtEventData::PostRemove(e) => RecordId::from_table_key("forum", e.forum_id.0.clone()),
+        RtEventData::PostScore(e) => RecordId::from_table_key("forum", e.forum_id.0.clone()),
+        RtEventData::PostPin(e) => RecordId::from_table_key("forum", e.forum_id.0.clone()),
+        RtEventData::ModmailMessage(e) => {
+            RecordId::from_table_key("forum", e.thread.forum_id.0.clone())
+        }
+        RtEventData::ModmailThread(e) => {
+            RecordId::from_table_key("forum", e.data.forum_id.0.clone())
+        }
+        RtEventData::CommentCreate(e) => {
+            let post = RecordId::from_table_key("post", e.data.post_id.0.clone());
+            match posts::post_forum(state, &post).await? {
+                Some(forum) => forum,
+                None => return Ok(None),
+            }
+        }
+        RtEventData::CommentEdit(e) => {
+            let post = RecordId::from_table_key("post", e.new.post_id.0.clone());
+            match posts::post_forum(state, &post).await? {
+                Some(forum) => forum,
+                None => return Ok(None),
+            }
+        }
+    };
+
+    let forum: Option<DBForum> = state.db.select(forum).await?;
+
+    Ok(forum.filter(|f| f.deleted_at.is_none()))
+}
+
 pub struct Subscription;
 
 #[Subscription]
 impl Subscription {
+    /// Needs a non-empty filter or one of the deprecated arguments, subscribing to every
+    /// event of the site fails with `EMPTY_RTE_FILTER`. Filtering by post or forum only
+    /// subscribes to their channels.
     async fn rte(
         &self,
         ctx: &Context<'_>,
+        #[graphql(default)] filter: RteFilter,
+        #[graphql(default, deprecation = "Use `filter.types` with `POST_CREATE`.")]
         post_create: bool,
+        #[graphql(
+            default,
+            deprecation = "Use `filter.types` with `POST_EDIT` and `COMMENT_EDIT`."
+        )]
         post_update: bool,
+        #[graphql(
+            default,
+            deprecation = "Use `filter.types` with `FORUM_LOCK` and `POST_LOCK`.",
+            desc = "Forums and posts being locked or unlocked."
+        )]
+        lock_update: bool,
     ) -> async_graphql::Result<impl Stream<Item = RtEvent>> {
-        let state = state!(ctx);
+        let state = state!(ctx).clone();
+        let viewer = viewer(ctx).await?;
 
-        let mut channels = vec![];
-        if post_create {
-            channels.push("rte-post-create");
+        // What the deprecated arguments used to subscribe to.
+        let mut filter = filter;
+        let deprecated: [(bool, &[RtEventType]); 3] = [
+            (post_create, &[RtEventType::PostCreate]),
+            (
+                post_update,
+                &[RtEventType::PostEdit, RtEventType::CommentEdit],
+            ),
+            (
+                lock_update,
+                &[RtEventType::ForumLock, RtEventType::PostLock],
+            ),
+        ];
+        for (_, types) in deprecated.iter().filter(|(requested, _)| *requested) {
+            for ty in *types {
+                if !filter.types.contains(ty) {
+                    filter.types.push(*ty);
+                }
+            }
         }
-        if post_update {
-            channels.push("rte-post-update");
+
+        if filter.is_empty() {
+            return Err(RtwalkError::EmptyRteFilter).extend_err(|_, _| {});
         }
 
+        let channels = filter.channels();
+
         let mut sub_stream = state
             .pubsub
             .ssubscribe(channels)
@@ -219,7 +573,15 @@ impl Subscription {
                 if let Ok(sub_msg) = maybe_sub_msg {
                     let event: RtEvent = serde_json::from_reader(sub_msg.payload.reader()).expect("Payload must be valid");
 
-                    yield event;
+                    // Only pass on events from forums the subscriber can read.
+                    let readable = match event_forum(&state, &event).await {
+                        Ok(Some(forum)) => filter.matches(&event, &forum)
+                            && can_receive(&state, viewer.as_ref(), &event, &forum).await.unwrap_or(false),
+                        _ => false,
+                    };
+                    if readable {
+                        yield event;
+                    }
                 }
                 // TODO: Handle this error
             }
@@ -234,6 +596,9 @@ pub struct MergedQueryRoot(
     resolvers::users::UserQueryRoot,
     resolvers::forums::ForumQueryRoot,
     resolvers::posts::PostQueryRoot,
+    resolvers::email_domains::EmailDomainQueryRoot,
+    resolvers::categories::CategoryQueryRoot,
+    resolvers::modmail::ModmailQueryRoot,
 );
 
 #[derive(MergedObject, Default)]
@@ -243,4 +608,10 @@ pub struct MergedMutationRoot(
     resolvers::forums::ForumMutationRoot,
     resolvers::posts::PostMutationRoot,
     resolvers::comments::CommentMutationRoot,
+    resolvers::email_domains::EmailDomainMutationRoot,
+    resolvers::bans::BanMutationRoot,
+    resolvers::categories::CategoryMutationRoot,
+    resolvers::wiki::WikiMutationRoot,
+    resolvers::emojis::EmojiMutationRoot,
+    resolvers::modmail::ModmailMutationRoot,
 );
@@ -0,0 +1,209 @@
+use serde::Deserialize;
+use surrealdb::RecordId;
+
+use crate::{
+    error::RtwalkError,
+    gql::forums,
+    models::{
+        forum::DBForum,
+        moderator::{DBModerator, ModPermission},
+        user::User,
+        Key,
+    },
+    state::State,
+};
+
+#[derive(Deserialize)]
+struct ChainLink {
+    id: RecordId,
+    owner: RecordId,
+    parent: Option<RecordId>,
+    inherit: Option<bool>,
+}
+
+/// Permissions the user has in the forum, `None` if they aren't a moderator at all.
+/// Admins and the forum owner have every permission. Unless the forum opts out,
+/// permissions from its parent forums add up, and owning a parent grants every permission.
+pub async fn fetch_permissions(
+    state: &State,
+    user: &User,
+    forum: &RecordId,
+) -> Result<Option<Vec<ModPermission>>, RtwalkError> {
+    if user.admin {
+        return Ok(Some(ModPermission::ALL.to_vec()));
+    }
+
+    let chain = forums::ancestor_chain();
+    let mut res = state
+        .db
+        .query(format!(
+            "SELECT id, owner, parent, settings.inherit_moderators AS inherit \
+            FROM {chain} WHERE id != NONE"
+        ))
+        .query(format!(
+            "SELECT * FROM moderates WHERE in = $user AND out IN {chain}"
+        ))
+        .bind(("forum", forum.clone()))
+        .bind(("user", RecordId::from_table_key("user", user.id.0.clone())))
+        .await?;
+    let chain: Vec<ChainLink> = res.take(0)?;
+    let moderators: Vec<DBModerator> = res.take(1)?;
+
+    let mut permissions: Option<Vec<ModPermission>> = None;
+    let mut current = Some(forum.clone());
+
+    while let Some(forum) = current.take() {
+        let Some(link) = chain.iter().find(|l| l.id == forum) else {
+            break;
+        };
+        if link.owner.key() == &user.id.0 {
+            return Ok(Some(ModPermission::ALL.to_vec()));
+        }
+        if let Some(moderator) = moderators.iter().find(|m| m.forum == forum) {
+            let permissions = permissions.get_or_insert_with(Vec::new);
+            for permission in &moderator.permissions {
+                if !permissions.contains(permission) {
+                    permissions.push(*permission);
+                }
+            }
+        }
+
+        if link.inherit.unwrap_or(true) {
+            current = link.parent.clone();
+        }
+    }
+
+    Ok(permissions)
+}
+
+pub async fn has_permission(
+    state: &State,
+    user: &User,
+    forum: &RecordId,
+    permission: ModPermission,
+) -> Result<bool, RtwalkError> {
+    Ok(fetch_permissions(state, user, forum)
+        .await?
+        .is_some_and(|p| p.contains(&permission)))
+}
+
+pub async fn ensure_permission(
+    state: &State,
+    user: &User,
+    forum: &RecordId,
+    permission: ModPermission,
+) -> Result<(), RtwalkError> {
+    if has_permission(state, user, forum, permission).await? {
+        Ok(())
+    } else {
+        Err(RtwalkError::UnauhorizedRequest)
+    }
+}
+
+pub async fn fetch_moderator(
+    state: &State,
+    forum: &RecordId,
+    user: Key,
+) -> Result<Option<DBModerator>, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT * FROM moderates WHERE in = $user AND out = $forum")
+        .bind(("user", RecordId::from_table_key("user", user.0)))
+        .bind(("forum", forum.clone()))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+pub async fn add_moderator(
+    state: &State,
+    forum: &DBForum,
+    user: Key,
+    added_by: Key,
+    permissions: Vec<ModPermission>,
+) -> Result<DBModerator, RtwalkError> {
+    if forum.owner.key() == &user.0 {
+        return Err(RtwalkError::AlreadyModerator);
+    }
+
+    let moderator = DBModerator::new(user, Key(forum.id.key().to_owned()), added_by, permissions);
+
+    let mut res = state
+        .db
+        .query("SELECT 1 FROM ONLY $user")
+        .query("SELECT 1 FROM moderates WHERE in = $user AND out = $forum")
+        .bind(("user", moderator.user.clone()))
+        .bind(("forum", moderator.forum.clone()))
+        .await?;
+
+    let user_exists: Option<u64> = res.take((0, "1"))?;
+    if user_exists.is_none() {
+        return Err(RtwalkError::UserNotFound);
+    }
+    let already_moderator: Option<u64> = res.take((1, "1"))?;
+    if already_moderator.is_some() {
+        return Err(RtwalkError::AlreadyModerator);
+    }
+
+    state
+        .db
+        .query("INSERT RELATION INTO moderates $moderator")
+        .bind(("moderator", moderator.clone()))
+        .await?;
+
+    Ok(moderator)
+}
+
+pub async fn update_permissions(
+    state: &State,
+    forum: &RecordId,
+    user: Key,
+    permissions: Vec<ModPermission>,
+) -> Result<Option<DBModerator>, RtwalkError> {
+    let mut res = state
+        .db
+        .query("UPDATE moderates SET permissions = $permissions WHERE in = $user AND out = $forum")
+        .bind(("user", RecordId::from_table_key("user", user.0)))
+        .bind(("forum", forum.clone()))
+        .bind(("permissions", permissions))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+/// Returns false if the user wasn't a moderator.
+pub async fn remove_moderator(
+    state: &State,
+    forum: &RecordId,
+    user: Key,
+) -> Result<bool, RtwalkError> {
+    let mut res = state
+        .db
+        .query("DELETE moderates WHERE in = $user AND out = $forum RETURN BEFORE")
+        .bind(("user", RecordId::from_table_key("user", user.0)))
+        .bind(("forum", forum.clone()))
+        .await?;
+
+    let removed: Vec<DBModerator> = res.take(0)?;
+
+    Ok(!removed.is_empty())
+}
+
+pub async fn fetch_moderators(
+    state: &State,
+    forum: &RecordId,
+    page: u32,
+    per_page: u32,
+) -> Result<Vec<DBModerator>, RtwalkError> {
+    let mut res = state
+        .db
+        .query(
+            "SELECT * FROM moderates WHERE out = $forum ORDER BY created_at ASC LIMIT $limit START $start",
+        )
+        .bind(("forum", forum.clone()))
+        .bind(("limit", per_page))
+        .bind(("start", (page.max(1) - 1).saturating_mul(per_page)))
+        .await?;
+
+    Ok(res.take(0)?)
+}
@@ -0,0 +1,62 @@
+use surrealdb::RecordId;
+
+use crate::{
+    error::RtwalkError,
+    models::{
+        modlog::{DBModLogEntry, ModAction},
+        Key,
+    },
+    state::State,
+};
+
+pub async fn record(
+    state: &State,
+    forum: &RecordId,
+    actor: &Key,
+    action: ModAction,
+    target: Option<RecordId>,
+    reason: Option<String>,
+) -> Result<(), RtwalkError> {
+    record_citing(state, forum, actor, action, target, reason, None).await
+}
+
+/// Like [`record`], also noting which forum rule the action was taken under.
+pub async fn record_citing(
+    state: &State,
+    forum: &RecordId,
+    actor: &Key,
+    action: ModAction,
+    target: Option<RecordId>,
+    reason: Option<String>,
+    rule: Option<String>,
+) -> Result<(), RtwalkError> {
+    let mut entry = DBModLogEntry::new(forum.clone(), actor.clone(), action, target, reason);
+    entry.rule = rule;
+
+    state
+        .db
+        .query("CREATE mod_log CONTENT $entry")
+        .bind(("entry", entry))
+        .await?;
+
+    Ok(())
+}
+
+pub async fn fetch_mod_log(
+    state: &State,
+    forum: &RecordId,
+    page: u32,
+    per_page: u32,
+) -> Result<Vec<DBModLogEntry>, RtwalkError> {
+    let mut res = state
+        .db
+        .query(
+            "SELECT * FROM mod_log WHERE forum = $forum ORDER BY created_at DESC LIMIT $limit START $start",
+        )
+        .bind(("forum", forum.clone()))
+        .bind(("limit", per_page))
+        .bind(("start", (page.max(1) - 1).saturating_mul(per_page)))
+        .await?;
+
+    Ok(res.take(0)?)
+}
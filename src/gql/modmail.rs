@@ -0,0 +1,217 @@
+use surrealdb::RecordId;
+
+use crate::{
+    config,
+    error::RtwalkError,
+    gql::moderators,
+    models::{
+        forum::DBForum,
+        moderator::ModPermission,
+        modmail::{DBModmailMessage, DBModmailThread},
+        user::User,
+        Key,
+    },
+    state::State,
+};
+
+/// Starts a thread with the user's first message. Users can only have a few threads
+/// waiting on the moderators at once.
+pub async fn create_thread(
+    state: &State,
+    forum: &DBForum,
+    user: Key,
+    subject: String,
+    content: String,
+) -> Result<(DBModmailThread, DBModmailMessage), RtwalkError> {
+    let thread = DBModmailThread::new(forum.id.clone(), user.clone(), subject);
+    let message = DBModmailMessage::new(thread.id.clone(), user, content, false);
+
+    let mut res = state
+        .db
+        .query(
+            "SELECT count() AS total FROM modmail_thread WHERE forum = $forum AND user = $user AND archived = false GROUP ALL",
+        )
+        .bind(("forum", forum.id.clone()))
+        .bind(("user", thread.user.clone()))
+        .await?;
+    let open: Option<usize> = res.take((0, "total"))?;
+    if open.unwrap_or(0) >= config::MAX_OPEN_MODMAIL_THREADS {
+        return Err(RtwalkError::TooManyModmailThreads);
+    }
+
+    state
+        .db
+        .query("BEGIN TRANSACTION")
+        .query("CREATE modmail_thread CONTENT $thread")
+        .query("CREATE modmail_message CONTENT $message")
+        .query("COMMIT TRANSACTION")
+        .bind(("thread", thread.clone()))
+        .bind(("message", message.clone()))
+        .await?
+        .check()?;
+
+    Ok((thread, message))
+}
+
+pub async fn fetch_thread(
+    state: &State,
+    thread: Key,
+) -> Result<Option<DBModmailThread>, RtwalkError> {
+    Ok(state.db.select(("modmail_thread", thread.0)).await?)
+}
+
+/// Moderators who can manage the forum's modmail, they also see internal notes.
+pub async fn is_team(
+    state: &State,
+    user: &User,
+    thread: &DBModmailThread,
+) -> Result<bool, RtwalkError> {
+    moderators::has_permission(state, user, &thread.forum, ModPermission::ManageModmail).await
+}
+
+/// Only the thread's user and the moderator team can read or answer it. Returns whether
+/// the user is on the team.
+pub async fn ensure_participant(
+    state: &State,
+    user: &User,
+    thread: &DBModmailThread,
+) -> Result<bool, RtwalkError> {
+    if is_team(state, user, thread).await? {
+        Ok(true)
+    } else if thread.user.key() == &user.id.0 {
+        Ok(false)
+    } else {
+        Err(RtwalkError::UnauhorizedRequest)
+    }
+}
+
+/// A message from the thread's user brings an archived thread back.
+pub async fn add_message(
+    state: &State,
+    thread: &DBModmailThread,
+    author: Key,
+    content: String,
+    internal: bool,
+) -> Result<(DBModmailThread, DBModmailMessage), RtwalkError> {
+    let mut thread = thread.clone();
+    if thread.user.key() == &author.0 && !internal {
+        thread.archived = false;
+    }
+    let message = DBModmailMessage::new(thread.id.clone(), author, content, internal);
+    if !internal {
+        thread.last_message_at = message.created_at;
+    }
+
+    state
+        .db
+        .query("BEGIN TRANSACTION")
+        .query("CREATE modmail_message CONTENT $message")
+        .query("UPDATE $thread SET archived = $archived, last_message_at = $last_message_at")
+        .query("COMMIT TRANSACTION")
+        .bind(("thread", thread.id.clone()))
+        .bind(("archived", thread.archived))
+        .bind(("last_message_at", thread.last_message_at))
+        .bind(("message", message.clone()))
+        .await?
+        .check()?;
+
+    Ok((thread, message))
+}
+
+pub async fn set_archived(
+    state: &State,
+    thread: &DBModmailThread,
+    archived: bool,
+) -> Result<DBModmailThread, RtwalkError> {
+    let mut res = state
+        .db
+        .query("UPDATE $thread SET archived = $archived")
+        .bind(("thread", thread.id.clone()))
+        .bind(("archived", archived))
+        .await?;
+    let thread: Option<DBModmailThread> = res.take(0)?;
+
+    thread.ok_or(RtwalkError::ModmailThreadNotFound)
+}
+
+/// Most recently active first.
+pub async fn fetch_forum_threads(
+    state: &State,
+    forum: &RecordId,
+    archived: bool,
+    page: u32,
+    per_page: u32,
+) -> Result<Vec<DBModmailThread>, RtwalkError> {
+    let mut res = state
+        .db
+        .query(
+            "SELECT * FROM modmail_thread WHERE forum = $forum AND archived = $archived ORDER BY last_message_at DESC LIMIT $limit START $start",
+        )
+        .bind(("forum", forum.clone()))
+        .bind(("archived", archived))
+        .bind(("limit", per_page))
+        .bind(("start", (page.max(1) - 1).saturating_mul(per_page)))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+/// Threads the user started, most recently active first.
+pub async fn fetch_user_threads(
+    state: &State,
+    user: &Key,
+    page: u32,
+    per_page: u32,
+) -> Result<Vec<DBModmailThread>, RtwalkError> {
+    let mut res = state
+        .db
+        .query(
+            "SELECT * FROM modmail_thread WHERE user = $user ORDER BY last_message_at DESC LIMIT $limit START $start",
+        )
+        .bind(("user", RecordId::from_table_key("user", user.0.clone())))
+        .bind(("limit", per_page))
+        .bind(("start", (page.max(1) - 1).saturating_mul(per_page)))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+/// Oldest first.
+pub async fn fetch_messages(
+    state: &State,
+    thread: &RecordId,
+    include_internal: bool,
+    page: u32,
+    per_page: u32,
+) -> Result<Vec<DBModmailMessage>, RtwalkError> {
+    let mut res = state
+        .db
+        .query(
+            "SELECT * FROM modmail_message WHERE thread = $thread AND ($internal OR internal = false) ORDER BY created_at ASC LIMIT $limit START $start",
+        )
+        .bind(("thread", thread.clone()))
+        .bind(("internal", include_internal))
+        .bind(("limit", per_page))
+        .bind(("start", (page.max(1) - 1).saturating_mul(per_page)))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+/// Bans can only cite a thread of the banned user in the same forum.
+pub async fn ensure_citable(
+    state: &State,
+    forum: &RecordId,
+    user: &Key,
+    thread: Key,
+) -> Result<RecordId, RtwalkError> {
+    let thread = fetch_thread(state, thread)
+        .await?
+        .filter(|t| &t.forum == forum)
+        .ok_or(RtwalkError::ModmailThreadNotFound)?;
+    if thread.user.key() != &user.0 {
+        return Err(RtwalkError::ModmailThreadNotFound);
+    }
+
+    Ok(thread.id)
+}
@@ -1,29 +1,47 @@
+use async_graphql::UploadValue;
+use chrono::{TimeDelta, Utc};
+use rustis::{
+    client::BatchPreparedCommand,
+    commands::{ExpireOption, GenericCommands, StringCommands},
+};
+
 use crate::{
+    config,
     error::RtwalkError,
-    gql::PageInfo,
-    models::{file::File, post::DBPost, Key},
+    gql::{forums, members, moderators, revisions, stats, users, PageInfo},
+    models::{
+        file::FileOps,
+        forum::{DBForum, ForumVisibility},
+        moderator::ModPermission,
+        post::{DBPost, PostSort, TopWindow},
+        user::User,
+        Key,
+    },
     state::State,
 };
 use surrealdb::RecordId;
 
 use super::resolvers::posts::{MultiplePostSelectCriteria, PostSelectCriteria};
 
-pub async fn create_post(
-    state: &State,
-    title: String,
-    tags: Vec<String>,
-    content: Option<String>,
-    attachments: Vec<File>,
-    poster: Key,
-    forum: Key,
-) -> Result<DBPost, RtwalkError> {
-    let post = DBPost::new(title, tags, content, attachments, poster, forum);
-
+pub async fn create_post(state: &State, post: DBPost) -> Result<DBPost, RtwalkError> {
     state
         .db
+        .query("BEGIN TRANSACTION")
         .query("CREATE post CONTENT $post")
+        .query("UPDATE $forum SET post_count += 1")
+        .query("COMMIT TRANSACTION")
         .bind(("post", post.clone()))
-        .await?;
+        .bind(("forum", post.forum.clone()))
+        .await?
+        .check()?;
+
+    stats::record(
+        state,
+        &post.forum,
+        &Key(post.poster.key().to_owned()),
+        Submission::Post,
+    )
+    .await?;
 
     Ok(post)
 }
@@ -31,143 +49,499 @@ pub async fn create_post(
 pub async fn fetch_post(
     state: &State,
     criteria: PostSelectCriteria,
+    viewer: Option<&User>,
 ) -> Result<Option<DBPost>, RtwalkError> {
     let post: Option<DBPost> = match criteria {
-        PostSelectCriteria::Id(id) => state.db.select(("post", id.0)).await?,
+        PostSelectCriteria::Id(id) => {
+            // Posts of deleted forums are hidden along with the forum.
+            let mut res = state
+                .db
+                .query(format!(
+                    "SELECT * FROM $post WHERE forum.deleted_at = NONE AND {} AND {}",
+                    forums::visibility_condition("forum", viewer),
+                    forums::moderation_condition("poster", "forum", viewer)
+                ))
+                .bind(("post", RecordId::from_table_key("post", id.0)))
+                .bind(forums::viewer_access(state, viewer).await?)
+                .await?;
+
+            res.take(0)?
+        }
     };
 
     Ok(post)
 }
 
-pub async fn fetch_posts(
+/// `(post, forum)` for every post in `posts` that exists.
+pub async fn post_forums(
     state: &State,
-    criteria: MultiplePostSelectCriteria,
-    page_info: &PageInfo,
+    posts: &[RecordId],
+) -> Result<Vec<(RecordId, RecordId)>, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT VALUE [id, forum] FROM $posts WHERE id != NONE")
+        .bind(("posts", posts.to_vec()))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+/// Posts waiting for approval, oldest first.
+pub async fn fetch_held_posts(
+    state: &State,
+    forum: &RecordId,
+    page: u32,
+    per_page: u32,
 ) -> Result<Vec<DBPost>, RtwalkError> {
-    let posts: Vec<DBPost> = match criteria {
-        MultiplePostSelectCriteria::Ids(ids) => {
-            let mut query = state
-                .db
-                .query("SELECT * FROM $ids LIMIT $limit START $start");
+    let mut res = state
+        .db
+        .query(
+            "SELECT * FROM post WHERE forum = $forum AND held = true ORDER BY created_at ASC LIMIT $limit START $start",
+        )
+        .bind(("forum", forum.clone()))
+        .bind(("limit", per_page))
+        .bind(("start", (page.max(1) - 1).saturating_mul(per_page)))
+        .await?;
 
-            if page_info.needs_page_info {
-                query = query.query("SELECT count() as total FROM $ids");
-            }
+    Ok(res.take(0)?)
+}
 
-            let mut res = query
-                .bind((
-                    "ids",
-                    ids.into_iter()
-                        .map(|x| RecordId::from_table_key("post", x.0))
-                        .collect::<Vec<_>>(),
-                ))
-                .bind(("limit", page_info.per_page))
-                .bind(("start", (page_info.page - 1) * page_info.per_page))
-                .await?;
+/// Writes the given `fields` of the post back, moving the forum's `post_count` when the
+/// post gets held, removed, approved or deleted. `was_counted` is [`DBPost::is_counted`]
+/// before the change. The vote counters and ranks are never written from here, votes
+/// made since `post` was read would be undone.
+pub async fn save_post(
+    state: &State,
+    post: DBPost,
+    was_counted: bool,
+    fields: &[&str],
+) -> Result<DBPost, RtwalkError> {
+    let delta = post.is_counted() as i64 - was_counted as i64;
+    let set = fields
+        .iter()
+        .map(|field| format!("{field} = $post.{field}"))
+        .collect::<Vec<_>>()
+        .join(", ");
 
-            if page_info.needs_page_info {
-                if let Some(total) = res.take((1, "total"))? {
-                    page_info
-                        .total
-                        .0
-                        .store(total, std::sync::atomic::Ordering::Relaxed);
-                    page_info.has_next_page.0.store(
-                        total > (page_info.page - 1) * page_info.per_page + page_info.per_page,
-                        std::sync::atomic::Ordering::Relaxed,
-                    );
-                }
-            }
+    state
+        .db
+        .query("BEGIN TRANSACTION")
+        .query(format!("UPDATE $id SET {set}"))
+        .query("IF $delta != 0 { UPDATE $forum SET post_count += $delta }")
+        .query("COMMIT TRANSACTION")
+        .bind(("id", post.id.clone()))
+        .bind(("forum", post.forum.clone()))
+        .bind(("post", post.clone()))
+        .bind(("delta", delta))
+        .await?
+        .check()?;
 
-            res.take(0)?
-        }
-        MultiplePostSelectCriteria::Forum(id) => {
-            let mut query = state
-                .db
-                .query("SELECT * FROM post WHERE forum.id = $forum_id LIMIT $limit START $start");
+    let res: Option<DBPost> = state.db.select(&post.id).await?;
 
-            if page_info.needs_page_info {
-                query = query.query("SELECT count() as total FROM post WHERE forum.id = $forum_id");
+    res.ok_or(RtwalkError::PostNotFound)
+}
+
+/// Tombstones the post: its title becomes "[deleted]", content and attachments are
+/// dropped. Comments and earlier revisions stay where they are, as do the files the
+/// revisions point at.
+pub async fn delete_post(state: &State, mut post: DBPost) -> Result<DBPost, RtwalkError> {
+    let was_counted = post.is_counted();
+    let attachments = std::mem::take(&mut post.attachments);
+
+    post.title = "[deleted]".to_string();
+    post.content = None;
+    post.tags = vec![];
+    post.flair = None;
+    post.pinned = false;
+    post.deleted = true;
+
+    let post = save_post(
+        state,
+        post,
+        was_counted,
+        &[
+            "title",
+            "content",
+            "tags",
+            "flair",
+            "pinned",
+            "deleted",
+            "attachments",
+        ],
+    )
+    .await?;
+
+    // Only once the post no longer points at them.
+    let referenced = revisions::fetch_referenced_files(state, &post.id).await?;
+    for attachment in attachments.iter().filter(|a| !referenced.contains(&a.loc)) {
+        attachment.delete(&state.op).await?;
+    }
+
+    Ok(post)
+}
+
+/// Thrown by the pin transaction when the forum already has the maximum of pinned posts.
+const TOO_MANY_PINS: &str = "forum has too many pinned posts";
+
+/// Pins or unpins the post. Pinning fails once the forum has [`config::MAX_PINNED_POSTS`].
+pub async fn set_pinned(state: &State, post: &DBPost, pinned: bool) -> Result<DBPost, RtwalkError> {
+    state
+        .db
+        .query("BEGIN TRANSACTION")
+        .query(
+            "IF $pinned AND (SELECT count() AS total FROM post \
+            WHERE forum = $forum AND pinned = true AND id != $id GROUP ALL)[0].total >= $max \
+            { THROW $too_many }",
+        )
+        .query("UPDATE $id SET pinned = $pinned")
+        .query("COMMIT TRANSACTION")
+        .bind(("id", post.id.clone()))
+        .bind(("forum", post.forum.clone()))
+        .bind(("pinned", pinned))
+        .bind(("max", config::MAX_PINNED_POSTS))
+        .bind(("too_many", TOO_MANY_PINS))
+        .await?
+        .check()
+        .map_err(|e| {
+            if e.to_string().contains(TOO_MANY_PINS) {
+                RtwalkError::TooManyPinnedPosts
+            } else {
+                e.into()
             }
+        })?;
 
-            let mut res = query
-                .bind(("forum_id", RecordId::from_table_key("forum", id.0)))
-                .bind(("limit", page_info.per_page))
-                .bind(("start", (page_info.page - 1) * page_info.per_page))
+    let res: Option<DBPost> = state.db.select(&post.id).await?;
+
+    res.ok_or(RtwalkError::PostNotFound)
+}
+
+pub async fn post_forum(state: &State, post: &RecordId) -> Result<Option<RecordId>, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT VALUE forum FROM ONLY $post")
+        .bind(("post", post.clone()))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+/// Fails if the user can't post, comment or edit in the forum right now: the forum,
+/// or the post if given, is locked, or the forum needs a membership the user doesn't have.
+/// Moderators with `permission` skip these checks.
+pub async fn ensure_can_post(
+    state: &State,
+    user: &User,
+    forum: &RecordId,
+    post: Option<&DBPost>,
+    permission: ModPermission,
+) -> Result<DBForum, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT * FROM $forum WHERE deleted_at = NONE")
+        .bind(("forum", forum.clone()))
+        .await?;
+
+    let forum: Option<DBForum> = res.take(0)?;
+    let forum = forum.ok_or(RtwalkError::ForumNotFound)?;
+
+    let denied = if forum.locked {
+        Some(RtwalkError::ForumLocked)
+    } else if post.is_some_and(|p| p.locked) {
+        Some(RtwalkError::PostLocked)
+    } else if (forum.settings.require_membership
+        || forum.settings.visibility != ForumVisibility::Public)
+        && !members::is_member(
+            state,
+            &forum.id,
+            &RecordId::from_table_key("user", user.id.0.clone()),
+        )
+        .await?
+    {
+        Some(RtwalkError::NotForumMember)
+    } else {
+        None
+    };
+
+    match denied {
+        Some(e) if !moderators::has_permission(state, user, &forum.id, permission).await? => Err(e),
+        _ => Ok(forum),
+    }
+}
+
+/// Posts and comments are rate limited separately.
+#[derive(Clone, Copy)]
+pub enum Submission {
+    Post,
+    Comment,
+}
+
+fn submission_rate_key(kind: Submission, forum: &RecordId, user: &Key) -> String {
+    match kind {
+        Submission::Post => format!("post_rate:{}:{}", forum.key(), user.0),
+        Submission::Comment => format!("comment_rate:{}:{}", forum.key(), user.0),
+    }
+}
+
+fn rate_limit(forum: &DBForum, kind: Submission) -> Option<u32> {
+    match kind {
+        Submission::Post => forum.settings.post_rate_limit,
+        Submission::Comment => forum.settings.comment_rate_limit,
+    }
+}
+
+/// Posting requirements from the forum settings, only checked for new posts and comments.
+/// Moderators with `permission` skip them like they skip [`ensure_can_post`].
+pub async fn ensure_meets_requirements(
+    state: &State,
+    user: &User,
+    forum: &DBForum,
+    kind: Submission,
+    attachments: &[UploadValue],
+    permission: ModPermission,
+) -> Result<(), RtwalkError> {
+    let settings = &forum.settings;
+
+    let denied = if Utc::now().timestamp() - user.created_at < settings.min_account_age as i64 {
+        Some(RtwalkError::AccountTooNew)
+    } else if settings.require_verified_email && !user.email_verified {
+        Some(RtwalkError::EmailNotVerified)
+    } else if !attachments.is_empty() && !settings.allow_attachments {
+        Some(RtwalkError::AttachmentsNotAllowed)
+    } else if settings
+        .max_attachments
+        .is_some_and(|max| attachments.len() > max as usize)
+    {
+        Some(RtwalkError::TooManyAttachments)
+    } else if !settings.allowed_mime_types.is_empty()
+        && !attachments.iter().all(|a| {
+            a.content_type.as_ref().is_some_and(|t| {
+                settings
+                    .allowed_mime_types
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(t))
+            })
+        })
+    {
+        Some(RtwalkError::AttachmentTypeNotAllowed)
+    } else {
+        None
+    };
+
+    let denied = match (denied, settings.min_karma) {
+        (None, Some(min_karma)) => {
+            let karma = users::fetch_karma(state, &user.id).await?;
+            (karma < min_karma).then_some(RtwalkError::NotEnoughKarma)
+        }
+        (denied, _) => denied,
+    };
+
+    let denied = match (denied, rate_limit(forum, kind)) {
+        (None, Some(limit)) => {
+            let recent: Option<u32> = state
+                .redis
+                .get(submission_rate_key(kind, &forum.id, &user.id))
                 .await?;
+            (recent.unwrap_or(0) >= limit).then_some(match kind {
+                Submission::Post => RtwalkError::PostRateLimited,
+                Submission::Comment => RtwalkError::CommentRateLimited,
+            })
+        }
+        (denied, _) => denied,
+    };
 
-            if page_info.needs_page_info {
-                if let Some(total) = res.take((1, "total"))? {
-                    page_info
-                        .total
-                        .0
-                        .store(total, std::sync::atomic::Ordering::Relaxed);
-                    page_info.has_next_page.0.store(
-                        total > (page_info.page - 1) * page_info.per_page + page_info.per_page,
-                        std::sync::atomic::Ordering::Relaxed,
-                    );
-                }
-            }
+    match denied {
+        Some(e) if !moderators::has_permission(state, user, &forum.id, permission).await? => Err(e),
+        _ => Ok(()),
+    }
+}
 
-            res.take(0)?
+/// Counts a new post or comment towards the forum's rate limit.
+pub async fn record_submission(
+    state: &State,
+    user: &Key,
+    forum: &DBForum,
+    kind: Submission,
+) -> Result<(), RtwalkError> {
+    if rate_limit(forum, kind).is_none() {
+        return Ok(());
+    }
+
+    let key = submission_rate_key(kind, &forum.id, user);
+
+    let mut pipeline = state.redis.create_pipeline();
+    pipeline.incr(&key).forget();
+    pipeline
+        .expire(
+            &key,
+            config::SUBMISSION_RATE_WINDOW_SECONDS,
+            ExpireOption::Nx,
+        )
+        .forget();
+    pipeline.execute::<()>().await?;
+
+    Ok(())
+}
+
+/// Recomputes the sort ranks of posts that were voted on since the last run, and of
+/// every post young enough to be rising. Posts that aged out of rising drop to 0.
+pub async fn refresh_ranks(state: &State) -> Result<(), RtwalkError> {
+    let rising_since = Utc::now() - TimeDelta::seconds(config::RISING_WINDOW_SECONDS as i64);
+
+    state
+        .db
+        .query(
+            "UPDATE post SET \
+            hot_rank = (IF score > 0 { 1 } ELSE IF score < 0 { -1 } ELSE { 0 }) \
+                * math::log10(math::max([math::abs(score), 1])) \
+                + <float> (time::unix(created_at) - $hot_epoch) / $hot_decay, \
+            controversial_rank = IF upvotes = 0 OR downvotes = 0 { 0 } \
+                ELSE { math::pow(<float> (upvotes + downvotes), \
+                    <float> math::min([upvotes, downvotes]) / math::max([upvotes, downvotes])) }, \
+            rising_rank = IF created_at > $rising_since \
+                { <float> score / math::max([(time::unix(time::now()) - time::unix(created_at)) / 3600.0, 1]) } \
+                ELSE { 0 }, \
+            ranks_stale = false \
+            WHERE ranks_stale = true OR created_at > $rising_since OR rising_rank != 0",
+        )
+        .bind(("hot_epoch", config::HOT_RANK_EPOCH))
+        .bind(("hot_decay", config::HOT_RANK_DECAY_SECONDS))
+        .bind(("rising_since", rising_since))
+        .await?
+        .check()?;
+
+    Ok(())
+}
+
+/// Viewer dependent options applied on top of [`MultiplePostSelectCriteria`].
+pub struct PostFilter {
+    pub sort: PostSort,
+    /// Only used with [`PostSort::Top`].
+    pub top_window: TopWindow,
+    pub show_nsfw: bool,
+    pub flair: Option<String>,
+    /// With forum criteria, also lists posts of its sub-forums.
+    pub include_subforums: bool,
+}
+
+pub async fn fetch_posts(
+    state: &State,
+    criteria: MultiplePostSelectCriteria,
+    filter: &PostFilter,
+    viewer: Option<&User>,
+    page_info: &PageInfo,
+) -> Result<Vec<DBPost>, RtwalkError> {
+    let visible = forums::visibility_condition("forum", viewer);
+    let moderated = forums::moderation_condition("poster", "forum", viewer);
+    let mut conditions = vec![visible.as_str(), moderated.as_str()];
+    let mut ids = vec![];
+    let mut forum_id = None;
+    let mut forum_ids = vec![];
+    let mut search = None;
+    // Forum listings show pinned posts first whatever the sort is.
+    let mut pins_first = false;
+
+    let from = match criteria {
+        MultiplePostSelectCriteria::Ids(keys) => {
+            ids = keys
+                .into_iter()
+                .map(|x| RecordId::from_table_key("post", x.0))
+                .collect();
+            "$ids"
         }
-        MultiplePostSelectCriteria::Search(search) => match search.as_str() {
-            "*" => {
-                let mut query = state
-                    .db
-                    .query("SELECT * FROM post ORDER BY created_at ASC LIMIT $limit START $start");
-                if page_info.needs_page_info {
-                    query = query.query("SELECT count() as total FROM post")
-                }
-                let mut res = query
-                    .bind(("limit", page_info.per_page))
-                    .bind(("start", (page_info.page - 1) * page_info.per_page))
-                    .await?;
-
-                if page_info.needs_page_info {
-                    if let Some(total) = res.take((1, "total"))? {
-                        page_info
-                            .total
-                            .0
-                            .store(total, std::sync::atomic::Ordering::Relaxed);
-                        page_info.has_next_page.0.store(
-                            total > (page_info.page - 1) * page_info.per_page + page_info.per_page,
-                            std::sync::atomic::Ordering::Relaxed,
-                        );
-                    }
-                }
-
-                res.take(0)?
+        MultiplePostSelectCriteria::Forum(id) => {
+            let id = RecordId::from_table_key("forum", id.0);
+            if filter.include_subforums {
+                conditions.push("forum IN $forum_ids");
+                forum_ids = forums::fetch_descendant_ids(state, &id)
+                    .await?
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                forum_ids.push(id);
+            } else {
+                conditions.push("forum = $forum_id");
+                forum_id = Some(id);
             }
-            _ => {
-                let mut query = state
-                .db
-                .query("SELECT * FROM post WHERE title @0@ $query OR content @1@ $query ORDER BY created_at ASC LIMIT $limit START $start");
-                if page_info.needs_page_info {
-                    query = query.query("SELECT count() as total FROM post WHERE title @0@ $query OR content @1@ $query")
-                }
-                let mut res = query
-                    .bind(("query", search))
-                    .bind(("limit", page_info.per_page))
-                    .bind(("start", (page_info.page - 1) * page_info.per_page))
-                    .await?;
-
-                if page_info.needs_page_info {
-                    if let Some(total) = res.take((1, "total"))? {
-                        page_info
-                            .total
-                            .0
-                            .store(total, std::sync::atomic::Ordering::Relaxed);
-                        page_info.has_next_page.0.store(
-                            total > (page_info.page - 1) * page_info.per_page + page_info.per_page,
-                            std::sync::atomic::Ordering::Relaxed,
-                        );
-                    }
-                }
-
-                res.take(0)?
+            pins_first = true;
+            "post"
+        }
+        MultiplePostSelectCriteria::Search(query) => {
+            if query != "*" {
+                conditions.push("(title @0@ $query OR content @1@ $query)");
+                search = Some(query);
             }
-        },
+            "post"
+        }
+    };
+
+    conditions.push("forum.deleted_at = NONE");
+    if !filter.show_nsfw {
+        conditions.push("nsfw != true");
+    }
+    if filter.flair.is_some() {
+        conditions.push("flair.id = $flair");
+    }
+
+    let top_since = match filter.sort {
+        PostSort::Top => filter
+            .top_window
+            .seconds()
+            .map(|s| Utc::now() - TimeDelta::seconds(s)),
+        _ => None,
+    };
+    if top_since.is_some() {
+        conditions.push(if pins_first {
+            "(created_at > $top_since OR pinned = true)"
+        } else {
+            "created_at > $top_since"
+        });
+    }
+
+    let where_clause = format!(" WHERE {}", conditions.join(" AND "));
+    let order = match filter.sort {
+        PostSort::New => "created_at DESC",
+        PostSort::Old => "created_at ASC",
+        PostSort::Top => "score DESC, created_at DESC",
+        PostSort::Hot => "hot_rank DESC",
+        PostSort::Controversial => "controversial_rank DESC, created_at DESC",
+        PostSort::Rising => "rising_rank DESC, created_at DESC",
     };
+    let order = if pins_first {
+        format!("pinned DESC, {order}")
+    } else {
+        order.to_string()
+    };
+
+    let mut query = state.db.query(format!(
+        "SELECT * FROM {from}{where_clause} ORDER BY {order} LIMIT $limit START $start"
+    ));
+
+    if page_info.needs_page_info {
+        query = query.query(format!(
+            "SELECT count() as total FROM {from}{where_clause} GROUP ALL"
+        ));
+    }
+
+    let mut res = query
+        .bind(("ids", ids))
+        .bind(("forum_id", forum_id))
+        .bind(("forum_ids", forum_ids))
+        .bind(("query", search))
+        .bind(("flair", filter.flair.clone()))
+        .bind(("top_since", top_since))
+        .bind(forums::viewer_access(state, viewer).await?)
+        .bind(("limit", page_info.per_page))
+        .bind((
+            "start",
+            (page_info.page - 1).saturating_mul(page_info.per_page),
+        ))
+        .await?;
+
+    if page_info.needs_page_info {
+        if let Some(total) = res.take((1, "total"))? {
+            page_info.set_total(total);
+        }
+    }
 
-    Ok(posts)
+    Ok(res.take(0)?)
 }
@@ -0,0 +1,139 @@
+use async_graphql::{Context, Object, ResultExt};
+use surrealdb::RecordId;
+
+use crate::{
+    error::RtwalkError,
+    gql::{bans, modlog, modmail, state, user},
+    mail::{self, NotificationKind},
+    models::{ban::ForumBan, forum::DBForum, moderator::ModPermission, modlog::ModAction, Key},
+};
+
+use super::super::ForumGuard;
+
+#[derive(Default)]
+pub struct BanMutationRoot;
+
+#[Object]
+impl BanMutationRoot {
+    /// Banned users can't post, comment or edit in the forum.
+    /// `duration` is in seconds, up to 100 years, leave it out for a permanent ban. Replaces any existing ban.
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::BanUsers)")]
+    async fn ban_from_forum<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+        user_id: Key,
+        #[graphql(validator(min_length = 1, max_length = 500))] reason: Option<String>,
+        #[graphql(validator(minimum = 1, maximum = 3_153_600_000))] duration: Option<u64>,
+        #[graphql(desc = "Id of the forum rule the user broke.")] rule_id: Option<String>,
+        #[graphql(desc = "Modmail thread with the user about the ban.")] modmail_thread_id: Option<
+            Key,
+        >,
+    ) -> async_graphql::Result<ForumBan> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let forum: Option<DBForum> = state.db.select(("forum", forum_id.0)).await?;
+
+        if let Some(forum) = forum {
+            let rule = match &rule_id {
+                Some(id) => Some(
+                    forum
+                        .rule(id)
+                        .ok_or(RtwalkError::RuleNotFound)
+                        .extend_err(|_, _| {})?,
+                ),
+                None => None,
+            };
+            let modmail_thread = match modmail_thread_id {
+                Some(thread) => Some(
+                    modmail::ensure_citable(state, &forum.id, &user_id, thread)
+                        .await
+                        .extend_err(|_, _| {})?,
+                ),
+                None => None,
+            };
+            let message = match (&reason, rule) {
+                (Some(reason), Some(rule)) => format!("{}\n\nRule: {}", reason, rule.title),
+                (Some(reason), None) => reason.clone(),
+                (None, Some(rule)) => format!("Rule: {}", rule.title),
+                (None, None) => "No reason was given.".to_string(),
+            };
+
+            let ban = bans::ban_user(
+                state,
+                &forum,
+                user_id.clone(),
+                reason,
+                duration,
+                user.id.clone(),
+                modmail_thread.clone(),
+            )
+            .await
+            .extend_err(|_, _| {})?;
+
+            modlog::record_citing(
+                state,
+                &forum.id,
+                &user.id,
+                ModAction::Ban,
+                Some(ban.user.clone()),
+                ban.reason.clone(),
+                rule_id,
+            )
+            .await
+            .extend_err(|_, _| {})?;
+
+            mail::notify(
+                state,
+                user_id,
+                NotificationKind::Moderation,
+                format!("You have been banned from {}", forum.display_name),
+                message,
+                match &modmail_thread {
+                    Some(thread) => format!("modmail/{}", thread.key()),
+                    None => format!("f/{}", forum.name),
+                },
+            );
+
+            Ok(ban.into())
+        } else {
+            Err(RtwalkError::ForumNotFound).extend_err(|_, _| {})
+        }
+    }
+
+    /// Returns false if the user wasn't banned.
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::BanUsers)")]
+    async fn unban_from_forum<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+        user_id: Key,
+    ) -> async_graphql::Result<bool> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let forum = RecordId::from_table_key("forum", forum_id.0);
+        let target = RecordId::from_table_key("user", user_id.0.clone());
+
+        let unbanned = bans::unban_user(state, &forum, user_id)
+            .await
+            .extend_err(|_, _| {})?;
+
+        if unbanned {
+            modlog::record(
+                state,
+                &forum,
+                &user.id,
+                ModAction::Unban,
+                Some(target),
+                None,
+            )
+            .await
+            .extend_err(|_, _| {})?;
+        }
+
+        Ok(unbanned)
+    }
+}
@@ -0,0 +1,120 @@
+use async_graphql::{Context, MaybeUndefined, Object, ResultExt};
+use surrealdb::RecordId;
+
+use crate::{
+    error::RtwalkError,
+    gql::{categories, state, user},
+    models::{category::Category, Key},
+};
+
+use super::super::Role;
+
+#[derive(Default)]
+pub struct CategoryQueryRoot;
+
+#[Object]
+impl CategoryQueryRoot {
+    /// Forum directory groups, list a category's forums with the `category` forum criteria.
+    async fn categories(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Category>> {
+        let state = state!(ctx);
+
+        let categories = categories::fetch_categories(state)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(categories.into_iter().map(|x| x.into()).collect())
+    }
+}
+
+#[derive(Default)]
+pub struct CategoryMutationRoot;
+
+#[Object]
+impl CategoryMutationRoot {
+    #[graphql(guard = Role::Admin)]
+    async fn create_category(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 1, max_length = 50))] name: String,
+        #[graphql(validator(min_length = 1, max_length = 500))] description: Option<String>,
+        #[graphql(default)] position: u32,
+    ) -> async_graphql::Result<Category> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let category = categories::create_category(state, name, description, position, user.id)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(category.into())
+    }
+
+    #[graphql(guard = Role::Admin)]
+    async fn update_category(
+        &self,
+        ctx: &Context<'_>,
+        category_id: Key,
+        #[graphql(validator(min_length = 1, max_length = 50))] name: Option<String>,
+        #[graphql(validator(min_length = 1, max_length = 500))] description: MaybeUndefined<String>,
+        position: Option<u32>,
+    ) -> async_graphql::Result<Category> {
+        let state = state!(ctx);
+
+        let mut category = categories::fetch_category(state, category_id)
+            .await
+            .extend_err(|_, _| {})?
+            .ok_or(RtwalkError::CategoryNotFound)
+            .extend_err(|_, _| {})?;
+
+        if let Some(name) = name {
+            category.name = name;
+        }
+        if !description.is_undefined() {
+            category.description = description.take();
+        }
+        if let Some(position) = position {
+            category.position = position;
+        }
+
+        let category = categories::update_category(state, category)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(category.into())
+    }
+
+    /// Forums in the category become uncategorized.
+    #[graphql(guard = Role::Admin)]
+    async fn delete_category(
+        &self,
+        ctx: &Context<'_>,
+        category_id: Key,
+    ) -> async_graphql::Result<bool> {
+        let state = state!(ctx);
+
+        categories::delete_category(state, category_id)
+            .await
+            .extend_err(|_, _| {})
+    }
+
+    /// `null` takes the forum out of its category.
+    #[graphql(guard = Role::Admin)]
+    async fn set_forum_category(
+        &self,
+        ctx: &Context<'_>,
+        forum_id: Key,
+        category_id: Option<Key>,
+    ) -> async_graphql::Result<bool> {
+        let state = state!(ctx);
+
+        categories::set_forum_category(
+            state,
+            &RecordId::from_table_key("forum", forum_id.0),
+            category_id,
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        Ok(true)
+    }
+}
@@ -1,20 +1,95 @@
-use async_graphql::{Context, MaybeUndefined, Object, OneofObject, ResultExt, Upload};
-use chrono::DateTime;
+use async_graphql::{
+    dataloader::DataLoader, ComplexObject, Context, MaybeUndefined, Object, OneofObject, ResultExt,
+    Upload,
+};
+use chrono::Utc;
 use cuid2::cuid;
-use rustis::commands::PubSubCommands;
+use surrealdb::RecordId;
 
 use crate::{
     config,
     error::RtwalkError,
-    gql::{comments, state, user},
+    gql::{
+        automod, bans, comments, emojis,
+        loaders::{EmojiLoader, PostForumLoader},
+        moderators, modlog,
+        posts::{self, Submission},
+        publish_event, revisions, state, user, viewer,
+    },
+    mail::{self, NotificationKind},
     models::{
         comment::{Comment, DBComment},
+        emoji::ContentToken,
         file::{File, FileOps},
+        moderator::ModPermission,
+        modlog::ModAction,
+        post::DBPost,
+        revision::{DBRevision, Revision},
         CommentCreateEvent, CommentEditEvent, Key, RtEvent, RtEventData, RtEventType,
     },
 };
 
-use super::super::Role;
+use super::{super::Role, posts::PostSelectCriteria};
+
+#[ComplexObject]
+impl Comment {
+    /// `content` split into text and the custom emoji of the post's forum.
+    async fn content_tokens(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<ContentToken>> {
+        let Some(content) = &self.content else {
+            return Ok(vec![]);
+        };
+        let post = RecordId::from_table_key("post", self.post_id.0.clone());
+        let Some(forum) = ctx
+            .data_unchecked::<DataLoader<PostForumLoader>>()
+            .load_one(post)
+            .await?
+        else {
+            return Ok(vec![]);
+        };
+        let emojis = ctx
+            .data_unchecked::<DataLoader<EmojiLoader>>()
+            .load_one(forum)
+            .await?
+            .unwrap_or_default();
+
+        Ok(emojis::tokenize(content, &emojis))
+    }
+
+    /// Earlier versions of the comment, newest first. Only moderators can see them unless
+    /// the forum made edit history public.
+    async fn revisions(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20, validator(maximum = 100))] per_page: u32,
+    ) -> async_graphql::Result<Vec<Revision>> {
+        let state = state!(ctx);
+        let viewer = viewer(ctx).await?;
+
+        let post = RecordId::from_table_key("post", self.post_id.0.clone());
+        let forum = posts::post_forum(state, &post)
+            .await
+            .extend_err(|_, _| {})?
+            .ok_or(RtwalkError::PostNotFound)
+            .extend_err(|_, _| {})?;
+        revisions::ensure_can_view(
+            state,
+            viewer.as_ref(),
+            &forum,
+            ModPermission::ManageComments,
+            false,
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        let comment = RecordId::from_table_key("comment", self.id.0.clone());
+        let revisions = revisions::fetch_revisions(state, &comment, page, per_page)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(revisions.into_iter().map(|x| x.into()).collect())
+    }
+}
 
 #[derive(Default)]
 pub struct CommentMutationRoot;
@@ -32,9 +107,65 @@ impl CommentMutationRoot {
         let user = user!(ctx);
         let state = state!(ctx);
 
+        let commented_post =
+            posts::fetch_post(state, PostSelectCriteria::Id(post.clone()), Some(&user))
+                .await
+                .extend_err(|_, _| {})?
+                .ok_or(RtwalkError::PostNotFound)
+                .extend_err(|_, _| {})?;
+
+        // The author still sees their removed or held post, but only moderators can
+        // comment on it.
+        if (commented_post.removed || commented_post.held)
+            && !moderators::has_permission(
+                state,
+                &user,
+                &commented_post.forum,
+                ModPermission::ManagePosts,
+            )
+            .await
+            .extend_err(|_, _| {})?
+        {
+            return Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {});
+        }
+
+        let forum = posts::ensure_can_post(
+            state,
+            &user,
+            &commented_post.forum,
+            Some(&commented_post),
+            ModPermission::ManageComments,
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        bans::ensure_not_banned(state, &commented_post.forum, &user.id)
+            .await
+            .extend_err(|_, _| {})?;
+
+        let attachments = attachments
+            .into_iter()
+            .map(|v| v.value(ctx))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        posts::ensure_meets_requirements(
+            state,
+            &user,
+            &forum,
+            Submission::Comment,
+            &attachments,
+            ModPermission::ManageComments,
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        let attachment_types: Vec<String> = attachments
+            .iter()
+            .filter_map(|a| a.content_type.clone())
+            .collect();
+
         let mut uploads = vec![];
-        for v in attachments {
-            let mut upload_value = v.value(&ctx)?;
+        for mut upload_value in attachments {
             if upload_value.size()? > config::MAX_UPLOAD_SIZE {
                 return Err(RtwalkError::MaxUploadSizeExceeded).extend_err(|_, _| {})?;
             }
@@ -54,20 +185,52 @@ impl CommentMutationRoot {
             uploads.push(f);
         }
 
-        let comment: Comment = comments::create_comment(&state, content, uploads, user.id, post)
+        let comment = comments::create_comment(
+            state,
+            content,
+            uploads,
+            user.id.clone(),
+            post.clone(),
+            &forum.id,
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        posts::record_submission(state, &user.id, &forum, Submission::Comment)
             .await
-            .extend_err(|_, _| {})?
-            .into();
+            .extend_err(|_, _| {})?;
 
-        state.redis.publish(
-            "rte-comment-create",
-            serde_json::to_vec(&RtEvent {
+        let comment: Comment =
+            automod::check_comment(state, &forum, &user, comment, &attachment_types, false)
+                .await
+                .extend_err(|_, _| {})?
+                .into();
+
+        // Removed and held comments don't notify or get announced.
+        if comment.removed || comment.held {
+            return Ok(comment);
+        }
+
+        if commented_post.poster.key() != &user.id.0 {
+            mail::notify(
+                state,
+                Key(commented_post.poster.key().to_owned()),
+                NotificationKind::PostComment,
+                format!("{} commented on your post", user.display_name),
+                format!("New comment on \"{}\".", commented_post.title),
+                format!("post/{}", commented_post.id.key()),
+            );
+        }
+
+        publish_event(
+            state,
+            &Key(forum.id.key().to_owned()),
+            &RtEvent {
                 ty: RtEventType::CommentCreate,
                 event_data: RtEventData::CommentCreate(CommentCreateEvent {
                     data: comment.clone(),
                 }),
-            })
-            .expect("Cant fail to serialize self constructed data"),
+            },
         );
 
         Ok(comment)
@@ -87,10 +250,40 @@ impl CommentMutationRoot {
         let comment: Option<DBComment> = state.db.select(("comment", comment_id.0)).await?;
 
         if let Some(mut comment) = comment {
-            let original_comment: Comment = comment.clone().into();
+            let previous = comment.clone();
+            let original_comment: Comment = previous.clone().into();
+
+            let post: DBPost = state
+                .db
+                .select(&comment.post)
+                .await?
+                .ok_or(RtwalkError::PostNotFound)
+                .extend_err(|_, _| {})?;
+            let forum = post.forum.clone();
+
+            let forum_data = posts::ensure_can_post(
+                state,
+                &user,
+                &forum,
+                Some(&post),
+                ModPermission::ManageComments,
+            )
+            .await
+            .extend_err(|_, _| {})?;
 
+            bans::ensure_not_banned(state, &forum, &user.id)
+                .await
+                .extend_err(|_, _| {})?;
+
+            // Moderators can strip attachments from others' comments but not edit them.
             if &user.id.0 != comment.commenter.key() {
-                return Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {});
+                let moderator =
+                    moderators::has_permission(state, &user, &forum, ModPermission::ManageComments)
+                        .await
+                        .extend_err(|_, _| {})?;
+                if !moderator || !content.is_undefined() {
+                    return Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {});
+                }
             }
 
             if content.is_null() {
@@ -99,35 +292,104 @@ impl CommentMutationRoot {
                 comment.content = Some(content);
             }
 
+            // The files stay for the revision below.
             if remove_attachments {
-                for attachment in &comment.attachments {
-                    attachment.delete(&state.op).await.extend_err(|_, _| {})?;
-                }
+                comment.attachments = vec![];
             }
 
-            comment.edited_at = DateTime::default();
+            if comment.content != previous.content || comment.attachments != previous.attachments {
+                revisions::create_revision(
+                    state,
+                    DBRevision::of_comment(&previous, forum.clone(), user.id.clone()),
+                )
+                .await
+                .extend_err(|_, _| {})?;
+            }
+
+            comment.edited_at = Utc::now();
 
             let res: Option<DBComment> = state.db.update(&comment.id).content(comment).await?;
 
-            let updated_comment: Comment = res.expect("Comment exists").into();
-
-            state.redis.publish(
-                "rte-post-update",
-                serde_json::to_vec(&RtEvent {
-                    ty: RtEventType::CommentEdit,
-                    event_data: RtEventData::CommentEdit(CommentEditEvent {
-                        original: original_comment,
-                        new: updated_comment.clone(),
-                    }),
-                })
-                .expect("Cant fail to serialize self constructed data"),
-            );
+            let updated_comment: Comment = automod::check_comment(
+                state,
+                &forum_data,
+                &user,
+                res.expect("Comment exists"),
+                &[],
+                true,
+            )
+            .await
+            .extend_err(|_, _| {})?
+            .into();
+
+            if !updated_comment.removed && !updated_comment.held {
+                publish_event(
+                    state,
+                    &Key(forum.key().to_owned()),
+                    &RtEvent {
+                        ty: RtEventType::CommentEdit,
+                        event_data: RtEventData::CommentEdit(CommentEditEvent {
+                            original: original_comment,
+                            new: updated_comment.clone(),
+                        }),
+                    },
+                );
+            }
 
             Ok(updated_comment)
         } else {
             Err(RtwalkError::CommentNotFound).extend_err(|_, _| {})
         }
     }
+
+    /// Releases a comment AutoModerator held or removed.
+    #[graphql(guard = Role::Authenticated)]
+    async fn approve_comment<'r>(
+        &self,
+        ctx: &Context<'r>,
+        comment_id: Key,
+    ) -> async_graphql::Result<Comment> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let comment: Option<DBComment> = state.db.select(("comment", comment_id.0)).await?;
+
+        if let Some(mut comment) = comment {
+            let forum = posts::post_forum(state, &comment.post)
+                .await
+                .extend_err(|_, _| {})?
+                .ok_or(RtwalkError::PostNotFound)
+                .extend_err(|_, _| {})?;
+
+            moderators::ensure_permission(state, &user, &forum, ModPermission::ManageComments)
+                .await
+                .extend_err(|_, _| {})?;
+
+            let was_counted = comment.is_counted();
+            comment.held = false;
+            comment.removed = false;
+            comment.removal_reason = None;
+
+            let comment = comments::save_comment(state, comment, &forum, was_counted)
+                .await
+                .extend_err(|_, _| {})?;
+
+            modlog::record(
+                state,
+                &forum,
+                &user.id,
+                ModAction::ApproveComment,
+                Some(comment.id.clone()),
+                None,
+            )
+            .await
+            .extend_err(|_, _| {})?;
+
+            Ok(comment.into())
+        } else {
+            Err(RtwalkError::CommentNotFound).extend_err(|_, _| {})
+        }
+    }
 }
 
 // TODO: Add more useful criterias
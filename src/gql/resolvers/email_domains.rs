@@ -0,0 +1,70 @@
+use async_graphql::{Context, Object, ResultExt};
+
+use crate::{
+    gql::{email_domains, state, user},
+    models::email_domain::{EmailDomain, EmailDomainListKind},
+};
+
+use super::super::Role;
+
+#[derive(Default)]
+pub struct EmailDomainQueryRoot;
+
+#[Object]
+impl EmailDomainQueryRoot {
+    /// Admin managed registration allow/deny lists.
+    /// Doesn't include the bundled disposable domain list.
+    #[graphql(guard = Role::Admin)]
+    async fn email_domains(
+        &self,
+        ctx: &Context<'_>,
+        kind: Option<EmailDomainListKind>,
+    ) -> async_graphql::Result<Vec<EmailDomain>> {
+        let state = state!(ctx);
+
+        let domains = email_domains::fetch_email_domains(state, kind)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(domains.into_iter().map(|x| x.into()).collect())
+    }
+}
+
+#[derive(Default)]
+pub struct EmailDomainMutationRoot;
+
+#[Object]
+impl EmailDomainMutationRoot {
+    /// Adds the domain (and its subdomains) to a list, moving it if it's already on the other one.
+    /// Once the allowlist has an entry only allowlisted domains can register.
+    #[graphql(guard = Role::Admin)]
+    async fn add_email_domain(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(max_length = 100, regex = r"^[a-zA-Z0-9-]+(\.[a-zA-Z0-9-]+)+$"))]
+        domain: String,
+        kind: EmailDomainListKind,
+    ) -> async_graphql::Result<EmailDomain> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let domain = email_domains::add_email_domain(state, domain, kind, user.id)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(domain.into())
+    }
+
+    #[graphql(guard = Role::Admin)]
+    async fn remove_email_domain(
+        &self,
+        ctx: &Context<'_>,
+        domain: String,
+    ) -> async_graphql::Result<bool> {
+        let state = state!(ctx);
+
+        email_domains::remove_email_domain(state, domain)
+            .await
+            .extend_err(|_, _| {})
+    }
+}
@@ -0,0 +1,83 @@
+use async_graphql::{Context, Object, ResultExt, Upload};
+use surrealdb::RecordId;
+
+use crate::{
+    error::RtwalkError,
+    gql::{emojis, modlog, state, user},
+    models::{emoji::Emoji, moderator::ModPermission, modlog::ModAction, Key},
+};
+
+use super::super::ForumGuard;
+
+#[derive(Default)]
+pub struct EmojiMutationRoot;
+
+#[Object]
+impl EmojiMutationRoot {
+    /// PNG, GIF, WebP or JPEG images up to 256kb. `:name:` in the forum's posts and
+    /// comments shows up as the emoji in their `contentTokens`.
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::EditForum)")]
+    async fn add_forum_emoji(
+        &self,
+        ctx: &Context<'_>,
+        forum_id: Key,
+        #[graphql(validator(min_length = 2, max_length = 32, regex = r"^[a-z0-9_]+$"))]
+        name: String,
+        image: Upload,
+    ) -> async_graphql::Result<Emoji> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+        let forum = RecordId::from_table_key("forum", forum_id.0);
+
+        let emoji = emojis::create_emoji(state, &forum, name, image.value(ctx)?, user.id.clone())
+            .await
+            .extend_err(|_, _| {})?;
+
+        modlog::record(
+            state,
+            &forum,
+            &user.id,
+            ModAction::AddEmoji,
+            Some(emoji.id.clone()),
+            None,
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        Ok(emoji.into())
+    }
+
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::EditForum)")]
+    async fn delete_forum_emoji(
+        &self,
+        ctx: &Context<'_>,
+        forum_id: Key,
+        name: String,
+    ) -> async_graphql::Result<Emoji> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+        let forum = RecordId::from_table_key("forum", forum_id.0);
+
+        let emoji = emojis::fetch_emoji(state, &forum, name)
+            .await
+            .extend_err(|_, _| {})?
+            .ok_or(RtwalkError::EmojiNotFound)
+            .extend_err(|_, _| {})?;
+        emojis::delete_emoji(state, &emoji)
+            .await
+            .extend_err(|_, _| {})?;
+
+        modlog::record(
+            state,
+            &forum,
+            &user.id,
+            ModAction::DeleteEmoji,
+            None,
+            Some(emoji.name.clone()),
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        Ok(emoji.into())
+    }
+}
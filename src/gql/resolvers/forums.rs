@@ -1,144 +1,1506 @@
-use async_graphql::{Context, MaybeUndefined, Object, OneofObject, ResultExt, Upload};
+use std::collections::HashSet;
+
+use async_graphql::{
+    ComplexObject, Context, InputObject, MaybeUndefined, Object, OneofObject, ResultExt, Upload,
+};
 use cuid2::cuid;
+use surrealdb::RecordId;
+
+use crate::{
+    config,
+    error::RtwalkError,
+    gql::{
+        automod, bans, categories, comments, emojis, forums, members, moderators, modlog, modmail,
+        posts, publish_event, state, stats, user, users, viewer, wiki, PageInfo,
+    },
+    mail::{self, NotificationKind},
+    models::{
+        automod::{Automod, AutomodRule},
+        ban::ForumBan,
+        category::Category,
+        comment::Comment,
+        emoji::Emoji,
+        file::{File, FileOps},
+        forum::{
+            DBForum, Flair, Forum, ForumRule, ForumSettings, ForumSettingsInput, ForumTransfer,
+            ForumVisibility,
+        },
+        member::{JoinRequest, Member},
+        moderator::{ModPermission, Moderator},
+        modlog::{ModAction, ModLogEntry},
+        modmail::ModmailThread,
+        post::Post,
+        stats::{Contributor, ForumStats},
+        user::User,
+        wiki::WikiPage,
+        ForumLockEvent, Key, RtEvent, RtEventData, RtEventType,
+    },
+    state::State,
+};
+
+use super::{
+    super::{ForumGuard, Role},
+    users::UserSelectCriteria,
+    wiki::MultipleWikiPageSelectCriteria,
+};
+
+impl Forum {
+    /// [`forums::can_read`], only looking the forum up again when it's private.
+    async fn readable(&self, ctx: &Context<'_>) -> async_graphql::Result<bool> {
+        if self.settings.visibility != ForumVisibility::Private {
+            return Ok(true);
+        }
+        let state = state!(ctx);
+        let viewer = viewer(ctx).await?;
+
+        let forum: Option<DBForum> = state.db.select(("forum", self.id.0.clone())).await?;
+        let Some(forum) = forum else {
+            return Ok(false);
+        };
+
+        forums::can_read(state, viewer.as_ref(), &forum)
+            .await
+            .extend_err(|_, _| {})
+    }
+}
+
+/// Private forums the viewer can't read only show what's needed to ask to join: name,
+/// display name, description, icon and `visibility`. Everything else is `null` or empty.
+#[ComplexObject]
+impl Forum {
+    async fn visibility(&self) -> ForumVisibility {
+        self.settings.visibility
+    }
+
+    async fn settings(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<ForumSettings>> {
+        if !self.readable(ctx).await? {
+            return Ok(None);
+        }
+
+        Ok(Some(self.settings.clone()))
+    }
+
+    async fn rules(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<ForumRule>> {
+        if !self.readable(ctx).await? {
+            return Ok(vec![]);
+        }
+
+        Ok(self.rules.clone())
+    }
+
+    async fn flairs(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Flair>> {
+        if !self.readable(ctx).await? {
+            return Ok(vec![]);
+        }
+
+        Ok(self.flairs.clone())
+    }
+
+    async fn parent_id(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Key>> {
+        if !self.readable(ctx).await? {
+            return Ok(None);
+        }
+
+        Ok(self.parent_id.clone())
+    }
+
+    async fn parent(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Forum>> {
+        let state = state!(ctx);
+
+        let Some(parent) = &self.parent_id else {
+            return Ok(None);
+        };
+        if !self.readable(ctx).await? {
+            return Ok(None);
+        }
+        let forum = forums::fetch_forum(state, ForumSelectCriteria::Id(parent.to_string()))
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(forum.map(|x| x.into()))
+    }
+
+    /// Direct sub-forums, oldest first.
+    async fn children(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20, validator(maximum = 100))] per_page: u32,
+    ) -> async_graphql::Result<Vec<Forum>> {
+        let state = state!(ctx);
+        let viewer = viewer(ctx).await?;
+
+        let page_info = PageInfo {
+            page: page.max(1),
+            per_page,
+            ..Default::default()
+        };
+        let forums = forums::fetch_forums(
+            state,
+            MultipleForumSelectCriteria::Parent(self.id.to_string()),
+            viewer.as_ref(),
+            &page_info,
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        Ok(forums.into_iter().map(|x| x.into()).collect())
+    }
+
+    /// Activity over the last `days` days, counted in UTC days. `null` for private forums
+    /// the viewer can't read.
+    async fn stats(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 30, validator(minimum = 1, maximum = 90))] days: u32,
+        #[graphql(default = 10, validator(maximum = 50))] top: u32,
+    ) -> async_graphql::Result<Option<ForumStats>> {
+        let state = state!(ctx);
+        let viewer = viewer(ctx).await?;
+
+        let forum: Option<DBForum> = state.db.select(("forum", self.id.0.clone())).await?;
+        let Some(forum) = forum else {
+            return Ok(None);
+        };
+        if !forums::can_read(state, viewer.as_ref(), &forum)
+            .await
+            .extend_err(|_, _| {})?
+        {
+            return Ok(None);
+        }
+        let (post_count, comment_count) = (forum.post_count, forum.comment_count);
+        let forum = forum.id;
+
+        let daily = stats::fetch_daily(state, &forum, days)
+            .await
+            .extend_err(|_, _| {})?;
+        let top_contributors = stats::fetch_top_contributors(state, &forum, days, top as usize)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(Some(ForumStats {
+            member_count: self.member_count,
+            post_count,
+            comment_count,
+            daily,
+            top_contributors,
+        }))
+    }
+
+    /// Custom emoji, ordered by name.
+    async fn emojis(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Emoji>> {
+        let state = state!(ctx);
+
+        if !self.readable(ctx).await? {
+            return Ok(vec![]);
+        }
+        let forum = RecordId::from_table_key("forum", self.id.0.clone());
+
+        let emojis = emojis::fetch_emojis(state, &forum)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(emojis.into_iter().map(|x| x.into()).collect())
+    }
+
+    /// Private forums' pages are only shown to their members and moderators.
+    async fn wiki_page(
+        &self,
+        ctx: &Context<'_>,
+        slug: String,
+    ) -> async_graphql::Result<Option<WikiPage>> {
+        let state = state!(ctx);
+        let viewer = viewer(ctx).await?;
+
+        let forum: Option<DBForum> = state.db.select(("forum", self.id.0.clone())).await?;
+        let Some(forum) = forum else {
+            return Ok(None);
+        };
+        if !forums::can_read(state, viewer.as_ref(), &forum)
+            .await
+            .extend_err(|_, _| {})?
+        {
+            return Ok(None);
+        }
+
+        let page = wiki::fetch_page(state, &forum.id, slug)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(page.map(|x| x.into()))
+    }
+
+    /// Ordered by slug.
+    async fn wiki_pages(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20, validator(maximum = 100))] per_page: u32,
+    ) -> async_graphql::Result<Vec<WikiPage>> {
+        let state = state!(ctx);
+        let viewer = viewer(ctx).await?;
+
+        let page_info = PageInfo {
+            page: page.max(1),
+            per_page,
+            ..Default::default()
+        };
+        let pages = wiki::fetch_pages(
+            state,
+            MultipleWikiPageSelectCriteria::Forum(self.id.to_string()),
+            viewer.as_ref(),
+            &page_info,
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        Ok(pages.into_iter().map(|x| x.into()).collect())
+    }
+
+    /// Parent forums from the top level down, without this forum.
+    async fn breadcrumbs(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Forum>> {
+        let state = state!(ctx);
+
+        if !self.readable(ctx).await? {
+            return Ok(vec![]);
+        }
+
+        let forum: Option<DBForum> = state.db.select(("forum", self.id.0.clone())).await?;
+        let Some(forum) = forum else {
+            return Ok(vec![]);
+        };
+        let ancestors = forums::fetch_ancestors(state, &forum)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(ancestors
+            .into_iter()
+            .rev()
+            .filter(|x| x.deleted_at.is_none())
+            .map(|x| x.into())
+            .collect())
+    }
+
+    async fn category(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Category>> {
+        let state = state!(ctx);
+
+        let Some(category) = &self.category_id else {
+            return Ok(None);
+        };
+        let category = categories::fetch_category(state, category.clone())
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(category.map(|x| x.into()))
+    }
+
+    /// The owner isn't listed here.
+    async fn moderators(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20, validator(maximum = 100))] per_page: u32,
+    ) -> async_graphql::Result<Vec<Moderator>> {
+        let state = state!(ctx);
+
+        if !self.readable(ctx).await? {
+            return Ok(vec![]);
+        }
+
+        let moderators = moderators::fetch_moderators(
+            state,
+            &RecordId::from_table_key("forum", self.id.0.clone()),
+            page,
+            per_page,
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        Ok(moderators.into_iter().map(|x| x.into()).collect())
+    }
+
+    /// Your ban in this forum, if you have one. Expired bans are returned with `active: false`.
+    async fn my_ban(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<ForumBan>> {
+        let state = state!(ctx);
+
+        if let Some(viewer) = viewer(ctx).await? {
+            let ban = bans::fetch_ban(
+                state,
+                &RecordId::from_table_key("forum", self.id.0.clone()),
+                &viewer.id,
+            )
+            .await
+            .extend_err(|_, _| {})?;
+
+            return Ok(ban.map(|x| x.into()));
+        }
+        Ok(None)
+    }
+
+    /// Needs `BAN_USERS`.
+    #[graphql(guard = Role::Authenticated)]
+    async fn bans(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20, validator(maximum = 100))] per_page: u32,
+    ) -> async_graphql::Result<Vec<ForumBan>> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+        let forum = RecordId::from_table_key("forum", self.id.0.clone());
+
+        moderators::ensure_permission(state, &user, &forum, ModPermission::BanUsers)
+            .await
+            .extend_err(|_, _| {})?;
+
+        let bans = bans::fetch_bans(state, &forum, page, per_page)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(bans.into_iter().map(|x| x.into()).collect())
+    }
+
+    /// Only visible to the owner and the user the forum is offered to.
+    async fn pending_transfer(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<ForumTransfer>> {
+        let state = state!(ctx);
+
+        if let Some(viewer) = viewer(ctx).await? {
+            let transfer = forums::fetch_transfer(
+                state,
+                &RecordId::from_table_key("forum", self.id.0.clone()),
+            )
+            .await
+            .extend_err(|_, _| {})?;
+
+            return Ok(transfer
+                .filter(|t| t.from == viewer.id.to_string() || t.to == viewer.id.to_string())
+                .map(|x| x.into()));
+        }
+        Ok(None)
+    }
+
+    /// Oldest members first. Visible to every moderator.
+    #[graphql(guard = Role::Authenticated)]
+    async fn members(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20, validator(maximum = 100))] per_page: u32,
+    ) -> async_graphql::Result<Vec<Member>> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+        let forum = RecordId::from_table_key("forum", self.id.0.clone());
+
+        let permissions = moderators::fetch_permissions(state, &user, &forum)
+            .await
+            .extend_err(|_, _| {})?;
+        if permissions.is_none() {
+            return Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {});
+        }
+
+        let members = members::fetch_members(state, &forum, page, per_page)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(members.into_iter().map(|x| x.into()).collect())
+    }
+
+    /// Needs `MANAGE_MEMBERS`.
+    #[graphql(guard = Role::Authenticated)]
+    async fn join_requests(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20, validator(maximum = 100))] per_page: u32,
+    ) -> async_graphql::Result<Vec<JoinRequest>> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+        let forum = RecordId::from_table_key("forum", self.id.0.clone());
+
+        moderators::ensure_permission(state, &user, &forum, ModPermission::ManageMembers)
+            .await
+            .extend_err(|_, _| {})?;
+
+        let requests = members::fetch_join_requests(state, &forum, page, per_page)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(requests.into_iter().map(|x| x.into()).collect())
+    }
+
+    /// Your pending request to join, if you have one.
+    async fn my_join_request(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<JoinRequest>> {
+        let state = state!(ctx);
+
+        if let Some(viewer) = viewer(ctx).await? {
+            let request = members::fetch_join_request(
+                state,
+                &RecordId::from_table_key("forum", self.id.0.clone()),
+                &viewer.id,
+            )
+            .await
+            .extend_err(|_, _| {})?;
+
+            return Ok(request.map(|x| x.into()));
+        }
+        Ok(None)
+    }
+
+    /// Whether you're a member, false when logged out.
+    async fn joined(&self, ctx: &Context<'_>) -> async_graphql::Result<bool> {
+        let state = state!(ctx);
+
+        if let Some(viewer) = viewer(ctx).await? {
+            return members::is_member(
+                state,
+                &RecordId::from_table_key("forum", self.id.0.clone()),
+                &RecordId::from_table_key("user", viewer.id.0),
+            )
+            .await
+            .extend_err(|_, _| {});
+        }
+        Ok(false)
+    }
+
+    /// Moderator actions taken in the forum, newest first. Visible to every moderator.
+    #[graphql(guard = Role::Authenticated)]
+    async fn mod_log(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20, validator(maximum = 100))] per_page: u32,
+    ) -> async_graphql::Result<Vec<ModLogEntry>> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+        let forum = RecordId::from_table_key("forum", self.id.0.clone());
+
+        let permissions = moderators::fetch_permissions(state, &user, &forum)
+            .await
+            .extend_err(|_, _| {})?;
+        if permissions.is_none() {
+            return Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {});
+        }
+
+        let entries = modlog::fetch_mod_log(state, &forum, page, per_page)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(entries.into_iter().map(|x| x.into()).collect())
+    }
+
+    /// Posts AutoModerator held for review, oldest first.
+    #[graphql(guard = Role::Authenticated)]
+    async fn held_posts(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20, validator(maximum = 100))] per_page: u32,
+    ) -> async_graphql::Result<Vec<Post>> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+        let forum = RecordId::from_table_key("forum", self.id.0.clone());
+
+        moderators::ensure_permission(state, &user, &forum, ModPermission::ManagePosts)
+            .await
+            .extend_err(|_, _| {})?;
+
+        let posts = posts::fetch_held_posts(state, &forum, page, per_page)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(posts.into_iter().map(|x| x.into()).collect())
+    }
+
+    /// Comments AutoModerator held for review, oldest first.
+    #[graphql(guard = Role::Authenticated)]
+    async fn held_comments(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20, validator(maximum = 100))] per_page: u32,
+    ) -> async_graphql::Result<Vec<Comment>> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+        let forum = RecordId::from_table_key("forum", self.id.0.clone());
+
+        moderators::ensure_permission(state, &user, &forum, ModPermission::ManageComments)
+            .await
+            .extend_err(|_, _| {})?;
+
+        let comments = comments::fetch_held_comments(state, &forum, page, per_page)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(comments.into_iter().map(|x| x.into()).collect())
+    }
+
+    /// Modmail threads, most recently active first. Needs `MANAGE_MODMAIL`.
+    #[graphql(guard = Role::Authenticated)]
+    async fn modmail(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] archived: bool,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20, validator(maximum = 100))] per_page: u32,
+    ) -> async_graphql::Result<Vec<ModmailThread>> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+        let forum = RecordId::from_table_key("forum", self.id.0.clone());
+
+        moderators::ensure_permission(state, &user, &forum, ModPermission::ManageModmail)
+            .await
+            .extend_err(|_, _| {})?;
+
+        let threads = modmail::fetch_forum_threads(state, &forum, archived, page, per_page)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(threads.into_iter().map(|x| x.into()).collect())
+    }
+
+    /// Needs `EDIT_FORUM`, rules can include keywords the forum doesn't want public.
+    #[graphql(guard = Role::Authenticated)]
+    async fn automod(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Automod>> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+        let forum = RecordId::from_table_key("forum", self.id.0.clone());
+
+        moderators::ensure_permission(state, &user, &forum, ModPermission::EditForum)
+            .await
+            .extend_err(|_, _| {})?;
+
+        let automod = automod::fetch_automod(state, &forum)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(automod.map(|x| x.into()))
+    }
+}
+
+#[ComplexObject]
+impl Contributor {
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        let state = state!(ctx);
+
+        let user = users::fetch_user(state, UserSelectCriteria::Id(self.user_id.to_string()))
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(user.map(|x| x.into()))
+    }
+}
+
+#[ComplexObject]
+impl Member {
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        let state = state!(ctx);
+
+        let user = users::fetch_user(state, UserSelectCriteria::Id(self.user_id.to_string()))
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(user.map(|x| x.into()))
+    }
+}
+
+#[ComplexObject]
+impl JoinRequest {
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        let state = state!(ctx);
+
+        let user = users::fetch_user(state, UserSelectCriteria::Id(self.user_id.to_string()))
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(user.map(|x| x.into()))
+    }
+}
+
+#[ComplexObject]
+impl Moderator {
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        let state = state!(ctx);
+
+        let user = users::fetch_user(state, UserSelectCriteria::Id(self.user_id.to_string()))
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(user.map(|x| x.into()))
+    }
+}
+
+#[derive(InputObject)]
+pub struct ForumRuleInput {
+    /// Keep the id of an existing rule so mod log entries citing it still resolve.
+    id: Option<String>,
+    #[graphql(validator(min_length = 1, max_length = 100))]
+    title: String,
+    #[graphql(validator(min_length = 1, max_length = 1_000))]
+    description: Option<String>,
+}
+
+#[derive(InputObject)]
+pub struct FlairInput {
+    /// Keep the id of an existing flair, posts carry a copy of it.
+    id: Option<String>,
+    #[graphql(validator(min_length = 1, max_length = 32))]
+    text: String,
+    #[graphql(validator(regex = r"^#[0-9a-fA-F]{6}$"))]
+    color: String,
+}
+
+#[derive(Default)]
+pub struct ForumMutationRoot;
+
+#[Object]
+impl ForumMutationRoot {
+    #[graphql(guard = Role::Human)]
+    async fn create_forum(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 3, max_length = 20, regex = r"^[a-z0-9_]+$"))]
+        name: String,
+    ) -> async_graphql::Result<Forum> {
+        let user = user!(ctx);
+        let state = state!(ctx);
+
+        let forum = forums::create_forum(&state, name.into(), user.id)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(forum.into())
+    }
+
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::EditForum)")]
+    async fn update_forum<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+        #[graphql(validator(min_length = 3, max_length = 20, regex = r"^[a-z0-9_]+$"))]
+        name: Option<String>,
+        display_name: Option<String>,
+        description: MaybeUndefined<String>,
+        icon: MaybeUndefined<Upload>,
+        banner: MaybeUndefined<Upload>,
+    ) -> async_graphql::Result<Forum> {
+        let state = state!(ctx);
+
+        let forum: Option<DBForum> = state.db.select(("forum", forum_id.0)).await?;
+
+        if let Some(mut forum) = forum {
+            if let Some(name) = name {
+                forum.name = name;
+            }
+
+            if let Some(display_name) = display_name {
+                forum.display_name = display_name;
+            }
+
+            if description.is_null() {
+                forum.description = None;
+            } else if let MaybeUndefined::Value(description) = description {
+                forum.description = Some(description);
+            }
+
+            if icon.is_null() {
+                forum.icon.delete(&state.op).await.extend_err(|_, _| {})?;
+
+                forum.icon = None;
+            } else if let MaybeUndefined::Value(v) = icon {
+                let mut upload_value = v.value(&ctx)?;
+                if upload_value.size()? > config::MAX_UPLOAD_SIZE {
+                    return Err(RtwalkError::MaxUploadSizeExceeded).extend_err(|_, _| {})?;
+                }
+
+                forum.icon.delete(&state.op).await.extend_err(|_, _| {})?;
+
+                let icon_file = File {
+                    loc: format!("{}/{}-{}", forum.id, cuid(), upload_value.filename),
+                };
+                icon_file
+                    .save(&state.op, &mut upload_value)
+                    .await
+                    .extend_err(|_, _| {})?;
+                forum.icon = Some(icon_file);
+            }
+
+            if banner.is_null() {
+                forum.banner.delete(&state.op).await.extend_err(|_, _| {})?;
+
+                forum.banner = None;
+            } else if let MaybeUndefined::Value(v) = banner {
+                let mut upload_value = v.value(&ctx)?;
+                if upload_value.size()? > config::MAX_UPLOAD_SIZE {
+                    return Err(RtwalkError::MaxUploadSizeExceeded).extend_err(|_, _| {})?;
+                }
+
+                forum.banner.delete(&state.op).await.extend_err(|_, _| {})?;
+
+                let banner_file = File {
+                    loc: format!("{}/{}-{}", &forum.id, cuid(), upload_value.filename),
+                };
+                banner_file
+                    .save(&state.op, &mut upload_value)
+                    .await
+                    .extend_err(|_, _| {})?;
+                forum.banner = Some(banner_file);
+            }
+
+            let res: Option<DBForum> = state.db.update(&forum.id).content(forum).await?;
+
+            Ok(res.expect("Forum exists").into())
+        } else {
+            Err(RtwalkError::ForumNotFound).extend_err(|_, _| {})
+        }
+    }
+
+    /// Moves the forum under another one, `null` makes it top level again. Needs
+    /// `EDIT_FORUM` in both forums.
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::EditForum)")]
+    async fn set_forum_parent<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+        parent_id: Option<Key>,
+    ) -> async_graphql::Result<Forum> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let forum = forums::fetch_forum(state, ForumSelectCriteria::Id(forum_id.to_string()))
+            .await
+            .extend_err(|_, _| {})?
+            .ok_or(RtwalkError::ForumNotFound)
+            .extend_err(|_, _| {})?;
+
+        let parent = match parent_id {
+            Some(parent_id) => {
+                let parent =
+                    forums::fetch_forum(state, ForumSelectCriteria::Id(parent_id.to_string()))
+                        .await
+                        .extend_err(|_, _| {})?
+                        .ok_or(RtwalkError::ForumNotFound)
+                        .extend_err(|_, _| {})?;
+                moderators::ensure_permission(state, &user, &parent.id, ModPermission::EditForum)
+                    .await
+                    .extend_err(|_, _| {})?;
+                Some(parent)
+            }
+            None => None,
+        };
+
+        let forum = forums::set_parent(state, &forum, parent.as_ref())
+            .await
+            .extend_err(|_, _| {})?;
+
+        modlog::record(
+            state,
+            &forum.id,
+            &user.id,
+            ModAction::SetForumParent,
+            parent.map(|p| p.id),
+            None,
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        Ok(forum.into())
+    }
+
+    /// Replaces only the settings that are given.
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::EditForum)")]
+    async fn update_forum_settings<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+        settings: ForumSettingsInput,
+    ) -> async_graphql::Result<ForumSettings> {
+        let state = state!(ctx);
+
+        let forum: Option<DBForum> = state.db.select(("forum", forum_id.0)).await?;
+
+        if let Some(mut forum) = forum {
+            settings.apply(&mut forum.settings);
+
+            let res: Option<DBForum> = state.db.update(&forum.id).content(forum).await?;
+
+            Ok(res.expect("Forum exists").settings)
+        } else {
+            Err(RtwalkError::ForumNotFound).extend_err(|_, _| {})
+        }
+    }
+
+    /// Replaces the forum's AutoModerator rules. They run in order on new and edited posts
+    /// and comments, every matching rule applies. Moderators' content is never checked.
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::EditForum)")]
+    async fn set_automod<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+        #[graphql(
+            desc = "Bot account replies are posted as, it has to moderate the forum. Needed for `REPLY` rules."
+        )]
+        bot_id: Option<Key>,
+        #[graphql(validator(max_items = 50))] rules: Vec<AutomodRule>,
+    ) -> async_graphql::Result<Automod> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let forum: Option<DBForum> = state.db.select(("forum", forum_id.0)).await?;
+
+        if let Some(forum) = forum {
+            let automod = automod::set_automod(state, &forum, bot_id, rules, user.id.clone())
+                .await
+                .extend_err(|_, _| {})?;
+
+            modlog::record(
+                state,
+                &forum.id,
+                &user.id,
+                ModAction::UpdateAutomod,
+                None,
+                None,
+            )
+            .await
+            .extend_err(|_, _| {})?;
+
+            Ok(automod.into())
+        } else {
+            Err(RtwalkError::ForumNotFound).extend_err(|_, _| {})
+        }
+    }
+
+    /// Replaces the whole rule list, in the given order.
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::EditForum)")]
+    async fn set_forum_rules<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+        #[graphql(validator(max_items = 15))] rules: Vec<ForumRuleInput>,
+    ) -> async_graphql::Result<Vec<ForumRule>> {
+        let state = state!(ctx);
+
+        let forum: Option<DBForum> = state.db.select(("forum", forum_id.0)).await?;
+
+        if let Some(mut forum) = forum {
+            forum.rules = rules
+                .into_iter()
+                .map(|r| ForumRule {
+                    id: r.id.unwrap_or_else(cuid),
+                    title: r.title,
+                    description: r.description,
+                })
+                .collect();
+
+            // Removals and bans cite rules by id, each has to point at a single rule.
+            let mut ids = HashSet::new();
+            if !forum.rules.iter().all(|r| ids.insert(r.id.as_str())) {
+                return Err(RtwalkError::DuplicateId).extend_err(|_, _| {});
+            }
+
+            let res: Option<DBForum> = state.db.update(&forum.id).content(forum).await?;
+
+            Ok(res.expect("Forum exists").rules)
+        } else {
+            Err(RtwalkError::ForumNotFound).extend_err(|_, _| {})
+        }
+    }
+
+    /// Replaces the whole flair list, in the given order. Existing posts keep their flair.
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::EditForum)")]
+    async fn set_forum_flairs<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+        #[graphql(validator(max_items = 50))] flairs: Vec<FlairInput>,
+    ) -> async_graphql::Result<Vec<Flair>> {
+        let state = state!(ctx);
+
+        let forum: Option<DBForum> = state.db.select(("forum", forum_id.0)).await?;
+
+        if let Some(mut forum) = forum {
+            forum.flairs = flairs
+                .into_iter()
+                .map(|f| Flair {
+                    id: f.id.unwrap_or_else(cuid),
+                    text: f.text,
+                    color: f.color,
+                })
+                .collect();
+
+            let mut ids = HashSet::new();
+            if !forum.flairs.iter().all(|f| ids.insert(f.id.as_str())) {
+                return Err(RtwalkError::DuplicateId).extend_err(|_, _| {});
+            }
+
+            let res: Option<DBForum> = state.db.update(&forum.id).content(forum).await?;
+
+            Ok(res.expect("Forum exists").flairs)
+        } else {
+            Err(RtwalkError::ForumNotFound).extend_err(|_, _| {})
+        }
+    }
+
+    /// Joins a public forum right away. Restricted and private forums get a join request
+    /// instead, which moderators with `MANAGE_MEMBERS` approve, see `myJoinRequest`.
+    /// Returns the forum even if you were already a member.
+    #[graphql(guard = Role::Authenticated)]
+    async fn join_forum<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+        #[graphql(validator(min_length = 1, max_length = 500))] message: Option<String>,
+    ) -> async_graphql::Result<Forum> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let forum = forums::fetch_forum(state, ForumSelectCriteria::Id(forum_id.to_string()))
+            .await
+            .extend_err(|_, _| {})?
+            .ok_or(RtwalkError::ForumNotFound)
+            .extend_err(|_, _| {})?;
+
+        if forum.settings.visibility != ForumVisibility::Public {
+            let member = members::is_member(
+                state,
+                &forum.id,
+                &RecordId::from_table_key("user", user.id.0.clone()),
+            )
+            .await
+            .extend_err(|_, _| {})?;
 
-use crate::{
-    config,
-    error::RtwalkError,
-    gql::{forums, state, user},
-    models::{
-        file::{File, FileOps},
-        forum::{DBForum, Forum},
-        Key,
-    },
-};
+            if !member {
+                members::request_to_join(state, &forum, user.id, message)
+                    .await
+                    .extend_err(|_, _| {})?;
+            }
 
-use super::super::Role;
+            return Ok(forum.into());
+        }
 
-#[derive(Default)]
-pub struct ForumMutationRoot;
+        let joined = members::join_forum(state, &forum, user.id)
+            .await
+            .extend_err(|_, _| {})?;
 
-#[Object]
-impl ForumMutationRoot {
-    #[graphql(guard = Role::Human)]
-    async fn create_forum(
+        let mut forum: Forum = forum.into();
+        if joined {
+            forum.member_count += 1;
+        }
+
+        Ok(forum)
+    }
+
+    /// Makes the user a member. Returns false if they already were one.
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::ManageMembers)")]
+    async fn approve_join_request<'r>(
         &self,
-        ctx: &Context<'_>,
-        #[graphql(validator(min_length = 3, max_length = 20, regex = r"^[a-z0-9_]+$"))]
-        name: String,
-    ) -> async_graphql::Result<Forum> {
+        ctx: &Context<'r>,
+        forum_id: Key,
+        user_id: Key,
+    ) -> async_graphql::Result<bool> {
+        let state = state!(ctx);
         let user = user!(ctx);
+
+        let forum: Option<DBForum> = state.db.select(("forum", forum_id.0)).await?;
+
+        if let Some(forum) = forum {
+            let removed = members::remove_join_request(state, &forum.id, &user_id)
+                .await
+                .extend_err(|_, _| {})?;
+            if !removed {
+                return Err(RtwalkError::JoinRequestNotFound).extend_err(|_, _| {});
+            }
+
+            let joined = members::join_forum(state, &forum, user_id.clone())
+                .await
+                .extend_err(|_, _| {})?;
+
+            modlog::record(
+                state,
+                &forum.id,
+                &user.id,
+                ModAction::ApproveJoinRequest,
+                Some(RecordId::from_table_key("user", user_id.0.clone())),
+                None,
+            )
+            .await
+            .extend_err(|_, _| {})?;
+
+            mail::notify(
+                state,
+                user_id,
+                NotificationKind::Moderation,
+                format!("You have joined {}", forum.display_name),
+                "Your request to join was approved.".to_string(),
+                format!("f/{}", forum.name),
+            );
+
+            Ok(joined)
+        } else {
+            Err(RtwalkError::ForumNotFound).extend_err(|_, _| {})
+        }
+    }
+
+    /// Returns false if the user hadn't asked to join.
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::ManageMembers)")]
+    async fn deny_join_request<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+        user_id: Key,
+    ) -> async_graphql::Result<bool> {
         let state = state!(ctx);
+        let user = user!(ctx);
 
-        let forum = forums::create_forum(&state, name.into(), user.id)
+        let forum = RecordId::from_table_key("forum", forum_id.0);
+
+        let removed = members::remove_join_request(state, &forum, &user_id)
             .await
             .extend_err(|_, _| {})?;
 
-        Ok(forum.into())
+        if removed {
+            modlog::record(
+                state,
+                &forum,
+                &user.id,
+                ModAction::DenyJoinRequest,
+                Some(RecordId::from_table_key("user", user_id.0)),
+                None,
+            )
+            .await
+            .extend_err(|_, _| {})?;
+        }
+
+        Ok(removed)
+    }
+
+    /// Returns false if you weren't a member. Also withdraws a pending join request.
+    #[graphql(guard = Role::Authenticated)]
+    async fn leave_forum<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+    ) -> async_graphql::Result<bool> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        members::leave_forum(
+            state,
+            &RecordId::from_table_key("forum", forum_id.0),
+            user.id,
+        )
+        .await
+        .extend_err(|_, _| {})
+    }
+
+    /// Nobody but moderators can post or comment in a locked forum.
+    /// Locking an already locked forum updates the reason.
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::EditForum)")]
+    async fn lock_forum<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+        #[graphql(validator(min_length = 1, max_length = 500))] reason: Option<String>,
+    ) -> async_graphql::Result<Forum> {
+        set_forum_lock(ctx, forum_id, true, reason).await
+    }
+
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::EditForum)")]
+    async fn unlock_forum<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+    ) -> async_graphql::Result<Forum> {
+        set_forum_lock(ctx, forum_id, false, None).await
     }
 
+    /// Hides the forum for `FORUM_DELETION_GRACE_SECONDS` (7 days), after which it's
+    /// removed with all of its content. Only the owner or an admin can do this.
     #[graphql(guard = Role::Human)]
-    async fn update_forum<'r>(
+    async fn delete_forum<'r>(
         &self,
         ctx: &Context<'r>,
         forum_id: Key,
-        #[graphql(validator(min_length = 3, max_length = 20, regex = r"^[a-z0-9_]+$"))]
-        name: Option<String>,
-        display_name: Option<String>,
-        description: MaybeUndefined<String>,
-        icon: MaybeUndefined<Upload>,
-        banner: MaybeUndefined<Upload>,
     ) -> async_graphql::Result<Forum> {
         let state = state!(ctx);
         let user = user!(ctx);
 
         let forum: Option<DBForum> = state.db.select(("forum", forum_id.0)).await?;
 
-        if let Some(mut forum) = forum {
-            if &user.id.0 != forum.owner.key() {
-                return Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {});
-            }
+        match forum {
+            Some(forum) if forum.deleted_at.is_none() => {
+                if !user.admin && forum.owner.key() != &user.id.0 {
+                    return Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {});
+                }
 
-            if let Some(name) = name {
-                forum.name = name;
+                let forum = forums::delete_forum(state, forum)
+                    .await
+                    .extend_err(|_, _| {})?;
+
+                modlog::record(
+                    state,
+                    &forum.id,
+                    &user.id,
+                    ModAction::DeleteForum,
+                    None,
+                    None,
+                )
+                .await
+                .extend_err(|_, _| {})?;
+
+                Ok(forum.into())
             }
+            _ => Err(RtwalkError::ForumNotFound).extend_err(|_, _| {}),
+        }
+    }
 
-            if let Some(display_name) = display_name {
-                forum.display_name = display_name;
+    /// Undoes `deleteForum` while the grace period lasts.
+    #[graphql(guard = Role::Human)]
+    async fn restore_forum<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+    ) -> async_graphql::Result<Forum> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let forum: Option<DBForum> = state.db.select(("forum", forum_id.0)).await?;
+
+        if let Some(forum) = forum {
+            if !user.admin && forum.owner.key() != &user.id.0 {
+                return Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {});
             }
+            let was_deleted = forum.deleted_at.is_some();
 
-            if description.is_null() {
-                forum.description = None;
-            } else if let MaybeUndefined::Value(description) = description {
-                forum.description = Some(description);
+            let forum = forums::restore_forum(state, forum)
+                .await
+                .extend_err(|_, _| {})?;
+
+            if was_deleted {
+                modlog::record(
+                    state,
+                    &forum.id,
+                    &user.id,
+                    ModAction::RestoreForum,
+                    None,
+                    None,
+                )
+                .await
+                .extend_err(|_, _| {})?;
             }
 
-            if icon.is_null() {
-                forum.icon.delete(&state.op).await.extend_err(|_, _| {})?;
+            Ok(forum.into())
+        } else {
+            Err(RtwalkError::ForumNotFound).extend_err(|_, _| {})
+        }
+    }
 
-                forum.icon = None;
-            } else if let MaybeUndefined::Value(v) = icon {
-                let mut upload_value = v.value(&ctx)?;
-                if upload_value.size()? > config::MAX_UPLOAD_SIZE {
-                    return Err(RtwalkError::MaxUploadSizeExceeded).extend_err(|_, _| {})?;
-                }
+    /// Bots can be moderators. Moderators with `MANAGE_MODERATORS` can only grant
+    /// permissions they have themselves.
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::ManageModerators)")]
+    async fn add_moderator<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+        user_id: Key,
+        permissions: Vec<ModPermission>,
+    ) -> async_graphql::Result<Moderator> {
+        let state = state!(ctx);
+        let user = user!(ctx);
 
-                forum.icon.delete(&state.op).await.extend_err(|_, _| {})?;
+        let forum: Option<DBForum> = state.db.select(("forum", forum_id.0)).await?;
 
-                let icon_file = File {
-                    loc: format!("{}/{}-{}", forum.id, cuid(), upload_value.filename),
-                };
-                icon_file
-                    .save(&state.op, &mut upload_value)
+        if let Some(forum) = forum {
+            ensure_can_grant(state, &user, &forum.id, &permissions)
+                .await
+                .extend_err(|_, _| {})?;
+
+            let moderator =
+                moderators::add_moderator(state, &forum, user_id, user.id.clone(), permissions)
                     .await
                     .extend_err(|_, _| {})?;
-                forum.icon = Some(icon_file);
+
+            modlog::record(
+                state,
+                &forum.id,
+                &user.id,
+                ModAction::AddModerator,
+                Some(moderator.user.clone()),
+                None,
+            )
+            .await
+            .extend_err(|_, _| {})?;
+
+            Ok(moderator.into())
+        } else {
+            Err(RtwalkError::ForumNotFound).extend_err(|_, _| {})
+        }
+    }
+
+    /// Replaces the moderator's permissions, same rules as `addModerator`.
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::ManageModerators)")]
+    async fn update_moderator_permissions<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+        user_id: Key,
+        permissions: Vec<ModPermission>,
+    ) -> async_graphql::Result<Moderator> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let forum = RecordId::from_table_key("forum", forum_id.0);
+
+        let moderator = moderators::fetch_moderator(state, &forum, user_id.clone())
+            .await
+            .extend_err(|_, _| {})?
+            .ok_or(RtwalkError::NotModerator)
+            .extend_err(|_, _| {})?;
+
+        ensure_can_grant(state, &user, &forum, &moderator.permissions)
+            .await
+            .extend_err(|_, _| {})?;
+        ensure_can_grant(state, &user, &forum, &permissions)
+            .await
+            .extend_err(|_, _| {})?;
+
+        let moderator = moderators::update_permissions(state, &forum, user_id, permissions)
+            .await
+            .extend_err(|_, _| {})?
+            .ok_or(RtwalkError::NotModerator)
+            .extend_err(|_, _| {})?;
+
+        modlog::record(
+            state,
+            &forum,
+            &user.id,
+            ModAction::UpdateModeratorPermissions,
+            Some(moderator.user.clone()),
+            None,
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        Ok(moderator.into())
+    }
+
+    /// Returns false if the user wasn't a moderator. Moderators with `MANAGE_MODERATORS`
+    /// can only remove moderators who don't have more permissions than them.
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::ManageModerators)")]
+    async fn remove_moderator<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+        user_id: Key,
+    ) -> async_graphql::Result<bool> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let forum = RecordId::from_table_key("forum", forum_id.0);
+
+        let moderator = moderators::fetch_moderator(state, &forum, user_id.clone())
+            .await
+            .extend_err(|_, _| {})?;
+
+        if let Some(moderator) = moderator {
+            ensure_can_grant(state, &user, &forum, &moderator.permissions)
+                .await
+                .extend_err(|_, _| {})?;
+
+            let removed = moderators::remove_moderator(state, &forum, user_id)
+                .await
+                .extend_err(|_, _| {})?;
+
+            if removed {
+                modlog::record(
+                    state,
+                    &forum,
+                    &user.id,
+                    ModAction::RemoveModerator,
+                    Some(moderator.user),
+                    None,
+                )
+                .await
+                .extend_err(|_, _| {})?;
             }
 
-            if banner.is_null() {
-                forum.banner.delete(&state.op).await.extend_err(|_, _| {})?;
+            Ok(removed)
+        } else {
+            Ok(false)
+        }
+    }
 
-                forum.banner = None;
-            } else if let MaybeUndefined::Value(v) = banner {
-                let mut upload_value = v.value(&ctx)?;
-                if upload_value.size()? > config::MAX_UPLOAD_SIZE {
-                    return Err(RtwalkError::MaxUploadSizeExceeded).extend_err(|_, _| {})?;
-                }
+    /// Offers the forum to another user, who has to accept it with `acceptForumOwnership`
+    /// before the offer expires. Replaces any earlier offer. Only the owner can do this.
+    #[graphql(guard = Role::Human)]
+    async fn transfer_forum_ownership<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+        new_owner: Key,
+    ) -> async_graphql::Result<ForumTransfer> {
+        let state = state!(ctx);
+        let user = user!(ctx);
 
-                forum.banner.delete(&state.op).await.extend_err(|_, _| {})?;
+        let forum: Option<DBForum> = state.db.select(("forum", forum_id.0)).await?;
 
-                let banner_file = File {
-                    loc: format!("{}/{}-{}", &forum.id, cuid(), upload_value.filename),
-                };
-                banner_file
-                    .save(&state.op, &mut upload_value)
-                    .await
-                    .extend_err(|_, _| {})?;
-                forum.banner = Some(banner_file);
+        if let Some(forum) = forum {
+            if forum.owner.key() != &user.id.0 {
+                return Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {});
             }
 
-            let res: Option<DBForum> = state.db.update(&forum.id).content(forum).await?;
+            let transfer = forums::start_transfer(state, &forum, new_owner.clone())
+                .await
+                .extend_err(|_, _| {})?;
 
-            Ok(res.expect("Forum exists").into())
+            mail::notify(
+                state,
+                new_owner,
+                NotificationKind::Moderation,
+                format!("You have been offered ownership of {}", forum.display_name),
+                format!(
+                    "{} wants to make you the owner of {}. The offer expires in {} days.",
+                    user.username,
+                    forum.display_name,
+                    config::FORUM_TRANSFER_EXPIERY_SECONDS / (24 * 60 * 60)
+                ),
+                format!("f/{}", forum.name),
+            );
+
+            Ok(transfer.into())
+        } else {
+            Err(RtwalkError::ForumNotFound).extend_err(|_, _| {})
+        }
+    }
+
+    /// Accepts a pending transfer offered to you. The previous owner becomes a moderator
+    /// with every permission.
+    #[graphql(guard = Role::Human)]
+    async fn accept_forum_ownership<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+    ) -> async_graphql::Result<Forum> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let forum: Option<DBForum> = state.db.select(("forum", forum_id.0)).await?;
+
+        if let Some(forum) = forum {
+            let forum = forums::accept_transfer(state, forum, &user.id)
+                .await
+                .extend_err(|_, _| {})?;
+
+            Ok(forum.into())
         } else {
             Err(RtwalkError::ForumNotFound).extend_err(|_, _| {})
         }
     }
 
-    // #[graphql(guard = Role::Human)]
-    // async fn add_moderator<'r>(
-    //     &self,
-    //     ctx: &Context<'r>,
-    //     forum_id: Key,
-    //     mod_id: Key,
-    // ) -> async_graphql::Result<String> {
-    //     let state = state!(ctx);
-    //     let user = user!(ctx);
+    /// Cancels (as the owner) or declines (as the recipient) a pending transfer.
+    /// Returns false if there was nothing to cancel.
+    #[graphql(guard = Role::Human)]
+    async fn cancel_forum_ownership_transfer<'r>(
+        &self,
+        ctx: &Context<'r>,
+        forum_id: Key,
+    ) -> async_graphql::Result<bool> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let forum = RecordId::from_table_key("forum", forum_id.0);
+
+        let transfer = forums::fetch_transfer(state, &forum)
+            .await
+            .extend_err(|_, _| {})?;
+
+        match transfer {
+            Some(t) if t.from == user.id.to_string() || t.to == user.id.to_string() => {
+                forums::cancel_transfer(state, &forum)
+                    .await
+                    .extend_err(|_, _| {})
+            }
+            Some(_) => Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {}),
+            None => Ok(false),
+        }
+    }
+}
+
+async fn set_forum_lock(
+    ctx: &Context<'_>,
+    forum_id: Key,
+    locked: bool,
+    reason: Option<String>,
+) -> async_graphql::Result<Forum> {
+    let state = state!(ctx);
+    let user = user!(ctx);
+
+    let forum: Option<DBForum> = state.db.select(("forum", forum_id.0)).await?;
+
+    if let Some(mut forum) = forum {
+        forum.locked = locked;
+        forum.lock_reason = reason.clone();
 
-    //     let forum: Option<DBForum> = state.db.select(("forum", forum_id.0)).await?;
-    //     let new_mod: Option<DBUser> = state.db.select(("user", mod_id.0)).await?;
+        let res: Option<DBForum> = state.db.update(&forum.id).content(forum).await?;
+        let forum = res.expect("Forum exists");
 
-    //     todo!()
-    // }
+        modlog::record(
+            state,
+            &forum.id,
+            &user.id,
+            if locked {
+                ModAction::LockForum
+            } else {
+                ModAction::UnlockForum
+            },
+            None,
+            reason.clone(),
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        let forum: Forum = forum.into();
+
+        publish_event(
+            state,
+            &forum.id,
+            &RtEvent {
+                ty: RtEventType::ForumLock,
+                event_data: RtEventData::ForumLock(ForumLockEvent {
+                    forum_id: forum.id.clone(),
+                    locked,
+                    reason,
+                }),
+            },
+        );
+
+        Ok(forum)
+    } else {
+        Err(RtwalkError::ForumNotFound).extend_err(|_, _| {})
+    }
+}
+
+/// Stops moderators from handing out (or taking away) more than they have.
+async fn ensure_can_grant(
+    state: &State,
+    user: &User,
+    forum: &RecordId,
+    permissions: &[ModPermission],
+) -> Result<(), RtwalkError> {
+    let own = moderators::fetch_permissions(state, user, forum)
+        .await?
+        .unwrap_or_default();
+
+    if permissions.iter().all(|p| own.contains(p)) {
+        Ok(())
+    } else {
+        Err(RtwalkError::UnauhorizedRequest)
+    }
 }
 
 #[derive(Default)]
@@ -154,11 +1516,15 @@ pub enum ForumSelectCriteria {
 pub enum MultipleForumSelectCriteria {
     Ids(Vec<String>),
     Names(Vec<String>),
+    /// Direct sub-forums of this forum.
+    Parent(String),
+    Category(String),
     Search(String),
 }
 
 #[Object]
 impl ForumQueryRoot {
+    /// Private forums are still returned so people can ask to join, their content isn't.
     async fn forum<'r>(
         &self,
         ctx: &Context<'r>,
@@ -1,5 +1,12 @@
+pub mod bans;
+pub mod categories;
 pub mod comments;
+pub mod email_domains;
+pub mod emojis;
 pub mod forums;
+pub mod modmail;
 pub mod page;
 pub mod posts;
+pub mod revisions;
 pub mod users;
+pub mod wiki;
@@ -0,0 +1,263 @@
+use async_graphql::{ComplexObject, Context, Object, ResultExt};
+
+use crate::{
+    error::RtwalkError,
+    gql::{forums, modmail, publish_event, state, user, users},
+    mail::{self, NotificationKind},
+    models::{
+        forum::Forum,
+        modmail::{DBModmailMessage, DBModmailThread, ModmailMessage, ModmailThread},
+        user::User,
+        Key, ModmailMessageEvent, ModmailThreadEvent, RtEvent, RtEventData, RtEventType,
+    },
+    state::State,
+};
+
+use super::{super::Role, forums::ForumSelectCriteria, users::UserSelectCriteria};
+
+#[ComplexObject]
+impl ModmailThread {
+    async fn forum(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Forum>> {
+        let state = state!(ctx);
+
+        let forum = forums::fetch_forum(state, ForumSelectCriteria::Id(self.forum_id.to_string()))
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(forum.map(|x| x.into()))
+    }
+
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        let state = state!(ctx);
+
+        let user = users::fetch_user(state, UserSelectCriteria::Id(self.user_id.to_string()))
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(user.map(|x| x.into()))
+    }
+
+    /// Oldest first. Internal notes are only listed for the moderator team.
+    #[graphql(guard = Role::Authenticated)]
+    async fn messages(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20, validator(maximum = 100))] per_page: u32,
+    ) -> async_graphql::Result<Vec<ModmailMessage>> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let thread = fetch_thread(state, self.id.clone())
+            .await
+            .extend_err(|_, _| {})?;
+        let team = modmail::ensure_participant(state, &user, &thread)
+            .await
+            .extend_err(|_, _| {})?;
+
+        let messages = modmail::fetch_messages(state, &thread.id, team, page, per_page)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(messages.into_iter().map(|x| x.into()).collect())
+    }
+}
+
+#[ComplexObject]
+impl ModmailMessage {
+    async fn author(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        let state = state!(ctx);
+
+        let user = users::fetch_user(state, UserSelectCriteria::Id(self.author_id.to_string()))
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(user.map(|x| x.into()))
+    }
+}
+
+async fn fetch_thread(state: &State, thread_id: Key) -> Result<DBModmailThread, RtwalkError> {
+    modmail::fetch_thread(state, thread_id)
+        .await?
+        .ok_or(RtwalkError::ModmailThreadNotFound)
+}
+
+fn publish_message(state: &State, thread: &DBModmailThread, message: DBModmailMessage) {
+    let thread: ModmailThread = thread.clone().into();
+    let forum = thread.forum_id.clone();
+
+    publish_event(
+        state,
+        &forum,
+        &RtEvent {
+            ty: RtEventType::ModmailMessage,
+            event_data: RtEventData::ModmailMessage(ModmailMessageEvent {
+                thread,
+                message: message.into(),
+            }),
+        },
+    );
+}
+
+#[derive(Default)]
+pub struct ModmailQueryRoot;
+
+#[Object]
+impl ModmailQueryRoot {
+    /// Only visible to the user who started it and the forum's moderator team.
+    #[graphql(guard = Role::Authenticated)]
+    async fn modmail_thread(
+        &self,
+        ctx: &Context<'_>,
+        id: Key,
+    ) -> async_graphql::Result<Option<ModmailThread>> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let Some(thread) = modmail::fetch_thread(state, id)
+            .await
+            .extend_err(|_, _| {})?
+        else {
+            return Ok(None);
+        };
+        if modmail::ensure_participant(state, &user, &thread)
+            .await
+            .is_err()
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(thread.into()))
+    }
+
+    /// Threads the logged in user started, most recently active first.
+    #[graphql(guard = Role::Authenticated)]
+    async fn my_modmail(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20, validator(maximum = 100))] per_page: u32,
+    ) -> async_graphql::Result<Vec<ModmailThread>> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let threads = modmail::fetch_user_threads(state, &user.id, page, per_page)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(threads.into_iter().map(|x| x.into()).collect())
+    }
+}
+
+#[derive(Default)]
+pub struct ModmailMutationRoot;
+
+#[Object]
+impl ModmailMutationRoot {
+    /// Messages the forum's moderator team. Banned users can still write to appeal.
+    #[graphql(guard = Role::Authenticated)]
+    async fn send_modmail(
+        &self,
+        ctx: &Context<'_>,
+        forum_id: Key,
+        #[graphql(validator(min_length = 1, max_length = 200))] subject: String,
+        #[graphql(validator(min_length = 1, max_length = 5_000))] content: String,
+    ) -> async_graphql::Result<ModmailThread> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let forum = forums::fetch_forum(state, ForumSelectCriteria::Id(forum_id.to_string()))
+            .await
+            .extend_err(|_, _| {})?
+            .ok_or(RtwalkError::ForumNotFound)
+            .extend_err(|_, _| {})?;
+
+        let (thread, message) = modmail::create_thread(state, &forum, user.id, subject, content)
+            .await
+            .extend_err(|_, _| {})?;
+
+        publish_message(state, &thread, message);
+
+        Ok(thread.into())
+    }
+
+    /// `internal` notes are only for the moderator team. Replies from the team email the
+    /// thread's user.
+    #[graphql(guard = Role::Authenticated)]
+    async fn reply_to_modmail(
+        &self,
+        ctx: &Context<'_>,
+        thread_id: Key,
+        #[graphql(validator(min_length = 1, max_length = 5_000))] content: String,
+        #[graphql(default)] internal: bool,
+    ) -> async_graphql::Result<ModmailMessage> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let thread = fetch_thread(state, thread_id).await.extend_err(|_, _| {})?;
+        let team = modmail::ensure_participant(state, &user, &thread)
+            .await
+            .extend_err(|_, _| {})?;
+        if internal && !team {
+            return Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {});
+        }
+
+        let (thread, message) =
+            modmail::add_message(state, &thread, user.id.clone(), content, internal)
+                .await
+                .extend_err(|_, _| {})?;
+
+        if !internal && thread.user.key() != &user.id.0 {
+            mail::notify(
+                state,
+                Key(thread.user.key().to_owned()),
+                NotificationKind::Moderation,
+                "The moderators replied to your message".to_string(),
+                format!("New reply in \"{}\".", thread.subject),
+                format!("modmail/{}", thread.id.key()),
+            );
+        }
+
+        publish_message(state, &thread, message.clone());
+
+        Ok(message.into())
+    }
+
+    /// Archived threads come back when their user writes again.
+    #[graphql(guard = Role::Authenticated)]
+    async fn archive_modmail(
+        &self,
+        ctx: &Context<'_>,
+        thread_id: Key,
+        #[graphql(default = true)] archived: bool,
+    ) -> async_graphql::Result<ModmailThread> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let thread = fetch_thread(state, thread_id).await.extend_err(|_, _| {})?;
+        if !modmail::is_team(state, &user, &thread)
+            .await
+            .extend_err(|_, _| {})?
+        {
+            return Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {});
+        }
+
+        let thread: ModmailThread = modmail::set_archived(state, &thread, archived)
+            .await
+            .extend_err(|_, _| {})?
+            .into();
+
+        publish_event(
+            state,
+            &thread.forum_id,
+            &RtEvent {
+                ty: RtEventType::ModmailThread,
+                event_data: RtEventData::ModmailThread(ModmailThreadEvent {
+                    data: thread.clone(),
+                }),
+            },
+        );
+
+        Ok(thread)
+    }
+}
@@ -2,13 +2,22 @@ use crate::{
     error::RtwalkError,
     gql::{
         comments, forums, posts,
+        posts::PostFilter,
         resolvers::{
             comments::MultipleCommentSelectCriteria, forums::MultipleForumSelectCriteria,
             posts::MultiplePostSelectCriteria, users::MultipleUserSelectCriteria,
+            wiki::MultipleWikiPageSelectCriteria,
         },
-        state, user, users, Page, Role,
+        state, user, users, viewer, wiki, Page, Role,
+    },
+    models::{
+        comment::Comment,
+        file::File,
+        forum::Forum,
+        post::{Post, PostSort, TopWindow},
+        user::User,
+        wiki::WikiPage,
     },
-    models::{comment::Comment, file::File, forum::Forum, post::Post, user::User},
 };
 use async_graphql::{ComplexObject, Context, ResultExt};
 
@@ -47,25 +56,60 @@ impl Page {
         Ok(files)
     }
 
+    /// Private forums are only listed for their members and moderators.
     async fn forum(
         &self,
         ctx: &Context<'_>,
         criteria: MultipleForumSelectCriteria,
     ) -> async_graphql::Result<Vec<Forum>> {
         let state = state!(ctx);
-        let forums = forums::fetch_forums(state, criteria, &self.page_info)
+        let viewer = viewer(ctx).await?;
+        let forums = forums::fetch_forums(state, criteria, viewer.as_ref(), &self.page_info)
             .await
             .extend_err(|_, _| {})?;
         Ok(forums.into_iter().map(|x| x.into()).collect())
     }
 
+    /// Sort defaults to the viewer's preferred sort, `OLD` if they have none. NSFW posts
+    /// are only listed for logged in users who opted into them.
     async fn post(
         &self,
         ctx: &Context<'_>,
         criteria: MultiplePostSelectCriteria,
+        sort: Option<PostSort>,
+        #[graphql(default, desc = "How far back the `TOP` sort looks.")] top_window: TopWindow,
+        #[graphql(desc = "Only posts with this flair id.")] flair: Option<String>,
+        #[graphql(
+            default,
+            desc = "With forum criteria, also list posts of its sub-forums."
+        )]
+        include_subforums: bool,
     ) -> async_graphql::Result<Vec<Post>> {
         let state = state!(ctx);
-        let posts = posts::fetch_posts(state, criteria, &self.page_info)
+
+        let viewer = viewer(ctx).await?;
+        let preferences = match &viewer {
+            Some(viewer) => Some(
+                users::fetch_preferences(state, &viewer.id)
+                    .await
+                    .extend_err(|_, _| {})?,
+            ),
+            None => None,
+        };
+        let filter = PostFilter {
+            sort: sort.unwrap_or(
+                preferences
+                    .as_ref()
+                    .map(|p| p.default_post_sort)
+                    .unwrap_or_default(),
+            ),
+            top_window,
+            show_nsfw: preferences.is_some_and(|p| p.show_nsfw),
+            flair,
+            include_subforums,
+        };
+
+        let posts = posts::fetch_posts(state, criteria, &filter, viewer.as_ref(), &self.page_info)
             .await
             .extend_err(|_, _| {})?;
         Ok(posts.into_iter().map(|x| x.into()).collect())
@@ -77,9 +121,24 @@ impl Page {
         criteria: MultipleCommentSelectCriteria,
     ) -> async_graphql::Result<Vec<Comment>> {
         let state = state!(ctx);
-        let comments = comments::fetch_comments(state, criteria, &self.page_info)
+        let viewer = viewer(ctx).await?;
+        let comments = comments::fetch_comments(state, criteria, viewer.as_ref(), &self.page_info)
             .await
             .extend_err(|_, _| {})?;
         Ok(comments.into_iter().map(|x| x.into()).collect())
     }
+
+    /// Searches titles and content, private forums' pages are left out for outsiders.
+    async fn wiki(
+        &self,
+        ctx: &Context<'_>,
+        criteria: MultipleWikiPageSelectCriteria,
+    ) -> async_graphql::Result<Vec<WikiPage>> {
+        let state = state!(ctx);
+        let viewer = viewer(ctx).await?;
+        let pages = wiki::fetch_pages(state, criteria, viewer.as_ref(), &self.page_info)
+            .await
+            .extend_err(|_, _| {})?;
+        Ok(pages.into_iter().map(|x| x.into()).collect())
+    }
 }
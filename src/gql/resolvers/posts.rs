@@ -1,26 +1,112 @@
-use async_graphql::{Context, MaybeUndefined, Object, OneofObject, ResultExt, Upload};
-use chrono::DateTime;
+use async_graphql::{
+    dataloader::DataLoader, ComplexObject, Context, MaybeUndefined, Object, OneofObject, ResultExt,
+    Upload,
+};
+use chrono::Utc;
 use cuid2::cuid;
-use rustis::commands::PubSubCommands;
+use surrealdb::RecordId;
 
 use crate::{
     config,
     error::RtwalkError,
-    gql::{posts, state, user},
+    gql::{
+        automod, bans, emojis, forums,
+        loaders::{EmojiLoader, VoteLoader},
+        moderators, modlog,
+        posts::{self, Submission},
+        publish_event, revisions, state, user, viewer, votes,
+    },
+    mail::{self, NotificationKind},
     models::{
+        emoji::ContentToken,
         file::{File, FileOps},
+        moderator::ModPermission,
+        modlog::ModAction,
         post::{DBPost, Post},
-        Key, PostCreateEvent, PostEditEvent, RtEvent, RtEventData, RtEventType,
+        revision::{DBRevision, Revision},
+        vote::VoteDirection,
+        Key, PostCreateEvent, PostDeleteEvent, PostEditEvent, PostLockEvent, PostPinEvent,
+        PostRemoveEvent, RtEvent, RtEventData, RtEventType,
     },
 };
 
 use super::super::Role;
 
+#[ComplexObject]
+impl Post {
+    /// `content` split into text and the forum's custom emoji.
+    async fn content_tokens(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<ContentToken>> {
+        let Some(content) = &self.content else {
+            return Ok(vec![]);
+        };
+        let forum = RecordId::from_table_key("forum", self.forum_id.0.clone());
+        let emojis = ctx
+            .data_unchecked::<DataLoader<EmojiLoader>>()
+            .load_one(forum)
+            .await?
+            .unwrap_or_default();
+
+        Ok(emojis::tokenize(content, &emojis))
+    }
+
+    /// Earlier versions of the post, newest first. Only moderators can see them unless the
+    /// forum made edit history public, and always once the post is deleted.
+    async fn revisions(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20, validator(maximum = 100))] per_page: u32,
+    ) -> async_graphql::Result<Vec<Revision>> {
+        let state = state!(ctx);
+        let viewer = viewer(ctx).await?;
+
+        let forum = RecordId::from_table_key("forum", self.forum_id.0.clone());
+        revisions::ensure_can_view(
+            state,
+            viewer.as_ref(),
+            &forum,
+            ModPermission::ManagePosts,
+            self.deleted,
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        let post = RecordId::from_table_key("post", self.id.0.clone());
+        let revisions = revisions::fetch_revisions(state, &post, page, per_page)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(revisions.into_iter().map(|x| x.into()).collect())
+    }
+
+    /// The logged in user's vote, `null` when they haven't voted.
+    async fn viewer_vote(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<VoteDirection>> {
+        let Some(viewer) = viewer(ctx).await? else {
+            return Ok(None);
+        };
+        let user = RecordId::from_table_key("user", viewer.id.0);
+        let post = RecordId::from_table_key("post", self.id.0.clone());
+        let vote = ctx
+            .data_unchecked::<DataLoader<VoteLoader>>()
+            .load_one((user, post))
+            .await?;
+
+        Ok(vote.map(|v| {
+            if v > 0 {
+                VoteDirection::Up
+            } else {
+                VoteDirection::Down
+            }
+        }))
+    }
+}
+
 #[derive(Default)]
 pub struct PostMutationRoot;
 
 #[Object]
 impl PostMutationRoot {
+    #[allow(clippy::too_many_arguments)]
     #[graphql(guard = Role::Authenticated)]
     async fn create_post(
         &self,
@@ -30,13 +116,60 @@ impl PostMutationRoot {
         #[graphql(validator(max_items = 8, list, max_length = 20))] tags: Vec<String>,
         #[graphql(validator(min_length = 1, max_length = 8_000))] content: Option<String>,
         attachments: Vec<Upload>,
+        #[graphql(default)] nsfw: bool,
+        #[graphql(desc = "Id of one of the forum's flairs.")] flair: Option<String>,
     ) -> async_graphql::Result<Post> {
         let user = user!(ctx);
         let state = state!(ctx);
 
+        let forum_id = RecordId::from_table_key("forum", forum.0.clone());
+
+        let forum_data =
+            posts::ensure_can_post(state, &user, &forum_id, None, ModPermission::ManagePosts)
+                .await
+                .extend_err(|_, _| {})?;
+
+        let flair = match flair {
+            Some(id) => Some(
+                forum_data
+                    .flair(&id)
+                    .cloned()
+                    .ok_or(RtwalkError::FlairNotFound)
+                    .extend_err(|_, _| {})?,
+            ),
+            None if forum_data.settings.require_flair => {
+                return Err(RtwalkError::FlairRequired).extend_err(|_, _| {});
+            }
+            None => None,
+        };
+
+        bans::ensure_not_banned(state, &forum_id, &user.id)
+            .await
+            .extend_err(|_, _| {})?;
+
+        let attachments = attachments
+            .into_iter()
+            .map(|v| v.value(ctx))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        posts::ensure_meets_requirements(
+            state,
+            &user,
+            &forum_data,
+            Submission::Post,
+            &attachments,
+            ModPermission::ManagePosts,
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        let attachment_types: Vec<String> = attachments
+            .iter()
+            .filter_map(|a| a.content_type.clone())
+            .collect();
+
         let mut uploads = vec![];
-        for v in attachments {
-            let mut upload_value = v.value(&ctx)?;
+        for mut upload_value in attachments {
             if upload_value.size()? > config::MAX_UPLOAD_SIZE {
                 return Err(RtwalkError::MaxUploadSizeExceeded).extend_err(|_, _| {})?;
             }
@@ -56,23 +189,41 @@ impl PostMutationRoot {
             uploads.push(f);
         }
 
-        let post: Post = posts::create_post(&state, title, tags, content, uploads, user.id, forum)
+        let post = DBPost {
+            nsfw,
+            flair,
+            ..DBPost::new(title, tags, content, uploads, user.id.clone(), forum)
+        };
+        let post = posts::create_post(state, post)
             .await
-            .extend_err(|_, _| {})?
-            .into();
-
-        state.redis.publish(
-            "rte-post-create",
-            serde_json::to_vec(&RtEvent {
-                ty: RtEventType::PostCreate,
-                event_data: RtEventData::PostCreate(PostCreateEvent { data: post.clone() }),
-            })
-            .expect("Cant fail to serialize self constructed data"),
-        );
+            .extend_err(|_, _| {})?;
+
+        posts::record_submission(state, &user.id, &forum_data, Submission::Post)
+            .await
+            .extend_err(|_, _| {})?;
+
+        let post: Post =
+            automod::check_post(state, &forum_data, &user, post, &attachment_types, false)
+                .await
+                .extend_err(|_, _| {})?
+                .into();
+
+        // Removed and held posts aren't announced, moderators find them in the queue.
+        if !post.removed && !post.held {
+            publish_event(
+                state,
+                &post.forum_id,
+                &RtEvent {
+                    ty: RtEventType::PostCreate,
+                    event_data: RtEventData::PostCreate(PostCreateEvent { data: post.clone() }),
+                },
+            );
+        }
 
         Ok(post)
     }
 
+    #[allow(clippy::too_many_arguments)]
     #[graphql(guard = Role::Authenticated)]
     async fn update_post<'r>(
         &self,
@@ -82,6 +233,8 @@ impl PostMutationRoot {
         #[graphql(validator(max_items = 8, list, max_length = 20))] tags: Option<Vec<String>>,
         #[graphql(validator(min_length = 1, max_length = 8_000))] content: MaybeUndefined<String>,
         remove_attachments: bool,
+        nsfw: Option<bool>,
+        flair: MaybeUndefined<String>,
     ) -> async_graphql::Result<Post> {
         let state = state!(ctx);
         let user = user!(ctx);
@@ -89,10 +242,40 @@ impl PostMutationRoot {
         let post: Option<DBPost> = state.db.select(("post", post_id.0)).await?;
 
         if let Some(mut post) = post {
-            let original_post: Post = post.clone().into();
+            if post.deleted {
+                return Err(RtwalkError::PostDeleted).extend_err(|_, _| {});
+            }
+            let previous = post.clone();
+            let original_post: Post = previous.clone().into();
 
+            let forum = posts::ensure_can_post(
+                state,
+                &user,
+                &post.forum,
+                Some(&post),
+                ModPermission::ManagePosts,
+            )
+            .await
+            .extend_err(|_, _| {})?;
+
+            bans::ensure_not_banned(state, &post.forum, &user.id)
+                .await
+                .extend_err(|_, _| {})?;
+
+            // Moderators can mark others' posts nsfw, change their flair and strip their
+            // attachments, nothing else.
             if &user.id.0 != post.poster.key() {
-                return Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {});
+                let moderator = moderators::has_permission(
+                    state,
+                    &user,
+                    &post.forum,
+                    ModPermission::ManagePosts,
+                )
+                .await
+                .extend_err(|_, _| {})?;
+                if !moderator || title.is_some() || tags.is_some() || !content.is_undefined() {
+                    return Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {});
+                }
             }
 
             if let Some(title) = title {
@@ -109,37 +292,472 @@ impl PostMutationRoot {
                 post.content = Some(content);
             }
 
-            if remove_attachments {
-                for attachment in &post.attachments {
-                    attachment.delete(&state.op).await.extend_err(|_, _| {})?;
+            if let Some(nsfw) = nsfw {
+                post.nsfw = nsfw;
+            }
+
+            if flair.is_null() {
+                if forum.settings.require_flair {
+                    return Err(RtwalkError::FlairRequired).extend_err(|_, _| {});
                 }
+                post.flair = None;
+            } else if let MaybeUndefined::Value(flair) = flair {
+                post.flair = Some(
+                    forum
+                        .flair(&flair)
+                        .cloned()
+                        .ok_or(RtwalkError::FlairNotFound)
+                        .extend_err(|_, _| {})?,
+                );
             }
 
-            post.edited_at = DateTime::default();
+            // The files stay for the revision below.
+            if remove_attachments {
+                post.attachments = vec![];
+            }
 
-            let res: Option<DBPost> = state.db.update(&post.id).content(post).await?;
+            if post.title != previous.title
+                || post.tags != previous.tags
+                || post.content != previous.content
+                || post.attachments != previous.attachments
+            {
+                revisions::create_revision(state, DBRevision::of_post(&previous, user.id.clone()))
+                    .await
+                    .extend_err(|_, _| {})?;
+            }
+
+            post.edited_at = Utc::now();
+
+            let was_counted = post.is_counted();
+            let post = posts::save_post(
+                state,
+                post,
+                was_counted,
+                &[
+                    "title",
+                    "tags",
+                    "content",
+                    "attachments",
+                    "nsfw",
+                    "flair",
+                    "edited_at",
+                ],
+            )
+            .await
+            .extend_err(|_, _| {})?;
+
+            let updated_post: Post = automod::check_post(state, &forum, &user, post, &[], true)
+                .await
+                .extend_err(|_, _| {})?
+                .into();
+
+            if !updated_post.removed && !updated_post.held {
+                publish_event(
+                    state,
+                    &updated_post.forum_id,
+                    &RtEvent {
+                        ty: RtEventType::PostEdit,
+                        event_data: RtEventData::PostEdit(PostEditEvent {
+                            original: Box::new(original_post),
+                            new: Box::new(updated_post.clone()),
+                        }),
+                    },
+                );
+            }
+
+            Ok(updated_post)
+        } else {
+            Err(RtwalkError::PostNotFound).extend_err(|_, _| {})
+        }
+    }
+
+    /// Locked posts can't be commented on or edited, except by moderators with
+    /// `MANAGE_POSTS`. Locking an already locked post updates the reason.
+    #[graphql(guard = Role::Authenticated)]
+    async fn lock_post<'r>(
+        &self,
+        ctx: &Context<'r>,
+        post_id: Key,
+        #[graphql(validator(min_length = 1, max_length = 500))] reason: Option<String>,
+        #[graphql(desc = "Id of the forum rule the post broke.")] rule_id: Option<String>,
+    ) -> async_graphql::Result<Post> {
+        set_post_lock(ctx, post_id, true, reason, rule_id).await
+    }
+
+    #[graphql(guard = Role::Authenticated)]
+    async fn unlock_post<'r>(
+        &self,
+        ctx: &Context<'r>,
+        post_id: Key,
+    ) -> async_graphql::Result<Post> {
+        set_post_lock(ctx, post_id, false, None, None).await
+    }
 
-            let updated_post: Post = res.expect("Post exists").into();
+    /// Pinned posts are listed first in their forum whatever the sort. A forum can have
+    /// at most a few pinned posts at a time.
+    #[graphql(guard = Role::Authenticated)]
+    async fn pin_post<'r>(&self, ctx: &Context<'r>, post_id: Key) -> async_graphql::Result<Post> {
+        set_post_pin(ctx, post_id, true).await
+    }
+
+    #[graphql(guard = Role::Authenticated)]
+    async fn unpin_post<'r>(&self, ctx: &Context<'r>, post_id: Key) -> async_graphql::Result<Post> {
+        set_post_pin(ctx, post_id, false).await
+    }
+
+    /// Releases a post AutoModerator held, or restores a removed one.
+    #[graphql(guard = Role::Authenticated)]
+    async fn approve_post<'r>(
+        &self,
+        ctx: &Context<'r>,
+        post_id: Key,
+    ) -> async_graphql::Result<Post> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let post: Option<DBPost> = state.db.select(("post", post_id.0)).await?;
+
+        if let Some(mut post) = post {
+            moderators::ensure_permission(state, &user, &post.forum, ModPermission::ManagePosts)
+                .await
+                .extend_err(|_, _| {})?;
+
+            let (was_removed, was_counted) = (post.removed, post.is_counted());
+            post.held = false;
+            post.removed = false;
+            post.removal_reason = None;
+
+            let post = posts::save_post(
+                state,
+                post,
+                was_counted,
+                &["held", "removed", "removal_reason"],
+            )
+            .await
+            .extend_err(|_, _| {})?;
+
+            modlog::record(
+                state,
+                &post.forum,
+                &user.id,
+                ModAction::ApprovePost,
+                Some(post.id.clone()),
+                None,
+            )
+            .await
+            .extend_err(|_, _| {})?;
+
+            let post: Post = post.into();
+
+            if was_removed {
+                publish_event(
+                    state,
+                    &post.forum_id,
+                    &RtEvent {
+                        ty: RtEventType::PostRemove,
+                        event_data: RtEventData::PostRemove(PostRemoveEvent {
+                            post_id: post.id.clone(),
+                            forum_id: post.forum_id.clone(),
+                            removed: false,
+                            reason: None,
+                        }),
+                    },
+                );
+            }
 
-            state.redis.publish(
-                "rte-post-update",
-                serde_json::to_vec(&RtEvent {
-                    ty: RtEventType::PostEdit,
-                    event_data: RtEventData::PostEdit(PostEditEvent {
-                        original: original_post,
-                        new: updated_post.clone(),
+            Ok(post)
+        } else {
+            Err(RtwalkError::PostNotFound).extend_err(|_, _| {})
+        }
+    }
+
+    /// Hides the post from everyone but its author, who sees the reason, and the forum's
+    /// moderators. `approvePost` restores it.
+    #[graphql(guard = Role::Authenticated)]
+    async fn remove_post<'r>(
+        &self,
+        ctx: &Context<'r>,
+        post_id: Key,
+        #[graphql(validator(min_length = 1, max_length = 500))] reason: Option<String>,
+        #[graphql(desc = "Id of the forum rule the post broke.")] rule_id: Option<String>,
+    ) -> async_graphql::Result<Post> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let post: Option<DBPost> = state.db.select(("post", post_id.0)).await?;
+
+        if let Some(mut post) = post {
+            moderators::ensure_permission(state, &user, &post.forum, ModPermission::ManagePosts)
+                .await
+                .extend_err(|_, _| {})?;
+
+            if let Some(rule) = &rule_id {
+                forums::ensure_rule_exists(state, &post.forum, rule)
+                    .await
+                    .extend_err(|_, _| {})?;
+            }
+
+            let was_counted = post.is_counted();
+            post.removed = true;
+            post.removal_reason = reason.clone();
+
+            let post = posts::save_post(state, post, was_counted, &["removed", "removal_reason"])
+                .await
+                .extend_err(|_, _| {})?;
+
+            modlog::record_citing(
+                state,
+                &post.forum,
+                &user.id,
+                ModAction::RemovePost,
+                Some(post.id.clone()),
+                reason.clone(),
+                rule_id,
+            )
+            .await
+            .extend_err(|_, _| {})?;
+
+            if post.poster.key() != &user.id.0 {
+                mail::notify(
+                    state,
+                    Key(post.poster.key().to_owned()),
+                    NotificationKind::Moderation,
+                    "Your post was removed".to_string(),
+                    format!(
+                        "\"{}\" was removed by the moderators. {}",
+                        post.title,
+                        reason.as_deref().unwrap_or("No reason was given.")
+                    ),
+                    format!("post/{}", post.id.key()),
+                );
+            }
+
+            let post: Post = post.into();
+
+            publish_event(
+                state,
+                &post.forum_id,
+                &RtEvent {
+                    ty: RtEventType::PostRemove,
+                    event_data: RtEventData::PostRemove(PostRemoveEvent {
+                        post_id: post.id.clone(),
+                        forum_id: post.forum_id.clone(),
+                        removed: true,
+                        reason,
                     }),
-                })
-                .expect("Cant fail to serialize self constructed data"),
+                },
             );
 
-            Ok(updated_post)
+            Ok(post)
+        } else {
+            Err(RtwalkError::PostNotFound).extend_err(|_, _| {})
+        }
+    }
+
+    /// Votes on someone else's post, voting again changes the vote and `null` removes it.
+    #[graphql(guard = Role::Authenticated)]
+    async fn vote_post<'r>(
+        &self,
+        ctx: &Context<'r>,
+        post_id: Key,
+        direction: Option<VoteDirection>,
+    ) -> async_graphql::Result<Post> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let post = posts::fetch_post(state, PostSelectCriteria::Id(post_id), Some(&user))
+            .await
+            .extend_err(|_, _| {})?
+            .ok_or(RtwalkError::PostNotFound)
+            .extend_err(|_, _| {})?;
+        if post.deleted {
+            return Err(RtwalkError::PostDeleted).extend_err(|_, _| {});
+        }
+
+        bans::ensure_not_banned(state, &post.forum, &user.id)
+            .await
+            .extend_err(|_, _| {})?;
+
+        let post = votes::vote(state, user.id, &post, direction)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(post.into())
+    }
+
+    /// Only the author can delete a post. The title and content are replaced and the
+    /// attachments deleted, its comments stay.
+    #[graphql(guard = Role::Authenticated)]
+    async fn delete_post<'r>(
+        &self,
+        ctx: &Context<'r>,
+        post_id: Key,
+    ) -> async_graphql::Result<Post> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let post: Option<DBPost> = state.db.select(("post", post_id.0)).await?;
+
+        if let Some(post) = post {
+            if post.poster.key() != &user.id.0 {
+                return Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {});
+            }
+            if post.deleted {
+                return Err(RtwalkError::PostDeleted).extend_err(|_, _| {});
+            }
+
+            let post: Post = posts::delete_post(state, post)
+                .await
+                .extend_err(|_, _| {})?
+                .into();
+
+            publish_event(
+                state,
+                &post.forum_id,
+                &RtEvent {
+                    ty: RtEventType::PostDelete,
+                    event_data: RtEventData::PostDelete(PostDeleteEvent {
+                        post_id: post.id.clone(),
+                        forum_id: post.forum_id.clone(),
+                    }),
+                },
+            );
+
+            Ok(post)
         } else {
             Err(RtwalkError::PostNotFound).extend_err(|_, _| {})
         }
     }
 }
 
+async fn set_post_lock(
+    ctx: &Context<'_>,
+    post_id: Key,
+    locked: bool,
+    reason: Option<String>,
+    rule: Option<String>,
+) -> async_graphql::Result<Post> {
+    let state = state!(ctx);
+    let user = user!(ctx);
+
+    let post: Option<DBPost> = state.db.select(("post", post_id.0)).await?;
+
+    if let Some(mut post) = post {
+        moderators::ensure_permission(state, &user, &post.forum, ModPermission::ManagePosts)
+            .await
+            .extend_err(|_, _| {})?;
+
+        if let Some(rule) = &rule {
+            forums::ensure_rule_exists(state, &post.forum, rule)
+                .await
+                .extend_err(|_, _| {})?;
+        }
+
+        post.locked = locked;
+        post.lock_reason = reason.clone();
+
+        let was_counted = post.is_counted();
+        let post = posts::save_post(state, post, was_counted, &["locked", "lock_reason"])
+            .await
+            .extend_err(|_, _| {})?;
+
+        modlog::record_citing(
+            state,
+            &post.forum,
+            &user.id,
+            if locked {
+                ModAction::LockPost
+            } else {
+                ModAction::UnlockPost
+            },
+            Some(post.id.clone()),
+            reason.clone(),
+            rule,
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        let post: Post = post.into();
+
+        publish_event(
+            state,
+            &post.forum_id,
+            &RtEvent {
+                ty: RtEventType::PostLock,
+                event_data: RtEventData::PostLock(PostLockEvent {
+                    post_id: post.id.clone(),
+                    forum_id: post.forum_id.clone(),
+                    locked,
+                    reason,
+                }),
+            },
+        );
+
+        Ok(post)
+    } else {
+        Err(RtwalkError::PostNotFound).extend_err(|_, _| {})
+    }
+}
+
+async fn set_post_pin(
+    ctx: &Context<'_>,
+    post_id: Key,
+    pinned: bool,
+) -> async_graphql::Result<Post> {
+    let state = state!(ctx);
+    let user = user!(ctx);
+
+    let post: Option<DBPost> = state.db.select(("post", post_id.0)).await?;
+
+    if let Some(post) = post {
+        moderators::ensure_permission(state, &user, &post.forum, ModPermission::ManagePosts)
+            .await
+            .extend_err(|_, _| {})?;
+
+        if post.deleted {
+            return Err(RtwalkError::PostDeleted).extend_err(|_, _| {});
+        }
+
+        let post = posts::set_pinned(state, &post, pinned)
+            .await
+            .extend_err(|_, _| {})?;
+
+        modlog::record(
+            state,
+            &post.forum,
+            &user.id,
+            if pinned {
+                ModAction::PinPost
+            } else {
+                ModAction::UnpinPost
+            },
+            Some(post.id.clone()),
+            None,
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        let post: Post = post.into();
+
+        publish_event(
+            state,
+            &post.forum_id,
+            &RtEvent {
+                ty: RtEventType::PostPin,
+                event_data: RtEventData::PostPin(PostPinEvent {
+                    post_id: post.id.clone(),
+                    forum_id: post.forum_id.clone(),
+                    pinned,
+                }),
+            },
+        );
+
+        Ok(post)
+    } else {
+        Err(RtwalkError::PostNotFound).extend_err(|_, _| {})
+    }
+}
+
 #[derive(Default)]
 pub struct PostQueryRoot;
 
@@ -165,7 +783,8 @@ impl PostQueryRoot {
     ) -> async_graphql::Result<Option<Post>> {
         let state = state!(ctx);
 
-        let post = posts::fetch_post(state, criteria)
+        let viewer = viewer(ctx).await?;
+        let post = posts::fetch_post(state, criteria, viewer.as_ref())
             .await
             .extend_err(|_, _| {})?;
 
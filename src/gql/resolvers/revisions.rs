@@ -0,0 +1,21 @@
+use async_graphql::{ComplexObject, Context, ResultExt};
+
+use crate::{
+    gql::{state, users},
+    models::{revision::Revision, user::User},
+};
+
+use super::users::UserSelectCriteria;
+
+#[ComplexObject]
+impl Revision {
+    async fn editor(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        let state = state!(ctx);
+
+        let user = users::fetch_user(state, UserSelectCriteria::Id(self.editor_id.to_string()))
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(user.map(|x| x.into()))
+    }
+}
@@ -1,8 +1,9 @@
-use async_graphql::SimpleObject;
 use async_graphql::{ComplexObject, Context, Object, ResultExt};
+use async_graphql::{InputObject, SimpleObject};
 use surrealdb::RecordId;
 
 use crate::models::Key;
+use crate::state::ClientIp;
 use crate::{
     config,
     error::RtwalkError,
@@ -17,8 +18,11 @@ use rustis::{
 use tower_cookies::cookie::time::Duration;
 use tower_cookies::Cookie;
 
-use super::super::{cookies, state, user, users, users::PasswordValidator, Role};
-use crate::models::user::{DBUser, User};
+use super::super::{
+    challenges, cookies, members, state, user, users, users::PasswordValidator, Role,
+};
+use crate::models::forum::Forum;
+use crate::models::user::{DBUser, Preferences, PreferencesInput, User};
 
 #[ComplexObject]
 impl User {
@@ -47,6 +51,45 @@ impl User {
             .extend_err(|_, _| {})
             .into()
     }
+
+    /// Only visible to the user themselves.
+    #[graphql(guard = Role::Authenticated)]
+    async fn preferences(&self, ctx: &Context<'_>) -> async_graphql::Result<Preferences> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        if user.id != self.id {
+            return Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {});
+        }
+
+        let preferences = users::fetch_preferences(state, &self.id)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(preferences.into())
+    }
+
+    /// Forums the user joined, most recent first. Only visible to the user themselves.
+    #[graphql(guard = Role::Authenticated)]
+    async fn joined_forums(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20, validator(maximum = 100))] per_page: u32,
+    ) -> async_graphql::Result<Vec<Forum>> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        if user.id != self.id {
+            return Err(RtwalkError::UnauhorizedRequest).extend_err(|_, _| {});
+        }
+
+        let forums = members::fetch_joined_forums(state, &self.id, page, per_page)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(forums.into_iter().map(|x| x.into()).collect())
+    }
 }
 
 #[derive(Default)]
@@ -85,6 +128,42 @@ impl UserQueryRoot {
 
         Ok(user.map(|x| x.into()))
     }
+
+    /// Proof of work required by `createUser` and `createBot`. Find any `solution` such that
+    /// `sha256("{nonce}:{solution}")` starts with `difficulty` zero bits.
+    /// Difficulty goes up with the number of recent signups from your ip.
+    async fn registration_challenge(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<RegistrationChallenge> {
+        let state = state!(ctx);
+        let ip = ctx.data_opt::<ClientIp>().map(|ip| ip.0);
+
+        let (nonce, difficulty) = challenges::create_challenge(state, ip)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(RegistrationChallenge {
+            nonce,
+            difficulty,
+            expires_in: config::CHALLENGE_EXPIERY_SECONDS,
+        })
+    }
+}
+
+#[derive(SimpleObject)]
+struct RegistrationChallenge {
+    nonce: String,
+    difficulty: u32,
+    /// Seconds
+    expires_in: u64,
+}
+
+#[derive(InputObject)]
+pub struct ChallengeSolution {
+    nonce: String,
+    #[graphql(validator(max_length = 64))]
+    solution: String,
 }
 
 #[derive(SimpleObject)]
@@ -114,12 +193,22 @@ impl UserMutationRoot {
             custom = PasswordValidator(&username, &email)
         ))]
         password: String,
+        challenge: ChallengeSolution,
     ) -> async_graphql::Result<&str> {
-        // On success makes 1 database and 2 redis query.
-        // Maximum 1 database and 1 redis query on failure.
+        // On success makes 1 database and 4 redis query.
+        // Maximum 1 database and 2 redis query on failure.
         // Also hashing takes place in this step. Its normal for latency to be > 1s.
         // Also email gets sends here. TODO: Doc if email is sent immediately or pushed to a queue.
-        users::push_pending(state!(ctx), username, email, password)
+        let state = state!(ctx);
+        let ip = ctx.data_opt::<ClientIp>().map(|ip| ip.0);
+
+        challenges::verify_challenge(state, &challenge.nonce, &challenge.solution)
+            .await
+            .extend_err(|_, _| {})?;
+        users::push_pending(state, username, email, password)
+            .await
+            .extend_err(|_, _| {})?;
+        challenges::record_signup(state, ip)
             .await
             .extend_err(|_, _| {})?;
         Ok("Verification code sent to email")
@@ -294,10 +383,20 @@ impl UserMutationRoot {
         &self,
         ctx: &Context<'r>,
         username: String,
+        challenge: ChallengeSolution,
     ) -> async_graphql::Result<Bot> {
         let user = user!(ctx);
+        let state = state!(ctx);
+        let ip = ctx.data_opt::<ClientIp>().map(|ip| ip.0);
+
+        challenges::verify_challenge(state, &challenge.nonce, &challenge.solution)
+            .await
+            .extend_err(|_, _| {})?;
         // 2 database and 1 redis query on sucess
-        let (token, bot) = users::create_bot(state!(ctx), user.id, username)
+        let (token, bot) = users::create_bot(state, user.id, username)
+            .await
+            .extend_err(|_, _| {})?;
+        challenges::record_signup(state, ip)
             .await
             .extend_err(|_, _| {})?;
         Ok(Bot {
@@ -667,6 +766,57 @@ impl UserMutationRoot {
         Ok(user)
     }
 
+    #[graphql(guard = Role::Authenticated)]
+    async fn update_preferences(
+        &self,
+        ctx: &Context<'_>,
+        preferences: PreferencesInput,
+    ) -> async_graphql::Result<Preferences> {
+        let PreferencesInput {
+            default_post_sort,
+            show_nsfw,
+            email_post_comments,
+            email_moderation,
+            language,
+            timezone,
+        } = preferences;
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let mut preferences = users::fetch_preferences(state, &user.id)
+            .await
+            .extend_err(|_, _| {})?;
+
+        if let Some(default_post_sort) = default_post_sort {
+            preferences.default_post_sort = default_post_sort;
+        }
+        if let Some(show_nsfw) = show_nsfw {
+            preferences.show_nsfw = show_nsfw;
+        }
+        if let Some(email_post_comments) = email_post_comments {
+            preferences.email_post_comments = email_post_comments;
+        }
+        if let Some(email_moderation) = email_moderation {
+            preferences.email_moderation = email_moderation;
+        }
+        if language.is_null() {
+            preferences.language = None;
+        } else if let MaybeUndefined::Value(v) = language {
+            preferences.language = Some(v);
+        }
+        if timezone.is_null() {
+            preferences.timezone = None;
+        } else if let MaybeUndefined::Value(v) = timezone {
+            preferences.timezone = Some(v);
+        }
+
+        let preferences = users::update_preferences(state, preferences)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(preferences.into())
+    }
+
     #[graphql(guard = Role::Admin, visible = false)]
     async fn ban_user(
         &self,
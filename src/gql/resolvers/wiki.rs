@@ -0,0 +1,233 @@
+use async_graphql::{ComplexObject, Context, Object, OneofObject, ResultExt};
+use surrealdb::RecordId;
+
+use crate::{
+    error::RtwalkError,
+    gql::{forums, modlog, state, user, users, wiki},
+    models::{
+        forum::{DBForum, Forum},
+        moderator::ModPermission,
+        modlog::ModAction,
+        user::User,
+        wiki::{WikiDiff, WikiPage, WikiPageInput, WikiRevision},
+        Key,
+    },
+    state::State,
+};
+
+use super::{
+    super::{ForumGuard, Role},
+    forums::ForumSelectCriteria,
+    users::UserSelectCriteria,
+};
+
+#[ComplexObject]
+impl WikiPage {
+    async fn forum(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Forum>> {
+        let state = state!(ctx);
+
+        let forum = forums::fetch_forum(state, ForumSelectCriteria::Id(self.forum_id.to_string()))
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(forum.map(|x| x.into()))
+    }
+
+    async fn updated_by(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        let state = state!(ctx);
+
+        let user = users::fetch_user(
+            state,
+            UserSelectCriteria::Id(self.updated_by_id.to_string()),
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        Ok(user.map(|x| x.into()))
+    }
+
+    /// Newest first.
+    async fn revisions(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20, validator(maximum = 100))] per_page: u32,
+    ) -> async_graphql::Result<Vec<WikiRevision>> {
+        let state = state!(ctx);
+        let id = RecordId::from_table_key("wiki_page", self.id.0.clone());
+
+        let revisions = wiki::fetch_revisions(state, &id, page, per_page)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(revisions.into_iter().map(|x| x.into()).collect())
+    }
+
+    async fn revision(
+        &self,
+        ctx: &Context<'_>,
+        number: u32,
+    ) -> async_graphql::Result<Option<WikiRevision>> {
+        let state = state!(ctx);
+        let id = RecordId::from_table_key("wiki_page", self.id.0.clone());
+
+        let revision = wiki::fetch_revision(state, &id, number)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(revision.map(|x| x.into()))
+    }
+
+    /// Line diff of the content between two revisions, `to` defaults to the latest.
+    async fn diff(
+        &self,
+        ctx: &Context<'_>,
+        from: u32,
+        to: Option<u32>,
+    ) -> async_graphql::Result<WikiDiff> {
+        let state = state!(ctx);
+        let id = RecordId::from_table_key("wiki_page", self.id.0.clone());
+        let to = to.unwrap_or(self.latest_revision);
+
+        let mut revisions = vec![];
+        for number in [from, to] {
+            let revision = wiki::fetch_revision(state, &id, number)
+                .await
+                .extend_err(|_, _| {})?
+                .ok_or(RtwalkError::WikiRevisionNotFound)
+                .extend_err(|_, _| {})?;
+            revisions.push(revision);
+        }
+        let (old, new) = (&revisions[0], &revisions[1]);
+
+        Ok(WikiDiff {
+            from,
+            to,
+            title_changed: old.title != new.title,
+            lines: wiki::diff_lines(&old.content, &new.content)
+                .ok_or(RtwalkError::WikiDiffTooLarge)
+                .extend_err(|_, _| {})?,
+        })
+    }
+}
+
+#[ComplexObject]
+impl WikiRevision {
+    async fn author(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        let state = state!(ctx);
+
+        let user = users::fetch_user(state, UserSelectCriteria::Id(self.author_id.to_string()))
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(user.map(|x| x.into()))
+    }
+}
+
+#[derive(OneofObject)]
+pub enum MultipleWikiPageSelectCriteria {
+    /// Every page of the forum, by slug.
+    Forum(String),
+    Search(String),
+}
+
+async fn fetch_forum(state: &State, forum_id: Key) -> Result<DBForum, RtwalkError> {
+    forums::fetch_forum(state, ForumSelectCriteria::Id(forum_id.to_string()))
+        .await?
+        .ok_or(RtwalkError::ForumNotFound)
+}
+
+#[derive(Default)]
+pub struct WikiMutationRoot;
+
+#[Object]
+impl WikiMutationRoot {
+    /// Creates the page if there's none with this slug. Pass the revision the edit
+    /// started from as `baseRevision` to fail instead of overwriting someone else's save.
+    #[graphql(guard = Role::Authenticated)]
+    async fn save_wiki_page(
+        &self,
+        ctx: &Context<'_>,
+        forum_id: Key,
+        page: WikiPageInput,
+    ) -> async_graphql::Result<WikiPage> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let forum = fetch_forum(state, forum_id).await.extend_err(|_, _| {})?;
+        wiki::ensure_can_edit(state, &user, &forum)
+            .await
+            .extend_err(|_, _| {})?;
+
+        let page = wiki::save_page(state, &forum.id, page, user.id)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(page.into())
+    }
+
+    /// Saves the content of an old revision as a new revision.
+    #[graphql(guard = Role::Authenticated)]
+    async fn revert_wiki_page(
+        &self,
+        ctx: &Context<'_>,
+        forum_id: Key,
+        slug: String,
+        revision: u32,
+    ) -> async_graphql::Result<WikiPage> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+
+        let forum = fetch_forum(state, forum_id).await.extend_err(|_, _| {})?;
+        wiki::ensure_can_edit(state, &user, &forum)
+            .await
+            .extend_err(|_, _| {})?;
+
+        let page = wiki::fetch_page(state, &forum.id, slug)
+            .await
+            .extend_err(|_, _| {})?
+            .ok_or(RtwalkError::WikiPageNotFound)
+            .extend_err(|_, _| {})?;
+        let page = wiki::revert_page(state, &page, revision, user.id)
+            .await
+            .extend_err(|_, _| {})?;
+
+        Ok(page.into())
+    }
+
+    /// Deletes the page with all of its revisions.
+    #[graphql(guard = "ForumGuard::new(forum_id.clone(), ModPermission::EditForum)")]
+    async fn delete_wiki_page(
+        &self,
+        ctx: &Context<'_>,
+        forum_id: Key,
+        slug: String,
+    ) -> async_graphql::Result<bool> {
+        let state = state!(ctx);
+        let user = user!(ctx);
+        let forum = RecordId::from_table_key("forum", forum_id.0);
+
+        let Some(page) = wiki::fetch_page(state, &forum, slug)
+            .await
+            .extend_err(|_, _| {})?
+        else {
+            return Ok(false);
+        };
+        wiki::delete_page(state, &page)
+            .await
+            .extend_err(|_, _| {})?;
+
+        modlog::record(
+            state,
+            &forum,
+            &user.id,
+            ModAction::DeleteWikiPage,
+            None,
+            Some(page.slug),
+        )
+        .await
+        .extend_err(|_, _| {})?;
+
+        Ok(true)
+    }
+}
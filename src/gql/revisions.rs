@@ -0,0 +1,85 @@
+use surrealdb::RecordId;
+
+use crate::{
+    error::RtwalkError,
+    gql::{forums, moderators},
+    models::{forum::DBForum, moderator::ModPermission, revision::DBRevision, user::User},
+    state::State,
+};
+
+pub async fn create_revision(state: &State, revision: DBRevision) -> Result<(), RtwalkError> {
+    state
+        .db
+        .query("CREATE revision CONTENT $revision")
+        .bind(("revision", revision))
+        .await?
+        .check()?;
+
+    Ok(())
+}
+
+/// Earlier versions of the post or comment, newest first.
+pub async fn fetch_revisions(
+    state: &State,
+    target: &RecordId,
+    page: u32,
+    per_page: u32,
+) -> Result<Vec<DBRevision>, RtwalkError> {
+    let mut res = state
+        .db
+        .query(
+            "SELECT * FROM revision WHERE target = $target ORDER BY created_at DESC LIMIT $limit START $start",
+        )
+        .bind(("target", target.clone()))
+        .bind(("limit", per_page))
+        .bind(("start", (page.max(1) - 1).saturating_mul(per_page)))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+/// Moderators with `permission` can always see edit history, everyone who can read the
+/// forum only when it's public there and the post or comment isn't `deleted`.
+pub async fn ensure_can_view(
+    state: &State,
+    viewer: Option<&User>,
+    forum: &RecordId,
+    permission: ModPermission,
+    deleted: bool,
+) -> Result<(), RtwalkError> {
+    let forum: DBForum = state
+        .db
+        .select(forum)
+        .await?
+        .ok_or(RtwalkError::ForumNotFound)?;
+
+    if !deleted
+        && forum.settings.public_edit_history
+        && forums::can_read(state, viewer, &forum).await?
+    {
+        return Ok(());
+    }
+    match viewer {
+        Some(viewer)
+            if moderators::has_permission(state, viewer, &forum.id, permission).await? =>
+        {
+            Ok(())
+        }
+        _ => Err(RtwalkError::UnauhorizedRequest),
+    }
+}
+
+/// Locations of the files earlier versions of the post or comment point at.
+pub async fn fetch_referenced_files(
+    state: &State,
+    target: &RecordId,
+) -> Result<Vec<String>, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT VALUE attachments.loc FROM revision WHERE target = $target")
+        .bind(("target", target.clone()))
+        .await?;
+    let locs: Vec<Vec<String>> = res.take(0)?;
+
+    Ok(locs.into_iter().flatten().collect())
+}
@@ -0,0 +1,141 @@
+use chrono::{NaiveDate, TimeDelta, Utc};
+use rustis::{
+    client::BatchPreparedCommand,
+    commands::{
+        ExpireOption, GenericCommands, HashCommands, SortedSetCommands, ZAggregate, ZRangeOptions,
+    },
+};
+use surrealdb::RecordId;
+
+use crate::{
+    config,
+    error::RtwalkError,
+    gql::posts::Submission,
+    models::{
+        stats::{Contributor, DailyStats},
+        Key,
+    },
+    state::State,
+};
+
+/// Post and comment counts of the day.
+fn counts_key(forum: &RecordId, day: NaiveDate) -> String {
+    format!("forum_stats:{}:{}", forum.key(), day.format("%Y-%m-%d"))
+}
+
+/// Submissions per user that day, its size is the number of active posters.
+fn posters_key(forum: &RecordId, day: NaiveDate) -> String {
+    format!("forum_posters:{}:{}", forum.key(), day.format("%Y-%m-%d"))
+}
+
+/// The last `days` days, oldest first, today included.
+fn window(days: u32) -> Vec<NaiveDate> {
+    let today = Utc::now().date_naive();
+    (0..days as i64)
+        .rev()
+        .map(|d| today - TimeDelta::days(d))
+        .collect()
+}
+
+/// Adds a new post or comment to today's bucket.
+pub async fn record(
+    state: &State,
+    forum: &RecordId,
+    user: &Key,
+    kind: Submission,
+) -> Result<(), RtwalkError> {
+    let today = Utc::now().date_naive();
+    let (counts, posters) = (counts_key(forum, today), posters_key(forum, today));
+    let field = match kind {
+        Submission::Post => "posts",
+        Submission::Comment => "comments",
+    };
+
+    let mut pipeline = state.redis.create_pipeline();
+    pipeline.hincrby(&counts, field, 1).forget();
+    pipeline.zincrby(&posters, 1.0, user.0.to_string()).forget();
+    pipeline
+        .expire(&counts, config::STATS_RETENTION_SECONDS, ExpireOption::Nx)
+        .forget();
+    pipeline
+        .expire(&posters, config::STATS_RETENTION_SECONDS, ExpireOption::Nx)
+        .forget();
+    pipeline.execute::<()>().await?;
+
+    Ok(())
+}
+
+pub async fn fetch_daily(
+    state: &State,
+    forum: &RecordId,
+    days: u32,
+) -> Result<Vec<DailyStats>, RtwalkError> {
+    let days = window(days);
+
+    let mut counts = state.redis.create_pipeline();
+    let mut posters = state.redis.create_pipeline();
+    for day in &days {
+        counts
+            .hmget::<_, _, Option<u64>, _, Vec<Option<u64>>>(
+                counts_key(forum, *day),
+                ["posts", "comments"],
+            )
+            .queue();
+        posters.zcard(posters_key(forum, *day)).queue();
+    }
+    let counts: Vec<Vec<Option<u64>>> = counts.execute().await?;
+    let posters: Vec<u64> = posters.execute().await?;
+
+    Ok(days
+        .into_iter()
+        .zip(counts)
+        .zip(posters)
+        .map(|((day, counts), active_posters)| DailyStats {
+            date: day.format("%Y-%m-%d").to_string(),
+            posts: counts.first().copied().flatten().unwrap_or(0),
+            comments: counts.get(1).copied().flatten().unwrap_or(0),
+            active_posters,
+        })
+        .collect())
+}
+
+pub async fn fetch_top_contributors(
+    state: &State,
+    forum: &RecordId,
+    days: u32,
+    limit: usize,
+) -> Result<Vec<Contributor>, RtwalkError> {
+    let keys: Vec<String> = window(days)
+        .into_iter()
+        .map(|day| posters_key(forum, day))
+        .collect();
+
+    if limit == 0 {
+        return Ok(vec![]);
+    }
+
+    // Summed into a throwaway key so only the top entries come back.
+    let union = format!("forum_top:{}:{}", forum.key(), cuid2::cuid());
+    let mut pipeline = state.redis.create_pipeline();
+    pipeline
+        .zunionstore(&union, keys, Option::<f64>::None, ZAggregate::Sum)
+        .forget();
+    pipeline
+        .zrange_with_scores::<_, _, String>(
+            &union,
+            0,
+            limit as isize - 1,
+            ZRangeOptions::default().reverse(),
+        )
+        .queue();
+    pipeline.del(&union).forget();
+    let totals: Vec<(String, f64)> = pipeline.execute().await?;
+
+    Ok(totals
+        .into_iter()
+        .map(|(user, submissions)| Contributor {
+            user_id: Key::from(user),
+            submissions: submissions as u64,
+        })
+        .collect())
+}
@@ -1,13 +1,13 @@
-use std::env;
 use std::ops::Deref;
 
 use crate::config;
+use crate::mail;
 use crate::models::user::User;
 use crate::models::Key;
 use crate::template::EmailVerify;
 use crate::{
     error::RtwalkError,
-    models::user::{DBUser, DBUserSecret},
+    models::user::{DBUser, DBUserPreferences, DBUserSecret},
     state::State,
 };
 
@@ -18,9 +18,6 @@ use argon2::{
 use async_graphql::CustomValidator;
 use chrono::DateTime;
 use cuid2::cuid;
-use lettre::message::header::ContentType;
-use lettre::transport::smtp::authentication::Credentials;
-use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
 use rand::Rng;
 
 use rustis::commands::GenericCommands;
@@ -33,6 +30,7 @@ use sailfish::TemplateSimple;
 use surrealdb::RecordId;
 use zxcvbn::zxcvbn;
 
+use super::email_domains;
 use super::resolvers::users::{MultipleUserSelectCriteria, UserSelectCriteria};
 use super::PageInfo;
 
@@ -64,7 +62,9 @@ pub async fn push_pending(
     password: String,
 ) -> Result<(), RtwalkError> {
     // Assumes data is already validated.
-    // First make sure username is unique
+    // Reject blocked/disposable email domains before doing anything else.
+    email_domains::check_email_domain(state, &email).await?;
+    // Then make sure username is unique
     let mut exists = state
         .db
         .query("SELECT 1 FROM user WHERE username = $username")
@@ -110,42 +110,7 @@ pub async fn push_pending(
     .render_once()
     .expect("Can't fail");
 
-    let email_message = Message::builder()
-        .from(
-            format!(
-                "{} <{}>",
-                env::var("SMTP_FROM_NAME").expect("SMTP_FROM_NAME must be set"),
-                env::var("SMTP_FROM").expect("SMTP_FROM must be set")
-            )
-            .parse()
-            .unwrap(),
-        )
-        .to(format!("{username} <{email}>").parse().unwrap())
-        .subject("Verify your email")
-        .header(ContentType::TEXT_HTML)
-        .body(template)
-        .unwrap();
-
-    let creds = Credentials::new(
-        env::var("SMTP_USERNAME").expect("SMTP_USERNAME must be set"),
-        env::var("SMTP_PASSWORD").expect("SMTP_PASSWORD must be set"),
-    );
-
-    let mailer: AsyncSmtpTransport<Tokio1Executor> =
-        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(
-            &env::var("SMTP_RELAY").expect("SMTP_RELAY must be set"),
-        )
-        .unwrap()
-        .credentials(creds)
-        .port(
-            env::var("SMTP_PORT")
-                .expect("SMTP_PORT must be set")
-                .parse::<u16>()
-                .expect("SMTP_PORT must be u16"),
-        )
-        .build();
-
-    mailer.send(email_message).await?;
+    mail::send_mail(&username, &email, "Verify your email", template).await?;
 
     // TODO: Actually send the mail, just printing for now
     // WARNING: Dont forget this ^
@@ -268,14 +233,16 @@ pub async fn verify_user(
             return Err(RtwalkError::InvalidVerificationCode);
         }
         // The user is REAL, make his account
+        let mut user: DBUser = serde_json::from_str(&user).map_err(|e| {
+            RtwalkError::ImpossibleError(
+                "User serialized by server can't be invalid",
+                Some(e.into()),
+            )
+        })?;
+        user.email_verified = true;
         let user = create_user(
             state,
-            serde_json::from_str(&user).map_err(|e| {
-                RtwalkError::ImpossibleError(
-                    "User serialized by server can't be invalid",
-                    Some(e.into()),
-                )
-            })?,
+            user,
             serde_json::from_str(&pending_secret).map_err(|e| {
                 RtwalkError::ImpossibleError(
                     "UserSecret serialized by server can't be invalid",
@@ -568,6 +535,34 @@ pub async fn update_user(state: &State, updated_user: User) -> Result<DBUser, Rt
     res.ok_or(RtwalkError::ImpossibleError("Failed at user update", None))
 }
 
+pub async fn fetch_preferences(
+    state: &State,
+    user: &Key,
+) -> Result<DBUserPreferences, RtwalkError> {
+    let preferences: Option<DBUserPreferences> = state
+        .db
+        .select(("user_preferences", user.0.clone()))
+        .await?;
+
+    Ok(preferences.unwrap_or_else(|| DBUserPreferences::new(user.clone())))
+}
+
+pub async fn update_preferences(
+    state: &State,
+    preferences: DBUserPreferences,
+) -> Result<DBUserPreferences, RtwalkError> {
+    let res: Option<DBUserPreferences> = state
+        .db
+        .upsert(&preferences.id)
+        .content(preferences)
+        .await?;
+
+    res.ok_or(RtwalkError::ImpossibleError(
+        "Upsert always returns the record",
+        None,
+    ))
+}
+
 pub async fn fetch_user(
     state: &State,
     criteria: UserSelectCriteria,
@@ -691,3 +686,15 @@ pub async fn fetch_users(
 
     Ok(user)
 }
+
+/// Karma in the session can be stale, this reads the current value.
+pub async fn fetch_karma(state: &State, user: &Key) -> Result<i64, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT VALUE karma FROM ONLY $user")
+        .bind(("user", RecordId::from_table_key("user", user.0.clone())))
+        .await?;
+    let karma: Option<i64> = res.take(0)?;
+
+    Ok(karma.unwrap_or(0))
+}
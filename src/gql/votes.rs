@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use rustis::commands::{SetCondition, SetExpiration, StringCommands};
+use surrealdb::RecordId;
+use tracing::error;
+
+use crate::{
+    config,
+    error::RtwalkError,
+    gql::publish_event,
+    models::{
+        post::DBPost,
+        vote::{DBVote, VoteDirection},
+        Key, PostScoreEvent, RtEvent, RtEventData, RtEventType,
+    },
+    state::State,
+};
+
+/// Votes of any of `users` on any of `posts`.
+pub async fn fetch_votes(
+    state: &State,
+    users: &[RecordId],
+    posts: &[RecordId],
+) -> Result<Vec<DBVote>, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT * FROM vote WHERE in IN $users AND out IN $posts")
+        .bind(("users", users.to_vec()))
+        .bind(("posts", posts.to_vec()))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+/// Sets, changes or with `None` removes the user's vote. The post's counters and the
+/// author's karma are updated in the same transaction, from the vote that's actually
+/// stored so concurrent votes can't count twice.
+pub async fn vote(
+    state: &State,
+    user: Key,
+    post: &DBPost,
+    direction: Option<VoteDirection>,
+) -> Result<DBPost, RtwalkError> {
+    if post.poster.key() == &user.0 {
+        return Err(RtwalkError::CannotVoteOwnPost);
+    }
+
+    let vote = direction.map(|d| DBVote::new(user.clone(), post.id.clone(), d));
+    let value = direction.map(|d| d.value()).unwrap_or(0);
+
+    state
+        .db
+        .query("BEGIN TRANSACTION")
+        .query("LET $old = (SELECT VALUE value FROM vote WHERE in = $user AND out = $post)[0] ?? 0")
+        .query("DELETE vote WHERE in = $user AND out = $post")
+        .query("IF $vote != NONE { INSERT RELATION INTO vote $vote }")
+        .query(
+            "UPDATE $post SET score += $value - $old, \
+            upvotes += (IF $value = 1 { 1 } ELSE { 0 }) - (IF $old = 1 { 1 } ELSE { 0 }), \
+            downvotes += (IF $value = -1 { 1 } ELSE { 0 }) - (IF $old = -1 { 1 } ELSE { 0 }), \
+            ranks_stale = true",
+        )
+        .query("UPDATE $author SET karma += $value - $old")
+        .query("COMMIT TRANSACTION")
+        .bind(("user", RecordId::from_table_key("user", user.0)))
+        .bind(("post", post.id.clone()))
+        .bind(("author", post.poster.clone()))
+        .bind(("vote", vote))
+        .bind(("value", value))
+        .await?
+        .check()?;
+
+    let post: Option<DBPost> = state.db.select(&post.id).await?;
+    let post = post.ok_or(RtwalkError::PostNotFound)?;
+
+    schedule_score_event(state, &post);
+
+    Ok(post)
+}
+
+/// Announces the score at most once per [`config::SCORE_EVENT_THROTTLE_SECONDS`] per post.
+/// The first vote of a window schedules the event for its end, so it carries the score
+/// after every vote in the window.
+fn schedule_score_event(state: &State, post: &DBPost) {
+    let state = state.clone();
+    let post = post.id.clone();
+
+    tokio::spawn(async move {
+        let scheduled = state
+            .redis
+            .set_with_options(
+                format!("score_event:{}", post.key()),
+                1,
+                SetCondition::NX,
+                SetExpiration::Ex(config::SCORE_EVENT_THROTTLE_SECONDS),
+                false,
+            )
+            .await;
+        match scheduled {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => {
+                error!("Failed to throttle score event: {:?}", e);
+                return;
+            }
+        }
+
+        tokio::time::sleep(Duration::from_secs(config::SCORE_EVENT_THROTTLE_SECONDS)).await;
+
+        let post: Option<DBPost> = match state.db.select(&post).await {
+            Ok(post) => post,
+            Err(e) => {
+                error!("Failed to fetch post for score event: {:?}", e);
+                return;
+            }
+        };
+        let Some(post) = post else {
+            return;
+        };
+        let forum = Key(post.forum.key().to_owned());
+
+        publish_event(
+            &state,
+            &forum,
+            &RtEvent {
+                ty: RtEventType::PostScore,
+                event_data: RtEventData::PostScore(PostScoreEvent {
+                    post_id: Key(post.id.key().to_owned()),
+                    forum_id: forum.clone(),
+                    score: post.score,
+                    upvotes: post.upvotes,
+                    downvotes: post.downvotes,
+                }),
+            },
+        );
+    });
+}
@@ -0,0 +1,405 @@
+use surrealdb::RecordId;
+
+use crate::{
+    config,
+    error::RtwalkError,
+    gql::{bans, forums, members, moderators, PageInfo},
+    models::{
+        forum::DBForum,
+        moderator::ModPermission,
+        user::User,
+        wiki::{DBWikiPage, DBWikiRevision, DiffKind, DiffLine, WikiEditors, WikiPageInput},
+        Key,
+    },
+    state::State,
+};
+
+use super::resolvers::wiki::MultipleWikiPageSelectCriteria;
+
+/// Thrown by the save transaction when the page changed since it was read.
+const EDIT_CONFLICT: &str = "wiki page was edited concurrently";
+
+/// Moderators with `EditForum` can always edit, members only when the forum allows it
+/// and it isn't locked.
+pub async fn ensure_can_edit(
+    state: &State,
+    user: &User,
+    forum: &DBForum,
+) -> Result<(), RtwalkError> {
+    if moderators::has_permission(state, user, &forum.id, ModPermission::EditForum).await? {
+        return Ok(());
+    }
+    if forum.settings.wiki_editors != WikiEditors::Members
+        || !members::is_member(
+            state,
+            &forum.id,
+            &RecordId::from_table_key("user", user.id.0.clone()),
+        )
+        .await?
+    {
+        return Err(RtwalkError::UnauhorizedRequest);
+    }
+    if forum.locked {
+        return Err(RtwalkError::ForumLocked);
+    }
+
+    bans::ensure_not_banned(state, &forum.id, &user.id).await
+}
+
+pub async fn fetch_page(
+    state: &State,
+    forum: &RecordId,
+    slug: String,
+) -> Result<Option<DBWikiPage>, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT * FROM wiki_page WHERE forum = $forum AND slug = $slug")
+        .bind(("forum", forum.clone()))
+        .bind(("slug", slug))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+pub async fn fetch_pages(
+    state: &State,
+    criteria: MultipleWikiPageSelectCriteria,
+    viewer: Option<&User>,
+    page_info: &PageInfo,
+) -> Result<Vec<DBWikiPage>, RtwalkError> {
+    let visible = forums::visibility_condition("forum", viewer);
+    let mut conditions = vec!["forum.deleted_at = NONE", visible.as_str()];
+    let mut forum = None;
+    let mut search = None;
+
+    let order = match criteria {
+        MultipleWikiPageSelectCriteria::Forum(key) => {
+            conditions.push("forum = $forum");
+            forum = Some(RecordId::from_table_key("forum", key));
+            "slug ASC"
+        }
+        MultipleWikiPageSelectCriteria::Search(query) => {
+            conditions.push("(title @0@ $query OR content @1@ $query)");
+            search = Some(query);
+            "updated_at DESC"
+        }
+    };
+
+    let where_clause = conditions.join(" AND ");
+
+    let mut query = state.db.query(format!(
+        "SELECT * FROM wiki_page WHERE {where_clause} ORDER BY {order} LIMIT $limit START $start"
+    ));
+
+    if page_info.needs_page_info {
+        query = query.query(format!(
+            "SELECT count() as total FROM wiki_page WHERE {where_clause} GROUP ALL"
+        ));
+    }
+
+    let mut res = query
+        .bind(("forum", forum))
+        .bind(("query", search))
+        .bind(forums::viewer_access(state, viewer).await?)
+        .bind(("limit", page_info.per_page))
+        .bind((
+            "start",
+            (page_info.page - 1).saturating_mul(page_info.per_page),
+        ))
+        .await?;
+
+    if page_info.needs_page_info {
+        if let Some(total) = res.take((1, "total"))? {
+            page_info.set_total(total);
+        }
+    }
+
+    Ok(res.take(0)?)
+}
+
+/// Creates the page or updates it, storing the new version as a revision either way.
+/// With `base_revision` the save fails if someone else saved since that revision.
+pub async fn save_page(
+    state: &State,
+    forum: &RecordId,
+    input: WikiPageInput,
+    author: Key,
+) -> Result<DBWikiPage, RtwalkError> {
+    let WikiPageInput {
+        slug,
+        title,
+        content,
+        reason,
+        base_revision,
+    } = input;
+    let existing = fetch_page(state, forum, slug.clone()).await?;
+
+    let page = match existing {
+        Some(mut page) => {
+            if base_revision.is_some_and(|r| r != page.revision) {
+                return Err(RtwalkError::WikiEditConflict);
+            }
+            page.title = title;
+            page.content = content;
+            page.revision += 1;
+            page.updated_by = RecordId::from_table_key("user", author.0);
+            page.updated_at = chrono::Utc::now();
+            page
+        }
+        None => {
+            if base_revision.is_some() {
+                return Err(RtwalkError::WikiEditConflict);
+            }
+            DBWikiPage::new(forum.clone(), slug, title, content, author)
+        }
+    };
+    let revision = DBWikiRevision::new(&page, reason);
+
+    // Updates only apply on top of the revision that was read, so concurrent saves can't
+    // both become the same revision. Concurrent creates hit the unique slug index.
+    let save = if page.revision == 1 {
+        "CREATE wiki_page CONTENT $page"
+    } else {
+        "IF (UPDATE $id CONTENT $page WHERE revision = $previous RETURN id) = [] \
+        { THROW $conflict }"
+    };
+
+    state
+        .db
+        .query("BEGIN TRANSACTION")
+        .query(save)
+        .query("CREATE wiki_revision CONTENT $revision")
+        .query("COMMIT TRANSACTION")
+        .bind(("id", page.id.clone()))
+        .bind(("previous", page.revision - 1))
+        .bind(("page", page.clone()))
+        .bind(("revision", revision))
+        .bind(("conflict", EDIT_CONFLICT))
+        .await?
+        .check()
+        .map_err(|e| {
+            if e.to_string().contains(EDIT_CONFLICT) || e.to_string().contains("wiki_page_slug") {
+                RtwalkError::WikiEditConflict
+            } else {
+                e.into()
+            }
+        })?;
+
+    Ok(page)
+}
+
+/// Saves the content of an old revision as a new one.
+pub async fn revert_page(
+    state: &State,
+    page: &DBWikiPage,
+    number: u32,
+    author: Key,
+) -> Result<DBWikiPage, RtwalkError> {
+    let revision = fetch_revision(state, &page.id, number)
+        .await?
+        .ok_or(RtwalkError::WikiRevisionNotFound)?;
+
+    save_page(
+        state,
+        &page.forum,
+        WikiPageInput {
+            slug: page.slug.clone(),
+            title: revision.title,
+            content: revision.content,
+            reason: Some(format!("Reverted to revision {number}")),
+            base_revision: Some(page.revision),
+        },
+        author,
+    )
+    .await
+}
+
+pub async fn delete_page(state: &State, page: &DBWikiPage) -> Result<(), RtwalkError> {
+    state
+        .db
+        .query("BEGIN TRANSACTION")
+        .query("DELETE wiki_revision WHERE page = $page")
+        .query("DELETE $page")
+        .query("COMMIT TRANSACTION")
+        .bind(("page", page.id.clone()))
+        .await?
+        .check()?;
+
+    Ok(())
+}
+
+pub async fn fetch_revision(
+    state: &State,
+    page: &RecordId,
+    number: u32,
+) -> Result<Option<DBWikiRevision>, RtwalkError> {
+    let mut res = state
+        .db
+        .query("SELECT * FROM wiki_revision WHERE page = $page AND number = $number")
+        .bind(("page", page.clone()))
+        .bind(("number", number))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+pub async fn fetch_revisions(
+    state: &State,
+    page: &RecordId,
+    page_number: u32,
+    per_page: u32,
+) -> Result<Vec<DBWikiRevision>, RtwalkError> {
+    let mut res = state
+        .db
+        .query(
+            "SELECT * FROM wiki_revision WHERE page = $page ORDER BY number DESC LIMIT $limit START $start",
+        )
+        .bind(("page", page.clone()))
+        .bind(("limit", per_page))
+        .bind(("start", (page_number.max(1) - 1).saturating_mul(per_page)))
+        .await?;
+
+    Ok(res.take(0)?)
+}
+
+/// Line based diff from the longest common subsequence of lines. Lines both versions
+/// start or end with are matched up front, `None` if more than
+/// [`config::MAX_WIKI_DIFF_LINES`] lines on either side are left after that.
+pub fn diff_lines(old: &str, new: &str) -> Option<Vec<DiffLine>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(o, n)| o == n).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(o, n)| o == n)
+        .count();
+    let (head, old_mid, new_mid, tail) = (
+        &old[..prefix],
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+        &old[old.len() - suffix..],
+    );
+    if old_mid.len() > config::MAX_WIKI_DIFF_LINES || new_mid.len() > config::MAX_WIKI_DIFF_LINES {
+        return None;
+    }
+
+    // lcs[i][j] is the LCS length of old_mid[i..] and new_mid[j..].
+    let mut lcs = vec![vec![0u16; new_mid.len() + 1]; old_mid.len() + 1];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lcs[i][j] = if old_mid[i] == new_mid[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let line = |kind, text: &str| DiffLine {
+        kind,
+        text: text.to_string(),
+    };
+    let mut lines: Vec<DiffLine> = head.iter().map(|l| line(DiffKind::Unchanged, l)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_mid.len() && j < new_mid.len() {
+        if old_mid[i] == new_mid[j] {
+            lines.push(line(DiffKind::Unchanged, old_mid[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            lines.push(line(DiffKind::Removed, old_mid[i]));
+            i += 1;
+        } else {
+            lines.push(line(DiffKind::Added, new_mid[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old_mid[i..].iter().map(|l| line(DiffKind::Removed, l)));
+    lines.extend(new_mid[j..].iter().map(|l| line(DiffKind::Added, l)));
+    lines.extend(tail.iter().map(|l| line(DiffKind::Unchanged, l)));
+
+    Some(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(old: &str, new: &str) -> Vec<(DiffKind, String)> {
+        diff_lines(old, new)
+            .expect("Small enough")
+            .into_iter()
+            .map(|l| (l.kind, l.text))
+            .collect()
+    }
+
+    fn line(kind: DiffKind, text: &str) -> (DiffKind, String) {
+        (kind, text.to_string())
+    }
+
+    #[test]
+    fn unchanged_text() {
+        assert_eq!(
+            kinds("a\nb", "a\nb"),
+            vec![
+                line(DiffKind::Unchanged, "a"),
+                line(DiffKind::Unchanged, "b")
+            ]
+        );
+        assert_eq!(kinds("", ""), vec![]);
+    }
+
+    #[test]
+    fn added_and_removed_lines() {
+        assert_eq!(
+            kinds("a\nb\nc", "a\nx\nc\nd"),
+            vec![
+                line(DiffKind::Unchanged, "a"),
+                line(DiffKind::Removed, "b"),
+                line(DiffKind::Added, "x"),
+                line(DiffKind::Unchanged, "c"),
+                line(DiffKind::Added, "d"),
+            ]
+        );
+        assert_eq!(kinds("", "a"), vec![line(DiffKind::Added, "a")]);
+        assert_eq!(kinds("a", ""), vec![line(DiffKind::Removed, "a")]);
+    }
+
+    #[test]
+    fn moved_line() {
+        assert_eq!(
+            kinds("a\nb\nc", "b\nc\na"),
+            vec![
+                line(DiffKind::Removed, "a"),
+                line(DiffKind::Unchanged, "b"),
+                line(DiffKind::Unchanged, "c"),
+                line(DiffKind::Added, "a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn too_many_changed_lines() {
+        let old = "old\n".repeat(config::MAX_WIKI_DIFF_LINES + 1);
+        let new = "new\n".repeat(config::MAX_WIKI_DIFF_LINES + 1);
+        assert!(diff_lines(&old, &new).is_none());
+    }
+
+    #[test]
+    fn long_common_text_is_not_counted() {
+        let same = "same\n".repeat(config::MAX_WIKI_DIFF_LINES * 2);
+        let lines = diff_lines(&format!("{same}old\n{same}"), &format!("{same}new\n{same}"))
+            .expect("Only one line changed");
+        assert_eq!(lines.len(), config::MAX_WIKI_DIFF_LINES * 4 + 2);
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|l| l.kind != DiffKind::Unchanged)
+                .count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use tracing::{error, info};
+
+use crate::{
+    error::RtwalkError,
+    gql::{forums, posts},
+    state::State,
+};
+
+const FORUM_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60); // 1 hour
+const RANK_REFRESH_INTERVAL: Duration = Duration::from_secs(60); // 1 minute
+
+/// Starts the periodic background jobs.
+pub fn spawn(state: State) {
+    let purge_state = state.clone();
+    tokio::spawn(async move {
+        let state = purge_state;
+        let mut interval = tokio::time::interval(FORUM_PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = purge_deleted_forums(&state).await {
+                error!("Failed to purge deleted forums: {:?}", e);
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RANK_REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = posts::refresh_ranks(&state).await {
+                error!("Failed to refresh post ranks: {:?}", e);
+            }
+        }
+    });
+}
+
+async fn purge_deleted_forums(state: &State) -> Result<(), RtwalkError> {
+    for forum in forums::fetch_purgeable_forums(state).await? {
+        forums::purge_forum(state, &forum).await?;
+        info!("Purged forum {}", forum.id);
+    }
+
+    Ok(())
+}
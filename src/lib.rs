@@ -1,6 +1,7 @@
 mod config;
 mod error;
 pub mod gql;
+mod mail;
 mod models;
 pub mod state;
 mod template;
@@ -0,0 +1,131 @@
+use std::env;
+
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use sailfish::TemplateSimple;
+use surrealdb::RecordId;
+use tracing::error;
+
+use crate::{
+    error::RtwalkError,
+    models::{user::DBUserPreferences, Key},
+    state::State,
+    template::Notification,
+};
+
+pub async fn send_mail(
+    username: &str,
+    email: &str,
+    subject: &str,
+    body: String,
+) -> Result<(), RtwalkError> {
+    let email_message = Message::builder()
+        .from(
+            format!(
+                "{} <{}>",
+                env::var("SMTP_FROM_NAME").expect("SMTP_FROM_NAME must be set"),
+                env::var("SMTP_FROM").expect("SMTP_FROM must be set")
+            )
+            .parse()
+            .unwrap(),
+        )
+        .to(format!("{username} <{email}>").parse().unwrap())
+        .subject(subject)
+        .header(ContentType::TEXT_HTML)
+        .body(body)
+        .unwrap();
+
+    let creds = Credentials::new(
+        env::var("SMTP_USERNAME").expect("SMTP_USERNAME must be set"),
+        env::var("SMTP_PASSWORD").expect("SMTP_PASSWORD must be set"),
+    );
+
+    let mailer: AsyncSmtpTransport<Tokio1Executor> =
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(
+            &env::var("SMTP_RELAY").expect("SMTP_RELAY must be set"),
+        )
+        .unwrap()
+        .credentials(creds)
+        .port(
+            env::var("SMTP_PORT")
+                .expect("SMTP_PORT must be set")
+                .parse::<u16>()
+                .expect("SMTP_PORT must be u16"),
+        )
+        .build();
+
+    mailer.send(email_message).await?;
+
+    Ok(())
+}
+
+/// Which preference toggle a notification falls under.
+#[derive(Clone, Copy)]
+pub enum NotificationKind {
+    PostComment,
+    Moderation,
+}
+
+/// Emails a notification to the user if their preferences allow it.
+/// Bots never get emails. Runs in the background, failures are only logged.
+pub fn notify(
+    state: &State,
+    user: Key,
+    kind: NotificationKind,
+    heading: String,
+    message: String,
+    link: String,
+) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = try_notify(&state, user, kind, &heading, &message, &link).await {
+            error!("Failed to send notification: {:?}", e);
+        }
+    });
+}
+
+async fn try_notify(
+    state: &State,
+    user: Key,
+    kind: NotificationKind,
+    heading: &str,
+    message: &str,
+    link: &str,
+) -> Result<(), RtwalkError> {
+    let user_id = RecordId::from_table_key("user", user.0.clone());
+    let mut res = state
+        .db
+        .query("SELECT * FROM type::thing('user_preferences', $key)")
+        .query("SELECT email, user.username AS username, user.bot AS bot FROM user_secret WHERE user = $user")
+        .bind(("key", user.0))
+        .bind(("user", user_id))
+        .await?;
+
+    let preferences: Option<DBUserPreferences> = res.take(0)?;
+    let email: Option<String> = res.take((1, "email"))?;
+    let username: Option<String> = res.take((1, "username"))?;
+    let bot: Option<bool> = res.take((1, "bot"))?;
+
+    // Missing preferences means defaults, which have every email on.
+    let enabled = preferences.is_none_or(|p| match kind {
+        NotificationKind::PostComment => p.email_post_comments,
+        NotificationKind::Moderation => p.email_moderation,
+    });
+
+    if let (true, Some(email), Some(username), Some(false)) = (enabled, email, username, bot) {
+        let template = Notification {
+            username: &username,
+            heading,
+            message,
+            link,
+            site_name: state.site_name,
+        }
+        .render_once()
+        .expect("Can't fail");
+
+        send_mail(&username, &email, heading, template).await?;
+    }
+
+    Ok(())
+}
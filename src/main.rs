@@ -1,10 +1,11 @@
-use std::{env, error::Error, sync::Arc};
+use std::{env, error::Error, net::SocketAddr, sync::Arc};
 
 use crate::gql::ApiInfo;
 
-use async_graphql::{http::GraphiQLSource, Schema};
+use async_graphql::{dataloader::DataLoader, http::GraphiQLSource, Schema};
 use async_graphql_axum::{GraphQLRequest, GraphQLResponse, GraphQLSubscription};
 use axum::{
+    extract::ConnectInfo,
     http::{header::CONTENT_TYPE, Method},
     response::{Html, IntoResponse},
     routing::get,
@@ -15,11 +16,14 @@ use cliparser::{
     types::{Argument, ArgumentOccurrence, ArgumentValueType, CliSpec, CliSpecMetaInfo},
 };
 use dotenvy::dotenv;
-use gql::{MergedMutationRoot, MergedQueryRoot, Subscription};
+use gql::{
+    loaders::{EmojiLoader, PostForumLoader, VoteLoader},
+    MergedMutationRoot, MergedQueryRoot, Subscription,
+};
 use opendal::Operator;
 use rustis::client::Client;
 use rusty_paseto::generic::{Local, PasetoSymmetricKey, V4};
-use state::Auth;
+use state::{Auth, ClientIp};
 use surrealdb::{engine::remote::ws::Ws, opt::auth::Root, Surreal};
 use tokio::net::TcpListener;
 use tower_cookies::{CookieManagerLayer, Cookies, Key};
@@ -29,6 +33,8 @@ use tracing::info;
 pub(crate) mod config;
 pub(crate) mod error;
 mod gql;
+mod jobs;
+pub(crate) mod mail;
 pub(crate) mod models;
 pub(crate) mod state;
 pub(crate) mod template;
@@ -45,11 +51,18 @@ async fn graphiql() -> impl IntoResponse {
 
 async fn gql(
     schema: Extension<Schema<MergedQueryRoot, MergedMutationRoot, Subscription>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     cookies: Cookies,
     request: GraphQLRequest,
 ) -> GraphQLResponse {
     schema
-        .execute(request.into_inner().data(cookies).data(Auth::default()))
+        .execute(
+            request
+                .into_inner()
+                .data(cookies)
+                .data(Auth::default())
+                .data(ClientIp(addr.ip())),
+        )
         .await
         .into()
 }
@@ -106,12 +119,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let opendal_service_builder = opendal::services::Fs::default().root("data/");
 
-    let schema = Schema::build(
-        MergedQueryRoot::default(),
-        MergedMutationRoot::default(),
-        Subscription,
-    )
-    .data(state::State {
+    let state = state::State {
         inner: Arc::new(state::InnerState {
             site_name: "DreamH",
             info: ApiInfo {
@@ -130,7 +138,24 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 cookies_key[..32].as_bytes(),
             )),
         }),
-    })
+    };
+
+    jobs::spawn(state.clone());
+
+    let schema = Schema::build(
+        MergedQueryRoot::default(),
+        MergedMutationRoot::default(),
+        Subscription,
+    )
+    // Batch per post and per comment lookups of a listing. Without a cache, so nothing
+    // outlives the batch it was loaded for.
+    .data(DataLoader::new(EmojiLoader(state.clone()), tokio::spawn))
+    .data(DataLoader::new(
+        PostForumLoader(state.clone()),
+        tokio::spawn,
+    ))
+    .data(DataLoader::new(VoteLoader(state.clone()), tokio::spawn))
+    .data(state)
     .finish();
 
     let app = Router::new()
@@ -160,7 +185,11 @@ async fn main() -> Result<(), Box<dyn Error>> {
     drop(spec);
     drop(res);
 
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -0,0 +1,118 @@
+use std::time::SystemTime;
+
+use async_graphql::{Enum, InputObject, SimpleObject};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use super::Key;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, Default)]
+pub enum AutomodTarget {
+    #[default]
+    All,
+    Posts,
+    Comments,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum AutomodAction {
+    /// Hides the content from everyone but its author and moderators.
+    Remove,
+    /// Hides the content until a moderator approves it.
+    Hold,
+    /// Sets `flairId` on the post. Does nothing for comments.
+    Flair,
+    /// Locks the post, or the post a comment is on.
+    Lock,
+    /// Comments `reply` on the post as the forum's AutoModerator bot.
+    Reply,
+}
+
+/// Matches when any keyword is in the text, case insensitive, or the regex matches.
+#[derive(SimpleObject, InputObject, Debug, Serialize, Deserialize, Clone, Default)]
+#[graphql(input_name = "TextConditionInput")]
+pub struct TextCondition {
+    #[graphql(validator(max_items = 100, list, min_length = 1, max_length = 100))]
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[graphql(validator(min_length = 1, max_length = 500))]
+    pub regex: Option<String>,
+}
+
+/// Every condition that's set has to match for the rule to apply.
+#[derive(SimpleObject, InputObject, Debug, Serialize, Deserialize, Clone)]
+#[graphql(input_name = "AutomodRuleInput")]
+pub struct AutomodRule {
+    /// Shown in the mod log.
+    #[graphql(validator(min_length = 1, max_length = 100))]
+    pub name: String,
+    #[serde(default)]
+    #[graphql(default)]
+    pub applies_to: AutomodTarget,
+    /// Only checked for posts.
+    pub title: Option<TextCondition>,
+    pub content: Option<TextCondition>,
+    /// Matches posts with any of these tags. Never matches comments.
+    #[graphql(validator(max_items = 20, list, max_length = 20))]
+    #[serde(default)]
+    #[graphql(default)]
+    pub tags: Vec<String>,
+    /// Matches authors whose account is younger than this many seconds.
+    pub account_age_below: Option<u64>,
+    /// Matches authors with less karma than this.
+    pub karma_below: Option<i64>,
+    /// Matches uploads of these MIME types, `image/` style prefixes match the whole group.
+    #[graphql(validator(max_items = 50, list, min_length = 1, max_length = 127))]
+    #[serde(default)]
+    #[graphql(default)]
+    pub attachment_types: Vec<String>,
+    pub action: AutomodAction,
+    /// For the `FLAIR` action.
+    pub flair_id: Option<String>,
+    /// For the `REPLY` action.
+    #[graphql(validator(min_length = 1, max_length = 1_000))]
+    pub reply: Option<String>,
+}
+
+/// A forum's AutoModerator config, keyed by the forum it belongs to.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DBAutomod {
+    pub id: RecordId,
+    /// Bot account replies are posted as, it has to be one of the forum's moderators.
+    pub bot: Option<RecordId>,
+    pub rules: Vec<AutomodRule>,
+    pub updated_by: RecordId,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl DBAutomod {
+    pub fn new(forum: Key, bot: Option<Key>, rules: Vec<AutomodRule>, updated_by: Key) -> Self {
+        Self {
+            id: RecordId::from_table_key("automod", forum.0),
+            bot: bot.map(|b| RecordId::from_table_key("user", b.0)),
+            rules,
+            updated_by: RecordId::from_table_key("user", updated_by.0),
+            updated_at: SystemTime::now().into(),
+        }
+    }
+}
+
+#[derive(SimpleObject, Debug)]
+pub struct Automod {
+    pub bot_id: Option<Key>,
+    pub rules: Vec<AutomodRule>,
+    pub updated_by_id: Key,
+    pub updated_at: i64,
+}
+
+impl From<DBAutomod> for Automod {
+    fn from(value: DBAutomod) -> Self {
+        Self {
+            bot_id: value.bot.map(|b| Key(b.key().to_owned())),
+            rules: value.rules,
+            updated_by_id: Key(value.updated_by.key().to_owned()),
+            updated_at: value.updated_at.timestamp(),
+        }
+    }
+}
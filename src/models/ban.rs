@@ -0,0 +1,79 @@
+use std::time::SystemTime;
+
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+use cuid2::cuid;
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use super::Key;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DBForumBan {
+    pub id: RecordId,
+    pub user: RecordId,
+    pub forum: RecordId,
+    pub reason: Option<String>,
+    /// `None` for permanent bans.
+    pub expires_at: Option<DateTime<Utc>>,
+    pub issued_by: RecordId,
+    pub created_at: DateTime<Utc>,
+    /// Modmail thread the ban was discussed in.
+    #[serde(default)]
+    pub modmail_thread: Option<RecordId>,
+}
+
+impl DBForumBan {
+    pub fn new(
+        user: Key,
+        forum: Key,
+        reason: Option<String>,
+        expires_at: Option<DateTime<Utc>>,
+        issued_by: Key,
+        modmail_thread: Option<RecordId>,
+    ) -> Self {
+        Self {
+            id: RecordId::from_table_key("forum_ban", cuid()),
+            user: RecordId::from_table_key("user", user.0),
+            forum: RecordId::from_table_key("forum", forum.0),
+            reason,
+            expires_at,
+            issued_by: RecordId::from_table_key("user", issued_by.0),
+            created_at: SystemTime::now().into(),
+            modmail_thread,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.expires_at.is_none_or(|e| e > Utc::now())
+    }
+}
+
+#[derive(SimpleObject, Debug)]
+pub struct ForumBan {
+    pub id: Key,
+    pub user_id: Key,
+    pub forum_id: Key,
+    pub reason: Option<String>,
+    pub expires_at: Option<i64>,
+    pub issued_by_id: Key,
+    pub created_at: i64,
+    pub active: bool,
+    pub modmail_thread_id: Option<Key>,
+}
+
+impl From<DBForumBan> for ForumBan {
+    fn from(value: DBForumBan) -> Self {
+        Self {
+            active: value.is_active(),
+            id: Key(value.id.key().to_owned()),
+            user_id: Key(value.user.key().to_owned()),
+            forum_id: Key(value.forum.key().to_owned()),
+            reason: value.reason,
+            expires_at: value.expires_at.map(|e| e.timestamp()),
+            issued_by_id: Key(value.issued_by.key().to_owned()),
+            created_at: value.created_at.timestamp(),
+            modmail_thread_id: value.modmail_thread.map(|t| Key(t.key().to_owned())),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::time::SystemTime;
+
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+use cuid2::cuid;
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use super::Key;
+
+/// Admin defined group of forums on the directory.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DBCategory {
+    pub id: RecordId,
+    pub name: String,
+    pub description: Option<String>,
+    /// Categories are listed by position, then name.
+    pub position: u32,
+    pub created_by: RecordId,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DBCategory {
+    pub fn new(name: String, description: Option<String>, position: u32, created_by: Key) -> Self {
+        Self {
+            id: RecordId::from_table_key("category", cuid()),
+            name,
+            description,
+            position,
+            created_by: RecordId::from_table_key("user", created_by.0),
+            created_at: SystemTime::now().into(),
+        }
+    }
+}
+
+#[derive(SimpleObject, Debug)]
+pub struct Category {
+    pub id: Key,
+    pub name: String,
+    pub description: Option<String>,
+    pub position: u32,
+    pub created_at: i64,
+}
+
+impl From<DBCategory> for Category {
+    fn from(value: DBCategory) -> Self {
+        Self {
+            id: Key(value.id.key().to_owned()),
+            name: value.name,
+            description: value.description,
+            position: value.position,
+            created_at: value.created_at.timestamp(),
+        }
+    }
+}
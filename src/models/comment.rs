@@ -1,3 +1,5 @@
+use std::time::SystemTime;
+
 use async_graphql::SimpleObject;
 use chrono::{DateTime, Utc};
 use cuid2::cuid;
@@ -15,11 +17,19 @@ pub struct DBComment {
     pub attachments: Vec<File>,
     pub created_at: DateTime<Utc>,
     pub edited_at: DateTime<Utc>,
+    /// Removed and held comments are only shown to their author and the forum's moderators.
+    #[serde(default)]
+    pub removed: bool,
+    #[serde(default)]
+    pub removal_reason: Option<String>,
+    /// Waiting for a moderator to approve it.
+    #[serde(default)]
+    pub held: bool,
 }
 
 impl DBComment {
     pub fn new(content: Option<String>, attachments: Vec<File>, commenter: Key, post: Key) -> Self {
-        let created_at = DateTime::default();
+        let created_at: DateTime<Utc> = SystemTime::now().into();
         let edited_at = created_at.clone();
         Self {
             id: RecordId::from_table_key("comment", cuid()),
@@ -29,11 +39,20 @@ impl DBComment {
             attachments,
             created_at,
             edited_at,
+            removed: false,
+            removal_reason: None,
+            held: false,
         }
     }
+
+    /// Whether the comment counts towards the forum's `comment_count`.
+    pub fn is_counted(&self) -> bool {
+        !self.removed && !self.held
+    }
 }
 
 #[derive(SimpleObject, Debug, Serialize, Deserialize, Clone)]
+#[graphql(complex)]
 pub struct Comment {
     pub id: Key,
     pub commenter_id: Key,
@@ -42,6 +61,9 @@ pub struct Comment {
     pub attachments: Vec<File>,
     pub created_at: i64,
     pub edited_at: i64,
+    pub removed: bool,
+    pub removal_reason: Option<String>,
+    pub held: bool,
 }
 
 impl From<DBComment> for Comment {
@@ -54,6 +76,9 @@ impl From<DBComment> for Comment {
             attachments: value.attachments,
             created_at: value.created_at.timestamp(),
             edited_at: value.edited_at.timestamp(),
+            removed: value.removed,
+            removal_reason: value.removal_reason,
+            held: value.held,
         }
     }
 }
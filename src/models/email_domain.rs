@@ -0,0 +1,55 @@
+use std::time::SystemTime;
+
+use async_graphql::{Enum, SimpleObject};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use super::Key;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum EmailDomainListKind {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DBEmailDomain {
+    pub id: RecordId,
+    pub domain: String,
+    pub kind: EmailDomainListKind,
+    pub added_by: RecordId,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DBEmailDomain {
+    pub fn new(domain: String, kind: EmailDomainListKind, added_by: Key) -> Self {
+        Self {
+            // Domain is the key so the same domain can't end up on both lists.
+            id: RecordId::from_table_key("email_domain", domain.clone()),
+            domain,
+            kind,
+            added_by: RecordId::from_table_key("user", added_by.0),
+            created_at: SystemTime::now().into(),
+        }
+    }
+}
+
+#[derive(SimpleObject, Debug)]
+pub struct EmailDomain {
+    pub domain: String,
+    pub kind: EmailDomainListKind,
+    pub added_by_id: Key,
+    pub created_at: i64,
+}
+
+impl From<DBEmailDomain> for EmailDomain {
+    fn from(value: DBEmailDomain) -> Self {
+        Self {
+            domain: value.domain,
+            kind: value.kind,
+            added_by_id: Key(value.added_by.key().to_owned()),
+            created_at: value.created_at.timestamp(),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::SystemTime;
+
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+use cuid2::cuid;
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use super::{file::File, Key};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DBEmoji {
+    pub id: RecordId,
+    pub forum: RecordId,
+    /// Used as `:name:` in posts and comments, unique within the forum.
+    pub name: String,
+    pub image: File,
+    pub created_by: RecordId,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DBEmoji {
+    pub fn new(forum: RecordId, name: String, image: File, created_by: Key) -> Self {
+        Self {
+            id: RecordId::from_table_key("emoji", cuid()),
+            forum,
+            name,
+            image,
+            created_by: RecordId::from_table_key("user", created_by.0),
+            created_at: SystemTime::now().into(),
+        }
+    }
+}
+
+#[derive(SimpleObject, Debug, Clone)]
+pub struct Emoji {
+    pub id: Key,
+    pub forum_id: Key,
+    pub name: String,
+    pub image: File,
+    pub created_by_id: Key,
+    pub created_at: i64,
+}
+
+impl From<DBEmoji> for Emoji {
+    fn from(value: DBEmoji) -> Self {
+        Self {
+            id: Key(value.id.key().to_owned()),
+            forum_id: Key(value.forum.key().to_owned()),
+            name: value.name,
+            image: value.image,
+            created_by_id: Key(value.created_by.key().to_owned()),
+            created_at: value.created_at.timestamp(),
+        }
+    }
+}
+
+/// A piece of post or comment content. Clients render `emoji` in place of the text when
+/// it's set.
+#[derive(SimpleObject, Debug)]
+pub struct ContentToken {
+    /// Plain text, or the `:name:` shortcode of an emoji.
+    pub text: String,
+    pub emoji: Option<Emoji>,
+}
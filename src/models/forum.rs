@@ -1,12 +1,12 @@
 use std::time::SystemTime;
 
-use async_graphql::SimpleObject;
+use async_graphql::{Enum, InputObject, MaybeUndefined, SimpleObject};
 use chrono::{DateTime, Utc};
 use cuid2::cuid;
 use serde::{Deserialize, Serialize};
 use surrealdb::RecordId;
 
-use super::{file::File, Key};
+use super::{file::File, wiki::WikiEditors, Key};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DBForum {
@@ -19,9 +19,39 @@ pub struct DBForum {
     pub banner: Option<File>,
     pub created_at: DateTime<Utc>,
     pub locked: bool,
+    #[serde(default)]
+    pub lock_reason: Option<String>,
+    /// Set while the forum waits to be purged, see `deleteForum`.
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub member_count: u64,
+    #[serde(default)]
+    pub post_count: u64,
+    #[serde(default)]
+    pub comment_count: u64,
+    #[serde(default)]
+    pub settings: ForumSettings,
+    /// In the order they're shown.
+    #[serde(default)]
+    pub rules: Vec<ForumRule>,
+    #[serde(default)]
+    pub flairs: Vec<Flair>,
+    #[serde(default)]
+    pub parent: Option<RecordId>,
+    #[serde(default)]
+    pub category: Option<RecordId>,
 }
 
 impl DBForum {
+    pub fn rule(&self, id: &str) -> Option<&ForumRule> {
+        self.rules.iter().find(|r| r.id == id)
+    }
+
+    pub fn flair(&self, id: &str) -> Option<&Flair> {
+        self.flairs.iter().find(|f| f.id == id)
+    }
+
     pub fn new(name: &str, owner: Key) -> Self {
         Self {
             id: RecordId::from_table_key("forum", cuid()),
@@ -33,11 +63,190 @@ impl DBForum {
             banner: None,
             created_at: SystemTime::now().into(),
             locked: false,
+            lock_reason: None,
+            deleted_at: None,
+            member_count: 0,
+            post_count: 0,
+            comment_count: 0,
+            settings: ForumSettings::default(),
+            rules: vec![],
+            flairs: vec![],
+            parent: None,
+            category: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, Default)]
+pub enum ForumVisibility {
+    /// Anyone can read and join.
+    #[default]
+    Public,
+    /// Anyone can read, joining needs approval and only members can post.
+    Restricted,
+    /// Only members can read, joining needs approval.
+    Private,
+}
+
+/// Rules the forum's moderators can configure.
+#[derive(SimpleObject, Debug, Serialize, Deserialize, Clone)]
+pub struct ForumSettings {
+    /// Only members can post and comment. Always the case unless the forum is public.
+    #[serde(default)]
+    pub require_membership: bool,
+    #[serde(default)]
+    pub visibility: ForumVisibility,
+    /// Posts must have one of the forum's flairs.
+    #[serde(default)]
+    pub require_flair: bool,
+    /// Seconds since signup before an account can post and comment.
+    #[serde(default)]
+    pub min_account_age: u64,
+    #[serde(default)]
+    pub min_karma: Option<i64>,
+    /// Bot accounts never have a verified email.
+    #[serde(default)]
+    pub require_verified_email: bool,
+    #[serde(default = "default_true")]
+    pub allow_attachments: bool,
+    #[serde(default)]
+    pub max_attachments: Option<u32>,
+    /// Empty allows every type.
+    #[serde(default)]
+    pub allowed_mime_types: Vec<String>,
+    /// Posts per user per hour.
+    #[serde(default)]
+    pub post_rate_limit: Option<u32>,
+    /// Comments per user per hour.
+    #[serde(default)]
+    pub comment_rate_limit: Option<u32>,
+    /// Moderators of the parent forum moderate this one too, with the same permissions.
+    #[serde(default = "default_true")]
+    pub inherit_moderators: bool,
+    /// Who can edit the forum's wiki pages.
+    #[serde(default)]
+    pub wiki_editors: WikiEditors,
+    /// Everyone who can read the forum sees the edit history of posts and comments, not
+    /// just moderators.
+    #[serde(default)]
+    pub public_edit_history: bool,
+}
+
+/// Changes to [`ForumSettings`], only the given settings are replaced.
+#[derive(InputObject, Debug, Default)]
+pub struct ForumSettingsInput {
+    pub require_membership: Option<bool>,
+    pub visibility: Option<ForumVisibility>,
+    pub require_flair: Option<bool>,
+    /// Seconds since signup before an account can post and comment.
+    pub min_account_age: Option<u64>,
+    pub min_karma: MaybeUndefined<i64>,
+    pub require_verified_email: Option<bool>,
+    pub allow_attachments: Option<bool>,
+    #[graphql(validator(minimum = 1))]
+    pub max_attachments: MaybeUndefined<u32>,
+    #[graphql(validator(max_items = 50, list, min_length = 3, max_length = 127))]
+    pub allowed_mime_types: Option<Vec<String>>,
+    /// Posts per user per hour.
+    #[graphql(validator(minimum = 1))]
+    pub post_rate_limit: MaybeUndefined<u32>,
+    /// Comments per user per hour.
+    #[graphql(validator(minimum = 1))]
+    pub comment_rate_limit: MaybeUndefined<u32>,
+    pub inherit_moderators: Option<bool>,
+    pub wiki_editors: Option<WikiEditors>,
+    pub public_edit_history: Option<bool>,
+}
+
+impl ForumSettingsInput {
+    pub fn apply(self, settings: &mut ForumSettings) {
+        if let Some(require_membership) = self.require_membership {
+            settings.require_membership = require_membership;
+        }
+        if let Some(visibility) = self.visibility {
+            settings.visibility = visibility;
+        }
+        if let Some(require_flair) = self.require_flair {
+            settings.require_flair = require_flair;
+        }
+        if let Some(min_account_age) = self.min_account_age {
+            settings.min_account_age = min_account_age;
+        }
+        if !self.min_karma.is_undefined() {
+            settings.min_karma = self.min_karma.take();
+        }
+        if let Some(require_verified_email) = self.require_verified_email {
+            settings.require_verified_email = require_verified_email;
+        }
+        if let Some(allow_attachments) = self.allow_attachments {
+            settings.allow_attachments = allow_attachments;
+        }
+        if !self.max_attachments.is_undefined() {
+            settings.max_attachments = self.max_attachments.take();
+        }
+        if let Some(allowed_mime_types) = self.allowed_mime_types {
+            settings.allowed_mime_types = allowed_mime_types;
+        }
+        if !self.post_rate_limit.is_undefined() {
+            settings.post_rate_limit = self.post_rate_limit.take();
+        }
+        if !self.comment_rate_limit.is_undefined() {
+            settings.comment_rate_limit = self.comment_rate_limit.take();
+        }
+        if let Some(inherit_moderators) = self.inherit_moderators {
+            settings.inherit_moderators = inherit_moderators;
+        }
+        if let Some(wiki_editors) = self.wiki_editors {
+            settings.wiki_editors = wiki_editors;
+        }
+        if let Some(public_edit_history) = self.public_edit_history {
+            settings.public_edit_history = public_edit_history;
         }
     }
 }
 
+fn default_true() -> bool {
+    true
+}
+
+impl Default for ForumSettings {
+    fn default() -> Self {
+        Self {
+            require_membership: false,
+            visibility: ForumVisibility::default(),
+            require_flair: false,
+            min_account_age: 0,
+            min_karma: None,
+            require_verified_email: false,
+            allow_attachments: true,
+            max_attachments: None,
+            allowed_mime_types: vec![],
+            post_rate_limit: None,
+            comment_rate_limit: None,
+            inherit_moderators: true,
+            wiki_editors: WikiEditors::default(),
+            public_edit_history: false,
+        }
+    }
+}
+
+#[derive(SimpleObject, Debug, Serialize, Deserialize, Clone)]
+pub struct ForumRule {
+    pub id: String,
+    pub title: String,
+    pub description: Option<String>,
+}
+
+#[derive(SimpleObject, Debug, Serialize, Deserialize, Clone)]
+pub struct Flair {
+    pub id: String,
+    pub text: String,
+    /// Hex colour, `#rrggbb`.
+    pub color: String,
+}
+
 #[derive(SimpleObject, Debug)]
+#[graphql(complex)]
 pub struct Forum {
     pub id: Key,
     pub owner_id: Key,
@@ -48,6 +257,20 @@ pub struct Forum {
     pub banner: Option<File>,
     pub created_at: i64,
     pub locked: bool,
+    pub lock_reason: Option<String>,
+    pub deleted_at: Option<i64>,
+    pub member_count: u64,
+    /// Resolved only for viewers who can read the forum, like `rules`, `flairs` and
+    /// `parent_id`.
+    #[graphql(skip)]
+    pub settings: ForumSettings,
+    #[graphql(skip)]
+    pub rules: Vec<ForumRule>,
+    #[graphql(skip)]
+    pub flairs: Vec<Flair>,
+    #[graphql(skip)]
+    pub parent_id: Option<Key>,
+    pub category_id: Option<Key>,
 }
 
 impl From<DBForum> for Forum {
@@ -62,6 +285,42 @@ impl From<DBForum> for Forum {
             banner: value.banner,
             created_at: value.created_at.timestamp(),
             locked: value.locked,
+            lock_reason: value.lock_reason,
+            deleted_at: value.deleted_at.map(|d| d.timestamp()),
+            member_count: value.member_count,
+            settings: value.settings,
+            rules: value.rules,
+            flairs: value.flairs,
+            parent_id: value.parent.map(|p| Key(p.key().to_owned())),
+            category_id: value.category.map(|c| Key(c.key().to_owned())),
+        }
+    }
+}
+
+/// Pending ownership transfer, kept in redis until it's accepted or expires.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DBForumTransfer {
+    pub forum: String,
+    pub from: String,
+    pub to: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(SimpleObject, Debug)]
+pub struct ForumTransfer {
+    pub forum_id: Key,
+    pub from_id: Key,
+    pub to_id: Key,
+    pub expires_at: i64,
+}
+
+impl From<DBForumTransfer> for ForumTransfer {
+    fn from(value: DBForumTransfer) -> Self {
+        Self {
+            forum_id: value.forum.into(),
+            from_id: value.from.into(),
+            to_id: value.to.into(),
+            expires_at: value.expires_at.timestamp(),
         }
     }
 }
@@ -0,0 +1,90 @@
+use std::time::SystemTime;
+
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use super::Key;
+
+/// `user->member_of->forum` relation.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DBMember {
+    pub id: RecordId,
+    #[serde(rename = "in")]
+    pub user: RecordId,
+    #[serde(rename = "out")]
+    pub forum: RecordId,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DBMember {
+    pub fn new(user: Key, forum: Key) -> Self {
+        Self {
+            id: RecordId::from_table_key("member_of", cuid2::cuid()),
+            user: RecordId::from_table_key("user", user.0),
+            forum: RecordId::from_table_key("forum", forum.0),
+            created_at: SystemTime::now().into(),
+        }
+    }
+}
+
+#[derive(SimpleObject, Debug)]
+#[graphql(complex)]
+pub struct Member {
+    pub user_id: Key,
+    pub forum_id: Key,
+    pub joined_at: i64,
+}
+
+impl From<DBMember> for Member {
+    fn from(value: DBMember) -> Self {
+        Self {
+            user_id: Key(value.user.key().to_owned()),
+            forum_id: Key(value.forum.key().to_owned()),
+            joined_at: value.created_at.timestamp(),
+        }
+    }
+}
+
+/// Request to join a restricted or private forum, waiting on a moderator.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DBJoinRequest {
+    pub id: RecordId,
+    pub user: RecordId,
+    pub forum: RecordId,
+    pub message: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DBJoinRequest {
+    pub fn new(user: Key, forum: Key, message: Option<String>) -> Self {
+        Self {
+            id: RecordId::from_table_key("forum_join_request", cuid2::cuid()),
+            user: RecordId::from_table_key("user", user.0),
+            forum: RecordId::from_table_key("forum", forum.0),
+            message,
+            created_at: SystemTime::now().into(),
+        }
+    }
+}
+
+#[derive(SimpleObject, Debug)]
+#[graphql(complex)]
+pub struct JoinRequest {
+    pub user_id: Key,
+    pub forum_id: Key,
+    pub message: Option<String>,
+    pub created_at: i64,
+}
+
+impl From<DBJoinRequest> for JoinRequest {
+    fn from(value: DBJoinRequest) -> Self {
+        Self {
+            user_id: Key(value.user.key().to_owned()),
+            forum_id: Key(value.forum.key().to_owned()),
+            message: value.message,
+            created_at: value.created_at.timestamp(),
+        }
+    }
+}
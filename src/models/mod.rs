@@ -2,15 +2,29 @@ use std::ops::Deref;
 
 use async_graphql::*;
 use comment::Comment;
+use modmail::{ModmailMessage, ModmailThread};
 use post::Post;
 use serde::{Deserialize, Serialize};
 use surrealdb::RecordIdKey;
 
+pub mod automod;
+pub mod ban;
+pub mod category;
 pub mod comment;
+pub mod email_domain;
+pub mod emoji;
 pub mod file;
 pub mod forum;
+pub mod member;
+pub mod moderator;
+pub mod modlog;
+pub mod modmail;
 pub mod post;
+pub mod revision;
+pub mod stats;
 pub mod user;
+pub mod vote;
+pub mod wiki;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct Key(pub RecordIdKey);
@@ -57,8 +71,8 @@ pub struct PostCreateEvent {
 
 #[derive(SimpleObject, Deserialize, Serialize, Clone)]
 pub struct PostEditEvent {
-    pub original: Post,
-    pub new: Post,
+    pub original: Box<Post>,
+    pub new: Box<Post>,
 }
 
 #[derive(SimpleObject, Deserialize, Serialize, Clone)]
@@ -72,12 +86,84 @@ pub struct CommentEditEvent {
     pub new: Comment,
 }
 
+/// Sent for both locking and unlocking.
+#[derive(SimpleObject, Deserialize, Serialize, Clone)]
+pub struct ForumLockEvent {
+    pub forum_id: Key,
+    pub locked: bool,
+    pub reason: Option<String>,
+}
+
+/// Sent for both locking and unlocking.
+#[derive(SimpleObject, Deserialize, Serialize, Clone)]
+pub struct PostLockEvent {
+    pub post_id: Key,
+    pub forum_id: Key,
+    pub locked: bool,
+    pub reason: Option<String>,
+}
+
+#[derive(SimpleObject, Deserialize, Serialize, Clone)]
+pub struct PostDeleteEvent {
+    pub post_id: Key,
+    pub forum_id: Key,
+}
+
+/// Sent for both removing and restoring.
+#[derive(SimpleObject, Deserialize, Serialize, Clone)]
+pub struct PostRemoveEvent {
+    pub post_id: Key,
+    pub forum_id: Key,
+    pub removed: bool,
+    pub reason: Option<String>,
+}
+
+/// Sent for both pinning and unpinning.
+#[derive(SimpleObject, Deserialize, Serialize, Clone)]
+pub struct PostPinEvent {
+    pub post_id: Key,
+    pub forum_id: Key,
+    pub pinned: bool,
+}
+
+/// Throttled, sent at most once every few seconds per post with the latest counts.
+#[derive(SimpleObject, Deserialize, Serialize, Clone)]
+pub struct PostScoreEvent {
+    pub post_id: Key,
+    pub forum_id: Key,
+    pub score: i64,
+    pub upvotes: u64,
+    pub downvotes: u64,
+}
+
+/// Only sent to the thread's user and moderators who can manage modmail, internal notes
+/// only to the moderators.
+#[derive(SimpleObject, Deserialize, Serialize, Clone)]
+pub struct ModmailMessageEvent {
+    pub thread: ModmailThread,
+    pub message: ModmailMessage,
+}
+
+/// Sent when a thread is archived or unarchived.
+#[derive(SimpleObject, Deserialize, Serialize, Clone)]
+pub struct ModmailThreadEvent {
+    pub data: ModmailThread,
+}
+
 #[derive(Union, Deserialize, Serialize, Clone)]
 pub enum RtEventData {
     PostCreate(PostCreateEvent),
     PostEdit(PostEditEvent),
     CommentCreate(CommentCreateEvent),
     CommentEdit(CommentEditEvent),
+    ForumLock(ForumLockEvent),
+    PostLock(PostLockEvent),
+    PostDelete(PostDeleteEvent),
+    PostRemove(PostRemoveEvent),
+    PostScore(PostScoreEvent),
+    PostPin(PostPinEvent),
+    ModmailMessage(ModmailMessageEvent),
+    ModmailThread(ModmailThreadEvent),
 }
 
 #[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Enum)]
@@ -86,6 +172,14 @@ pub enum RtEventType {
     PostEdit,
     CommentCreate,
     CommentEdit,
+    ForumLock,
+    PostLock,
+    PostDelete,
+    PostRemove,
+    PostScore,
+    PostPin,
+    ModmailMessage,
+    ModmailThread,
 }
 
 #[derive(SimpleObject, Serialize, Deserialize, Clone)]
@@ -0,0 +1,82 @@
+use std::time::SystemTime;
+
+use async_graphql::{Enum, SimpleObject};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use super::Key;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum ModPermission {
+    ManagePosts,
+    ManageComments,
+    BanUsers,
+    EditForum,
+    ManageModerators,
+    ManageMembers,
+    /// Read and answer the forum's modmail.
+    ManageModmail,
+}
+
+impl ModPermission {
+    /// What the forum owner and admins have.
+    pub const ALL: [ModPermission; 7] = [
+        ModPermission::ManagePosts,
+        ModPermission::ManageComments,
+        ModPermission::BanUsers,
+        ModPermission::EditForum,
+        ModPermission::ManageModerators,
+        ModPermission::ManageMembers,
+        ModPermission::ManageModmail,
+    ];
+}
+
+/// `user->moderates->forum` relation.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DBModerator {
+    pub id: RecordId,
+    #[serde(rename = "in")]
+    pub user: RecordId,
+    #[serde(rename = "out")]
+    pub forum: RecordId,
+    pub added_by: RecordId,
+    #[serde(default)]
+    pub permissions: Vec<ModPermission>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DBModerator {
+    pub fn new(user: Key, forum: Key, added_by: Key, permissions: Vec<ModPermission>) -> Self {
+        Self {
+            id: RecordId::from_table_key("moderates", cuid2::cuid()),
+            user: RecordId::from_table_key("user", user.0),
+            forum: RecordId::from_table_key("forum", forum.0),
+            added_by: RecordId::from_table_key("user", added_by.0),
+            permissions,
+            created_at: SystemTime::now().into(),
+        }
+    }
+}
+
+#[derive(SimpleObject, Debug)]
+#[graphql(complex)]
+pub struct Moderator {
+    pub user_id: Key,
+    pub forum_id: Key,
+    pub added_by_id: Key,
+    pub permissions: Vec<ModPermission>,
+    pub created_at: i64,
+}
+
+impl From<DBModerator> for Moderator {
+    fn from(value: DBModerator) -> Self {
+        Self {
+            user_id: Key(value.user.key().to_owned()),
+            forum_id: Key(value.forum.key().to_owned()),
+            added_by_id: Key(value.added_by.key().to_owned()),
+            permissions: value.permissions,
+            created_at: value.created_at.timestamp(),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::time::SystemTime;
+
+use async_graphql::{Enum, SimpleObject};
+use chrono::{DateTime, Utc};
+use cuid2::cuid;
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use super::Key;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum ModAction {
+    AddModerator,
+    RemoveModerator,
+    UpdateModeratorPermissions,
+    Ban,
+    Unban,
+    TransferOwnership,
+    LockForum,
+    UnlockForum,
+    LockPost,
+    UnlockPost,
+    DeleteForum,
+    RestoreForum,
+    ApproveJoinRequest,
+    DenyJoinRequest,
+    UpdateAutomod,
+    AutomodRemove,
+    AutomodHold,
+    AutomodFlair,
+    AutomodLock,
+    AutomodReply,
+    ApprovePost,
+    ApproveComment,
+    SetForumParent,
+    DeleteWikiPage,
+    AddEmoji,
+    DeleteEmoji,
+    RemovePost,
+    PinPost,
+    UnpinPost,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DBModLogEntry {
+    pub id: RecordId,
+    pub forum: RecordId,
+    pub actor: RecordId,
+    pub action: ModAction,
+    /// User, post or comment the action was taken on.
+    pub target: Option<RecordId>,
+    pub reason: Option<String>,
+    /// Id of the forum rule the action was taken under.
+    #[serde(default)]
+    pub rule: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DBModLogEntry {
+    pub fn new(
+        forum: RecordId,
+        actor: Key,
+        action: ModAction,
+        target: Option<RecordId>,
+        reason: Option<String>,
+    ) -> Self {
+        Self {
+            id: RecordId::from_table_key("mod_log", cuid()),
+            forum,
+            actor: RecordId::from_table_key("user", actor.0),
+            action,
+            target,
+            reason,
+            rule: None,
+            created_at: SystemTime::now().into(),
+        }
+    }
+}
+
+#[derive(SimpleObject, Debug)]
+pub struct ModLogEntry {
+    pub id: Key,
+    pub forum_id: Key,
+    pub actor_id: Key,
+    pub action: ModAction,
+    /// Table of the target, `user`, `post` or `comment`.
+    pub target_type: Option<String>,
+    pub target_id: Option<Key>,
+    pub reason: Option<String>,
+    pub rule_id: Option<String>,
+    pub created_at: i64,
+}
+
+impl From<DBModLogEntry> for ModLogEntry {
+    fn from(value: DBModLogEntry) -> Self {
+        Self {
+            id: Key(value.id.key().to_owned()),
+            forum_id: Key(value.forum.key().to_owned()),
+            actor_id: Key(value.actor.key().to_owned()),
+            action: value.action,
+            target_type: value.target.as_ref().map(|t| t.table().to_string()),
+            target_id: value.target.map(|t| Key(t.key().to_owned())),
+            reason: value.reason,
+            rule_id: value.rule,
+            created_at: value.created_at.timestamp(),
+        }
+    }
+}
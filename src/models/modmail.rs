@@ -0,0 +1,111 @@
+use std::time::SystemTime;
+
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+use cuid2::cuid;
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use super::Key;
+
+/// Conversation between a user and a forum's moderator team.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DBModmailThread {
+    pub id: RecordId,
+    pub forum: RecordId,
+    /// User who started the thread, moderators reply as the team.
+    pub user: RecordId,
+    pub subject: String,
+    pub archived: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_message_at: DateTime<Utc>,
+}
+
+impl DBModmailThread {
+    pub fn new(forum: RecordId, user: Key, subject: String) -> Self {
+        let created_at: DateTime<Utc> = SystemTime::now().into();
+        Self {
+            id: RecordId::from_table_key("modmail_thread", cuid()),
+            forum,
+            user: RecordId::from_table_key("user", user.0),
+            subject,
+            archived: false,
+            created_at,
+            last_message_at: created_at,
+        }
+    }
+}
+
+#[derive(SimpleObject, Debug, Serialize, Deserialize, Clone)]
+#[graphql(complex)]
+pub struct ModmailThread {
+    pub id: Key,
+    pub forum_id: Key,
+    pub user_id: Key,
+    pub subject: String,
+    pub archived: bool,
+    pub created_at: i64,
+    pub last_message_at: i64,
+}
+
+impl From<DBModmailThread> for ModmailThread {
+    fn from(value: DBModmailThread) -> Self {
+        Self {
+            id: Key(value.id.key().to_owned()),
+            forum_id: Key(value.forum.key().to_owned()),
+            user_id: Key(value.user.key().to_owned()),
+            subject: value.subject,
+            archived: value.archived,
+            created_at: value.created_at.timestamp(),
+            last_message_at: value.last_message_at.timestamp(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DBModmailMessage {
+    pub id: RecordId,
+    pub thread: RecordId,
+    pub author: RecordId,
+    pub content: String,
+    /// Notes between moderators, never shown to the thread's user.
+    pub internal: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DBModmailMessage {
+    pub fn new(thread: RecordId, author: Key, content: String, internal: bool) -> Self {
+        Self {
+            id: RecordId::from_table_key("modmail_message", cuid()),
+            thread,
+            author: RecordId::from_table_key("user", author.0),
+            content,
+            internal,
+            created_at: SystemTime::now().into(),
+        }
+    }
+}
+
+#[derive(SimpleObject, Debug, Serialize, Deserialize, Clone)]
+#[graphql(complex)]
+pub struct ModmailMessage {
+    pub id: Key,
+    pub thread_id: Key,
+    pub author_id: Key,
+    pub content: String,
+    pub internal: bool,
+    pub created_at: i64,
+}
+
+impl From<DBModmailMessage> for ModmailMessage {
+    fn from(value: DBModmailMessage) -> Self {
+        Self {
+            id: Key(value.id.key().to_owned()),
+            thread_id: Key(value.thread.key().to_owned()),
+            author_id: Key(value.author.key().to_owned()),
+            content: value.content,
+            internal: value.internal,
+            created_at: value.created_at.timestamp(),
+        }
+    }
+}
@@ -1,12 +1,12 @@
 use std::time::SystemTime;
 
-use async_graphql::SimpleObject;
+use async_graphql::{Enum, SimpleObject};
 use chrono::{DateTime, Utc};
 use cuid2::cuid;
 use serde::{Deserialize, Serialize};
 use surrealdb::RecordId;
 
-use super::{file::File, Key};
+use super::{file::File, forum::Flair, Key};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DBPost {
@@ -21,6 +21,41 @@ pub struct DBPost {
     pub edited_at: DateTime<Utc>,
     pub pinned: bool,
     pub locked: bool,
+    #[serde(default)]
+    pub lock_reason: Option<String>,
+    #[serde(default)]
+    pub nsfw: bool,
+    /// Copy of the forum's flair, kept even if the forum removes it.
+    #[serde(default)]
+    pub flair: Option<Flair>,
+    /// Removed and held posts are only shown to their author and the forum's moderators.
+    #[serde(default)]
+    pub removed: bool,
+    #[serde(default)]
+    pub removal_reason: Option<String>,
+    /// Waiting for a moderator to approve it.
+    #[serde(default)]
+    pub held: bool,
+    /// Deleted by its author, only the comments are left.
+    #[serde(default)]
+    pub deleted: bool,
+    /// Upvotes minus downvotes, kept in sync with the `vote` relation.
+    #[serde(default)]
+    pub score: i64,
+    #[serde(default)]
+    pub upvotes: u64,
+    #[serde(default)]
+    pub downvotes: u64,
+    /// Precomputed orderings, refreshed by a background job while `ranks_stale` is set
+    /// and for posts young enough to be rising.
+    #[serde(default)]
+    pub hot_rank: f64,
+    #[serde(default)]
+    pub controversial_rank: f64,
+    #[serde(default)]
+    pub rising_rank: f64,
+    #[serde(default)]
+    pub ranks_stale: bool,
 }
 
 impl DBPost {
@@ -35,7 +70,7 @@ impl DBPost {
         let created_at: DateTime<Utc> = SystemTime::now().into();
         let edited_at = created_at.clone();
         Self {
-            id: RecordId::from_table_key("forum", cuid()),
+            id: RecordId::from_table_key("post", cuid()),
             poster: RecordId::from_table_key("user", poster.0),
             forum: RecordId::from_table_key("forum", forum.0),
             title,
@@ -46,11 +81,31 @@ impl DBPost {
             edited_at,
             pinned: false,
             locked: false,
+            lock_reason: None,
+            nsfw: false,
+            flair: None,
+            removed: false,
+            removal_reason: None,
+            held: false,
+            deleted: false,
+            score: 0,
+            upvotes: 0,
+            downvotes: 0,
+            hot_rank: 0.0,
+            controversial_rank: 0.0,
+            rising_rank: 0.0,
+            ranks_stale: true,
         }
     }
+
+    /// Whether the post counts towards the forum's `post_count`.
+    pub fn is_counted(&self) -> bool {
+        !self.removed && !self.held && !self.deleted
+    }
 }
 
 #[derive(SimpleObject, Debug, Serialize, Deserialize, Clone)]
+#[graphql(complex)]
 pub struct Post {
     pub id: Key,
     pub poster_id: Key,
@@ -63,6 +118,16 @@ pub struct Post {
     pub edited_at: i64,
     pub pinned: bool,
     pub locked: bool,
+    pub lock_reason: Option<String>,
+    pub nsfw: bool,
+    pub flair: Option<Flair>,
+    pub removed: bool,
+    pub removal_reason: Option<String>,
+    pub held: bool,
+    pub deleted: bool,
+    pub score: i64,
+    pub upvotes: u64,
+    pub downvotes: u64,
 }
 
 impl From<DBPost> for Post {
@@ -79,6 +144,57 @@ impl From<DBPost> for Post {
             edited_at: value.edited_at.timestamp(),
             pinned: value.pinned,
             locked: value.locked,
+            lock_reason: value.lock_reason,
+            nsfw: value.nsfw,
+            flair: value.flair,
+            removed: value.removed,
+            removal_reason: value.removal_reason,
+            held: value.held,
+            deleted: value.deleted,
+            score: value.score,
+            upvotes: value.upvotes,
+            downvotes: value.downvotes,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, Default)]
+pub enum PostSort {
+    New,
+    /// The default, listings were oldest first before they could be sorted.
+    #[default]
+    Old,
+    /// Highest score within `topWindow`.
+    Top,
+    /// Score with a time decay, newer posts need fewer votes to rank.
+    Hot,
+    /// Many votes split evenly between up and down.
+    Controversial,
+    /// Recent posts gaining score fastest.
+    Rising,
+}
+
+/// How far back `TOP` looks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum, Default)]
+pub enum TopWindow {
+    Hour,
+    #[default]
+    Day,
+    Week,
+    Month,
+    Year,
+    All,
+}
+
+impl TopWindow {
+    pub fn seconds(self) -> Option<i64> {
+        match self {
+            TopWindow::Hour => Some(60 * 60),
+            TopWindow::Day => Some(24 * 60 * 60),
+            TopWindow::Week => Some(7 * 24 * 60 * 60),
+            TopWindow::Month => Some(30 * 24 * 60 * 60),
+            TopWindow::Year => Some(365 * 24 * 60 * 60),
+            TopWindow::All => None,
         }
     }
 }
@@ -0,0 +1,84 @@
+use std::time::SystemTime;
+
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+use cuid2::cuid;
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use super::{comment::DBComment, file::File, post::DBPost, Key};
+
+/// A post or comment as it was before an edit. `editor` and `created_at` are of the edit
+/// that replaced it, the current version is the post or comment itself.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DBRevision {
+    pub id: RecordId,
+    /// Post or comment that was edited.
+    pub target: RecordId,
+    pub forum: RecordId,
+    /// Always `None` for comments.
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub content: Option<String>,
+    /// Files removed by the edit are kept until the forum is purged.
+    pub attachments: Vec<File>,
+    pub editor: RecordId,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DBRevision {
+    pub fn of_post(post: &DBPost, editor: Key) -> Self {
+        Self {
+            id: RecordId::from_table_key("revision", cuid()),
+            target: post.id.clone(),
+            forum: post.forum.clone(),
+            title: Some(post.title.clone()),
+            tags: post.tags.clone(),
+            content: post.content.clone(),
+            attachments: post.attachments.clone(),
+            editor: RecordId::from_table_key("user", editor.0),
+            created_at: SystemTime::now().into(),
+        }
+    }
+
+    pub fn of_comment(comment: &DBComment, forum: RecordId, editor: Key) -> Self {
+        Self {
+            id: RecordId::from_table_key("revision", cuid()),
+            target: comment.id.clone(),
+            forum,
+            title: None,
+            tags: vec![],
+            content: comment.content.clone(),
+            attachments: comment.attachments.clone(),
+            editor: RecordId::from_table_key("user", editor.0),
+            created_at: SystemTime::now().into(),
+        }
+    }
+}
+
+#[derive(SimpleObject, Debug)]
+#[graphql(complex)]
+pub struct Revision {
+    pub id: Key,
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub content: Option<String>,
+    pub attachments: Vec<File>,
+    pub editor_id: Key,
+    /// When the edit that replaced this version was made.
+    pub edited_at: i64,
+}
+
+impl From<DBRevision> for Revision {
+    fn from(value: DBRevision) -> Self {
+        Self {
+            id: Key(value.id.key().to_owned()),
+            title: value.title,
+            tags: value.tags,
+            content: value.content,
+            attachments: value.attachments,
+            editor_id: Key(value.editor.key().to_owned()),
+            edited_at: value.created_at.timestamp(),
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use async_graphql::SimpleObject;
+
+use super::Key;
+
+#[derive(SimpleObject, Debug)]
+pub struct ForumStats {
+    pub member_count: u64,
+    pub post_count: u64,
+    pub comment_count: u64,
+    /// Oldest day first, days without activity included.
+    pub daily: Vec<DailyStats>,
+    /// Most posts and comments over the same days, most active first.
+    pub top_contributors: Vec<Contributor>,
+}
+
+#[derive(SimpleObject, Debug)]
+pub struct DailyStats {
+    /// UTC day, `YYYY-MM-DD`.
+    pub date: String,
+    pub posts: u64,
+    pub comments: u64,
+    /// Users who posted or commented that day.
+    pub active_posters: u64,
+}
+
+#[derive(SimpleObject, Debug)]
+#[graphql(complex)]
+pub struct Contributor {
+    pub user_id: Key,
+    /// Posts and comments combined.
+    pub submissions: u64,
+}
@@ -1,5 +1,7 @@
-use super::{file::File, Key};
-use async_graphql::SimpleObject;
+use std::time::SystemTime;
+
+use super::{file::File, post::PostSort, Key};
+use async_graphql::{InputObject, MaybeUndefined, SimpleObject};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use surrealdb::RecordId;
@@ -17,12 +19,17 @@ pub struct DBUser {
     pub admin: bool,
     pub bot: bool,
     pub owner: Option<RecordId>,
+    #[serde(default)]
+    pub karma: i64,
+    /// Set once the signup code sent to the email is entered. Bots have no email.
+    #[serde(default)]
+    pub email_verified: bool,
 }
 
 impl DBUser {
     /// Creates a new [`DBUser`].
     pub fn new(username: String, bot: bool, owner: Option<RecordId>) -> Self {
-        let created_at = DateTime::default();
+        let created_at: DateTime<Utc> = SystemTime::now().into();
         let modified_at = created_at.clone();
         DBUser {
             id: RecordId::from(("user".to_owned(), cuid2::cuid())),
@@ -36,6 +43,8 @@ impl DBUser {
             admin: false,
             bot,
             owner,
+            karma: 0,
+            email_verified: false,
         }
     }
 }
@@ -62,6 +71,11 @@ pub struct User {
     pub admin: bool,
     pub bot: bool,
     pub owner: Option<Key>,
+    #[serde(default)]
+    pub karma: i64,
+    #[graphql(skip)]
+    #[serde(default)]
+    pub email_verified: bool,
 }
 
 impl From<DBUser> for User {
@@ -78,6 +92,8 @@ impl From<DBUser> for User {
             admin: value.admin,
             bot: value.bot,
             owner: value.owner.map(|i| Key(i.key().to_owned())),
+            karma: value.karma,
+            email_verified: value.email_verified,
         }
     }
 }
@@ -100,6 +116,74 @@ impl From<User> for DBUser {
             admin: value.admin,
             bot: value.bot,
             owner: value.owner.map(|i| RecordId::from_table_key("user", i.0)),
+            karma: value.karma,
+            email_verified: value.email_verified,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DBUserPreferences {
+    pub id: RecordId,
+    pub default_post_sort: PostSort,
+    pub show_nsfw: bool,
+    pub email_post_comments: bool,
+    pub email_moderation: bool,
+    pub language: Option<String>,
+    pub timezone: Option<String>,
+}
+
+impl DBUserPreferences {
+    /// Preferences are keyed by the user they belong to.
+    pub fn new(user: Key) -> Self {
+        Self {
+            id: RecordId::from_table_key("user_preferences", user.0),
+            default_post_sort: PostSort::default(),
+            show_nsfw: false,
+            email_post_comments: true,
+            email_moderation: true,
+            language: None,
+            timezone: None,
         }
     }
 }
+
+#[derive(SimpleObject, Debug)]
+pub struct Preferences {
+    pub default_post_sort: PostSort,
+    pub show_nsfw: bool,
+    /// Email when someone comments on your post.
+    pub email_post_comments: bool,
+    /// Email when moderators act on your content or account.
+    pub email_moderation: bool,
+    /// BCP 47 language tag. Only stored for clients, emails aren't localized.
+    pub language: Option<String>,
+    /// IANA time zone name. Only stored for clients, emails don't show local times.
+    pub timezone: Option<String>,
+}
+
+impl From<DBUserPreferences> for Preferences {
+    fn from(value: DBUserPreferences) -> Self {
+        Self {
+            default_post_sort: value.default_post_sort,
+            show_nsfw: value.show_nsfw,
+            email_post_comments: value.email_post_comments,
+            email_moderation: value.email_moderation,
+            language: value.language,
+            timezone: value.timezone,
+        }
+    }
+}
+
+/// Only the given preferences are changed, `null` clears `language` and `timezone`.
+#[derive(InputObject, Debug, Default)]
+pub struct PreferencesInput {
+    pub default_post_sort: Option<PostSort>,
+    pub show_nsfw: Option<bool>,
+    pub email_post_comments: Option<bool>,
+    pub email_moderation: Option<bool>,
+    #[graphql(validator(max_length = 35, regex = r"^[a-zA-Z]{2,3}(-[a-zA-Z0-9]{2,8})*$"))]
+    pub language: MaybeUndefined<String>,
+    #[graphql(validator(max_length = 64, regex = r"^[A-Za-z_]+(/[A-Za-z0-9_+-]+)*$"))]
+    pub timezone: MaybeUndefined<String>,
+}
@@ -0,0 +1,49 @@
+use std::time::SystemTime;
+
+use async_graphql::Enum;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use super::Key;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum VoteDirection {
+    Up,
+    Down,
+}
+
+impl VoteDirection {
+    /// What the vote adds to the post's score.
+    pub fn value(self) -> i64 {
+        match self {
+            VoteDirection::Up => 1,
+            VoteDirection::Down => -1,
+        }
+    }
+}
+
+/// `user->vote->post` relation.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DBVote {
+    pub id: RecordId,
+    #[serde(rename = "in")]
+    pub user: RecordId,
+    #[serde(rename = "out")]
+    pub post: RecordId,
+    /// `1` or `-1`, stored as a number so scores can be summed in queries.
+    pub value: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DBVote {
+    pub fn new(user: Key, post: RecordId, direction: VoteDirection) -> Self {
+        Self {
+            id: RecordId::from_table_key("vote", cuid2::cuid()),
+            user: RecordId::from_table_key("user", user.0),
+            post,
+            value: direction.value(),
+            created_at: SystemTime::now().into(),
+        }
+    }
+}
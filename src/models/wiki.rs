@@ -0,0 +1,171 @@
+use std::time::SystemTime;
+
+use async_graphql::{Enum, InputObject, SimpleObject};
+use chrono::{DateTime, Utc};
+use cuid2::cuid;
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use super::Key;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Enum, Default)]
+pub enum WikiEditors {
+    /// Moderators with `EDIT_FORUM`.
+    #[default]
+    Moderators,
+    /// Members and moderators.
+    Members,
+}
+
+/// New version of a page, see `saveWikiPage`.
+#[derive(InputObject, Debug)]
+pub struct WikiPageInput {
+    #[graphql(validator(max_length = 100, regex = r"^[a-z0-9-]+(/[a-z0-9-]+)*$"))]
+    pub slug: String,
+    #[graphql(validator(min_length = 1, max_length = 200))]
+    pub title: String,
+    #[graphql(validator(max_length = 20_000))]
+    pub content: String,
+    /// Edit summary.
+    #[graphql(validator(min_length = 1, max_length = 200))]
+    pub reason: Option<String>,
+    /// Revision the edit started from.
+    pub base_revision: Option<u32>,
+}
+
+/// Current version of a page, every save also stores a [`DBWikiRevision`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DBWikiPage {
+    pub id: RecordId,
+    pub forum: RecordId,
+    /// Unique within the forum.
+    pub slug: String,
+    pub title: String,
+    pub content: String,
+    /// Number of the latest revision, starting at 1.
+    pub revision: u32,
+    pub created_at: DateTime<Utc>,
+    pub updated_by: RecordId,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl DBWikiPage {
+    pub fn new(forum: RecordId, slug: String, title: String, content: String, author: Key) -> Self {
+        let created_at: DateTime<Utc> = SystemTime::now().into();
+        Self {
+            id: RecordId::from_table_key("wiki_page", cuid()),
+            forum,
+            slug,
+            title,
+            content,
+            revision: 1,
+            created_at,
+            updated_by: RecordId::from_table_key("user", author.0),
+            updated_at: created_at,
+        }
+    }
+}
+
+#[derive(SimpleObject, Debug)]
+#[graphql(complex)]
+pub struct WikiPage {
+    pub id: Key,
+    pub forum_id: Key,
+    pub slug: String,
+    pub title: String,
+    pub content: String,
+    pub latest_revision: u32,
+    pub created_at: i64,
+    pub updated_by_id: Key,
+    pub updated_at: i64,
+}
+
+impl From<DBWikiPage> for WikiPage {
+    fn from(value: DBWikiPage) -> Self {
+        Self {
+            id: Key(value.id.key().to_owned()),
+            forum_id: Key(value.forum.key().to_owned()),
+            slug: value.slug,
+            title: value.title,
+            content: value.content,
+            latest_revision: value.revision,
+            created_at: value.created_at.timestamp(),
+            updated_by_id: Key(value.updated_by.key().to_owned()),
+            updated_at: value.updated_at.timestamp(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DBWikiRevision {
+    pub id: RecordId,
+    pub page: RecordId,
+    pub number: u32,
+    pub title: String,
+    pub content: String,
+    pub author: RecordId,
+    /// Edit summary.
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DBWikiRevision {
+    pub fn new(page: &DBWikiPage, reason: Option<String>) -> Self {
+        Self {
+            id: RecordId::from_table_key("wiki_revision", cuid()),
+            page: page.id.clone(),
+            number: page.revision,
+            title: page.title.clone(),
+            content: page.content.clone(),
+            author: page.updated_by.clone(),
+            reason,
+            created_at: page.updated_at,
+        }
+    }
+}
+
+#[derive(SimpleObject, Debug)]
+#[graphql(complex)]
+pub struct WikiRevision {
+    pub number: u32,
+    pub title: String,
+    pub content: String,
+    pub author_id: Key,
+    pub reason: Option<String>,
+    pub created_at: i64,
+}
+
+impl From<DBWikiRevision> for WikiRevision {
+    fn from(value: DBWikiRevision) -> Self {
+        Self {
+            number: value.number,
+            title: value.title,
+            content: value.content,
+            author_id: Key(value.author.key().to_owned()),
+            reason: value.reason,
+            created_at: value.created_at.timestamp(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum DiffKind {
+    Unchanged,
+    Added,
+    Removed,
+}
+
+/// One line of a line based diff.
+#[derive(SimpleObject, Debug)]
+pub struct DiffLine {
+    pub kind: DiffKind,
+    pub text: String,
+}
+
+#[derive(SimpleObject, Debug)]
+pub struct WikiDiff {
+    pub from: u32,
+    pub to: u32,
+    pub title_changed: bool,
+    pub lines: Vec<DiffLine>,
+}
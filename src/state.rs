@@ -1,4 +1,5 @@
 use std::{
+    net::IpAddr,
     ops::Deref,
     sync::{Arc, Mutex},
 };
@@ -9,6 +10,7 @@ use surrealdb::{engine::remote::ws::Client, Surreal};
 
 use crate::{gql::ApiInfo, models::user::User};
 
+#[derive(Clone)]
 pub struct State {
     pub inner: Arc<InnerState>,
 }
@@ -33,3 +35,6 @@ impl Deref for State {
 
 #[derive(Default)]
 pub struct Auth(pub Mutex<Option<User>>);
+
+/// Address of the client making the request, absent outside of http requests.
+pub struct ClientIp(pub IpAddr);
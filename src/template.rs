@@ -7,3 +7,14 @@ pub struct EmailVerify<'a> {
     pub code: u64,
     pub site_name: &'static str,
 }
+
+#[derive(TemplateSimple)]
+#[template(path = "notification.html")]
+pub struct Notification<'a> {
+    pub username: &'a str,
+    pub heading: &'a str,
+    pub message: &'a str,
+    /// Path on the frontend, without the leading slash
+    pub link: &'a str,
+    pub site_name: &'static str,
+}
@@ -1,11 +1,19 @@
 mod utils;
 
-use async_graphql::{value, Request, Variables};
+use async_graphql::{value, Request, Response, Value, Variables};
 use rustis::commands::StringCommands;
 use serde_json::json;
 
 type R = anyhow::Result<()>;
 
+fn tp(res: &Response) -> Option<&Value> {
+    res.errors.first()?.extensions.as_ref()?.get("tp")
+}
+
+fn request(query: &str, variables: serde_json::Value) -> Request {
+    Request::new(query).variables(Variables::from_json(variables))
+}
+
 #[tokio::test]
 async fn setup_test() -> R {
     let _ = utils::setup("setup_test").await?;
@@ -33,9 +41,19 @@ async fn test_api_version() -> R {
 #[tokio::test]
 async fn test_user_creation() -> R {
     let (schema, (_, redis, _)) = utils::setup("test_user_creation").await?;
-    let res = schema.execute(r#"mutation {
-        createUser(username: "test_user_creation", email: "test@example.com", password: "sTrOnGPaSs19@!") 
-    }"#).await;
+    let (nonce, solution) = utils::solve_challenge(&schema).await?;
+    let r = Request::new(
+        r#"
+                mutation($nonce: String!, $solution: String!) {
+                    createUser(username: "test_user_creation", email: "test@example.com", password: "sTrOnGPaSs19@!", challenge: { nonce: $nonce, solution: $solution })
+                }
+                "#,
+    )
+    .variables(Variables::from_json(json!({
+        "nonce": nonce,
+        "solution": solution
+    })));
+    let res = schema.execute(r).await;
     assert_eq!(
         res.data,
         value!({
@@ -69,3 +87,350 @@ async fn test_user_creation() -> R {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_disposable_email_rejected() -> R {
+    let (schema, _) = utils::setup("test_disposable_email_rejected").await?;
+    let (nonce, solution) = utils::solve_challenge(&schema).await?;
+    let r = Request::new(
+        r#"
+                mutation($nonce: String!, $solution: String!) {
+                    createUser(username: "test_disposable", email: "test@mailinator.com", password: "sTrOnGPaSs19@!", challenge: { nonce: $nonce, solution: $solution })
+                }
+                "#,
+    )
+    .variables(Variables::from_json(json!({
+        "nonce": nonce,
+        "solution": solution
+    })));
+    let res = schema.execute(r).await;
+    assert_eq!(
+        res.errors[0].extensions.as_ref().and_then(|e| e.get("tp")),
+        Some(&value!("EMAIL_DOMAIN_NOT_ALLOWED"))
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_invalid_challenge_solution() -> R {
+    let (schema, _) = utils::setup("test_invalid_challenge_solution").await?;
+    let res = schema.execute(r#"mutation {
+        createUser(username: "test_bad_challenge", email: "test@example.com", password: "sTrOnGPaSs19@!", challenge: { nonce: "unknown", solution: "0" })
+    }"#).await;
+    assert_eq!(
+        res.errors[0].extensions.as_ref().and_then(|e| e.get("tp")),
+        Some(&value!("INVALID_CHALLENGE"))
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_vote_arithmetic() -> R {
+    let (schema, (db, _, _)) = utils::setup("test_vote_arithmetic").await?;
+    let author = utils::create_user(&schema, &db, "author").await?;
+    let voter = utils::create_user(&schema, &db, "voter").await?;
+    let forum = utils::create_forum(&schema, &author).await?;
+    let post = utils::create_post(&schema, &author, &forum).await?;
+
+    // (direction, score, upvotes, downvotes), the author's karma follows the score.
+    let steps = [
+        (json!("UP"), 1, 1, 0),
+        (json!("UP"), 1, 1, 0),
+        (json!("DOWN"), -1, 0, 1),
+        (json!("DOWN"), -1, 0, 1),
+        (json!(null), 0, 0, 0),
+        (json!(null), 0, 0, 0),
+        (json!("UP"), 1, 1, 0),
+    ];
+    for (direction, score, upvotes, downvotes) in steps {
+        let r = Request::new(
+            r#"
+                mutation($post: Key!, $direction: VoteDirection) {
+                    votePost(postId: $post, direction: $direction) { score upvotes downvotes viewerVote }
+                }
+                "#,
+        )
+        .variables(Variables::from_json(json!({
+            "post": post,
+            "direction": direction
+        })));
+        let res = utils::execute_as(&schema, &voter, r).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let data: serde_json::Value = res.data.into_json()?;
+        assert_eq!(
+            data["votePost"],
+            json!({
+                "score": score,
+                "upvotes": upvotes,
+                "downvotes": downvotes,
+                "viewerVote": direction
+            })
+        );
+
+        let mut karma = db
+            .query("SELECT VALUE karma FROM ONLY type::thing('user', $id)")
+            .bind(("id", author.id.clone()))
+            .await?;
+        let karma: Option<i64> = karma.take(0)?;
+        assert_eq!(karma, Some(score));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_private_forum_filtering() -> R {
+    let (schema, (db, _, _)) = utils::setup("test_private_forum_filtering").await?;
+    let owner = utils::create_user(&schema, &db, "owner").await?;
+    let outsider = utils::create_user(&schema, &db, "outsider").await?;
+    let forum = utils::create_forum(&schema, &owner).await?;
+    let post = utils::create_post(&schema, &owner, &forum).await?;
+
+    let res = utils::execute_as(
+        &schema,
+        &owner,
+        request(
+            "mutation($forum: Key!) { updateForumSettings(forumId: $forum, settings: { visibility: PRIVATE }) { visibility } }",
+            json!({ "forum": forum }),
+        ),
+    )
+    .await;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+
+    let listing = r#"
+        query($forum: Key!, $post: Key!) {
+            Page { post(criteria: { forum: $forum }) { id } forum(criteria: { ids: [$forum] }) { id } }
+            post(criteria: { id: $post }) { id }
+        }
+        "#;
+    let variables = json!({ "forum": forum, "post": post });
+
+    let res = utils::execute_as(&schema, &outsider, request(listing, variables.clone())).await;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+    assert_eq!(
+        res.data.into_json()?,
+        json!({ "Page": { "post": [], "forum": [] }, "post": null })
+    );
+
+    let res = utils::execute_as(&schema, &owner, request(listing, variables)).await;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+    assert_eq!(
+        res.data.into_json()?,
+        json!({
+            "Page": { "post": [{ "id": post }], "forum": [{ "id": forum }] },
+            "post": { "id": post }
+        })
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ban_enforcement() -> R {
+    let (schema, (db, _, _)) = utils::setup("test_ban_enforcement").await?;
+    let owner = utils::create_user(&schema, &db, "owner").await?;
+    let member = utils::create_user(&schema, &db, "member").await?;
+    let forum = utils::create_forum(&schema, &owner).await?;
+    let post = utils::create_post(&schema, &member, &forum).await?;
+
+    let res = utils::execute_as(
+        &schema,
+        &owner,
+        request(
+            "mutation($forum: Key!, $user: Key!) { banFromForum(forumId: $forum, userId: $user) { id } }",
+            json!({ "forum": forum, "user": member.id }),
+        ),
+    )
+    .await;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+
+    let res = utils::execute_as(
+        &schema,
+        &member,
+        request(
+            r#"mutation($forum: Key!) { createPost(forum: $forum, title: "Banned", tags: [], content: "Hello", attachments: []) { id } }"#,
+            json!({ "forum": forum }),
+        ),
+    )
+    .await;
+    assert_eq!(tp(&res), Some(&value!("FORUM_BANNED")));
+
+    let res = utils::execute_as(
+        &schema,
+        &member,
+        request(
+            r#"mutation($post: Key!) { updatePost(postId: $post, content: "Edited", removeAttachments: false) { id } }"#,
+            json!({ "post": post }),
+        ),
+    )
+    .await;
+    assert_eq!(tp(&res), Some(&value!("FORUM_BANNED")));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_moderator_permissions() -> R {
+    let (schema, (db, _, _)) = utils::setup("test_moderator_permissions").await?;
+    let owner = utils::create_user(&schema, &db, "owner").await?;
+    let moderator = utils::create_user(&schema, &db, "moderator").await?;
+    let outsider = utils::create_user(&schema, &db, "outsider").await?;
+    let forum = utils::create_forum(&schema, &owner).await?;
+    let post = utils::create_post(&schema, &outsider, &forum).await?;
+
+    let remove_post = "mutation($post: Key!) { removePost(postId: $post) { id } }";
+    let ban = "mutation($forum: Key!, $user: Key!) { banFromForum(forumId: $forum, userId: $user) { id } }";
+
+    let res = utils::execute_as(
+        &schema,
+        &outsider,
+        request(remove_post, json!({ "post": post })),
+    )
+    .await;
+    assert_eq!(tp(&res), Some(&value!("UNAUTHORIZED_REQUEST")));
+
+    let res = utils::execute_as(
+        &schema,
+        &outsider,
+        request(
+            "mutation($forum: Key!) { updateForumSettings(forumId: $forum, settings: { visibility: PRIVATE }) { visibility } }",
+            json!({ "forum": forum }),
+        ),
+    )
+    .await;
+    assert_eq!(tp(&res), Some(&value!("UNAUTHORIZED_REQUEST")));
+
+    let res = utils::execute_as(
+        &schema,
+        &owner,
+        request(
+            "mutation($forum: Key!, $user: Key!) { addModerator(forumId: $forum, userId: $user, permissions: [MANAGE_POSTS]) { __typename } }",
+            json!({ "forum": forum, "user": moderator.id }),
+        ),
+    )
+    .await;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+
+    let res = utils::execute_as(
+        &schema,
+        &moderator,
+        request(remove_post, json!({ "post": post })),
+    )
+    .await;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+
+    let res = utils::execute_as(
+        &schema,
+        &moderator,
+        request(ban, json!({ "forum": forum, "user": outsider.id })),
+    )
+    .await;
+    assert_eq!(tp(&res), Some(&value!("UNAUTHORIZED_REQUEST")));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_vote_rejection() -> R {
+    let (schema, (db, _, _)) = utils::setup("test_vote_rejection").await?;
+    let author = utils::create_user(&schema, &db, "author").await?;
+    let banned = utils::create_user(&schema, &db, "banned").await?;
+    let forum = utils::create_forum(&schema, &author).await?;
+    let post = utils::create_post(&schema, &author, &forum).await?;
+
+    let vote = "mutation($post: Key!) { votePost(postId: $post, direction: UP) { score } }";
+
+    let res = utils::execute_as(&schema, &author, request(vote, json!({ "post": post }))).await;
+    assert_eq!(tp(&res), Some(&value!("CANNOT_VOTE_OWN_POST")));
+
+    let res = utils::execute_as(
+        &schema,
+        &author,
+        request(
+            "mutation($forum: Key!, $user: Key!) { banFromForum(forumId: $forum, userId: $user) { id } }",
+            json!({ "forum": forum, "user": banned.id }),
+        ),
+    )
+    .await;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+
+    let res = utils::execute_as(&schema, &banned, request(vote, json!({ "post": post }))).await;
+    assert_eq!(tp(&res), Some(&value!("FORUM_BANNED")));
+
+    let mut score = db
+        .query("SELECT VALUE score FROM ONLY type::thing('post', $id)")
+        .bind(("id", post))
+        .await?;
+    let score: Option<i64> = score.take(0)?;
+    assert_eq!(score, Some(0));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_revision_visibility() -> R {
+    let (schema, (db, _, _)) = utils::setup("test_revision_visibility").await?;
+    let owner = utils::create_user(&schema, &db, "owner").await?;
+    let author = utils::create_user(&schema, &db, "author").await?;
+    let outsider = utils::create_user(&schema, &db, "outsider").await?;
+    let forum = utils::create_forum(&schema, &owner).await?;
+    let post = utils::create_post(&schema, &author, &forum).await?;
+
+    let res = utils::execute_as(
+        &schema,
+        &author,
+        request(
+            r#"mutation($post: Key!) { updatePost(postId: $post, content: "Edited", removeAttachments: false) { id } }"#,
+            json!({ "post": post }),
+        ),
+    )
+    .await;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+
+    let revisions =
+        "query($post: Key!) { post(criteria: { id: $post }) { revisions { content } } }";
+    let variables = json!({ "post": post });
+    let history = json!({ "post": { "revisions": [{ "content": "Hello" }] } });
+
+    // Edit history is private by default.
+    let res = utils::execute_as(&schema, &outsider, request(revisions, variables.clone())).await;
+    assert_eq!(tp(&res), Some(&value!("UNAUTHORIZED_REQUEST")));
+    let res = utils::execute_as(&schema, &owner, request(revisions, variables.clone())).await;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+    assert_eq!(res.data.into_json()?, history);
+
+    let res = utils::execute_as(
+        &schema,
+        &owner,
+        request(
+            "mutation($forum: Key!) { updateForumSettings(forumId: $forum, settings: { publicEditHistory: true }) { publicEditHistory } }",
+            json!({ "forum": forum }),
+        ),
+    )
+    .await;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+
+    let res = utils::execute_as(&schema, &outsider, request(revisions, variables.clone())).await;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+    assert_eq!(res.data.into_json()?, history);
+
+    // Deleting the post hides its history from everyone but moderators.
+    let res = utils::execute_as(
+        &schema,
+        &author,
+        request(
+            "mutation($post: Key!) { deletePost(postId: $post) { id } }",
+            variables.clone(),
+        ),
+    )
+    .await;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+
+    let res = utils::execute_as(&schema, &outsider, request(revisions, variables.clone())).await;
+    assert_eq!(tp(&res), Some(&value!("UNAUTHORIZED_REQUEST")));
+    let res = utils::execute_as(&schema, &owner, request(revisions, variables)).await;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+    assert_eq!(res.data.into_json()?, history);
+
+    Ok(())
+}
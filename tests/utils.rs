@@ -1,25 +1,51 @@
 use anyhow::Result;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::SaltString;
+use argon2::Argon2;
+use argon2::PasswordHasher;
+use async_graphql::dataloader::DataLoader;
+use async_graphql::from_value;
 use async_graphql::EmptySubscription;
+use async_graphql::Request;
+use async_graphql::Response;
 use async_graphql::Schema;
+use async_graphql::Variables;
 use dotenvy::dotenv;
 use opendal::Operator;
 use rustis::client::Client;
 use rusty_paseto::generic::Local;
 use rusty_paseto::generic::PasetoSymmetricKey;
 use rusty_paseto::generic::V4;
+use sha2::{Digest, Sha256};
 use std::env;
 use std::sync::Arc;
 use surrealdb::engine::remote::ws::Ws;
 use surrealdb::opt::auth::Database;
 use surrealdb::Surreal;
+use tower_cookies::Cookies;
 use tower_cookies::Key;
 
+use rtwalk::gql::loaders::EmojiLoader;
+use rtwalk::gql::loaders::PostForumLoader;
+use rtwalk::gql::loaders::VoteLoader;
 use rtwalk::gql::ApiInfo;
 use rtwalk::gql::MergedMutationRoot;
 use rtwalk::gql::MergedQueryRoot;
+use rtwalk::state::Auth;
 use rtwalk::state::InnerState;
 use rtwalk::state::State;
 
+type TestSchema = Schema<MergedQueryRoot, MergedMutationRoot, EmptySubscription>;
+type Db = Surreal<surrealdb::engine::remote::ws::Client>;
+
+const PASSWORD: &str = "sTrOnGPaSs19@!";
+
+/// A logged in user, see [`create_user`].
+pub struct TestUser {
+    pub id: String,
+    pub cookies: Cookies,
+}
+
 pub async fn setup(
     test_name: &str,
 ) -> Result<(
@@ -49,12 +75,7 @@ pub async fn setup(
 
     let opendal_service_builder = opendal::services::Fs::default().root("data/");
 
-    let schema = Schema::build(
-        MergedQueryRoot::default(),
-        MergedMutationRoot::default(),
-        EmptySubscription,
-    )
-    .data(State {
+    let state = State {
         inner: Arc::new(InnerState {
             site_name: "DreamH",
             info: ApiInfo {
@@ -73,8 +94,146 @@ pub async fn setup(
                 cookies_key[..32].as_bytes(),
             )),
         }),
-    })
+    };
+
+    let schema = Schema::build(
+        MergedQueryRoot::default(),
+        MergedMutationRoot::default(),
+        EmptySubscription,
+    )
+    .data(DataLoader::new(EmojiLoader(state.clone()), tokio::spawn))
+    .data(DataLoader::new(
+        PostForumLoader(state.clone()),
+        tokio::spawn,
+    ))
+    .data(DataLoader::new(VoteLoader(state.clone()), tokio::spawn))
+    .data(state)
     .finish();
 
     Ok((schema, (surreal_client, redis, pubsub_redis)))
 }
+
+/// Fetches a registration challenge and brute forces it, returns `(nonce, solution)`.
+pub async fn solve_challenge(
+    schema: &Schema<MergedQueryRoot, MergedMutationRoot, EmptySubscription>,
+) -> Result<(String, String)> {
+    let res = schema
+        .execute("{ registrationChallenge { nonce difficulty } }")
+        .await;
+    let challenge: serde_json::Value = from_value(res.data)?;
+    let nonce = challenge["registrationChallenge"]["nonce"]
+        .as_str()
+        .expect("nonce")
+        .to_string();
+    let difficulty = challenge["registrationChallenge"]["difficulty"]
+        .as_u64()
+        .expect("difficulty") as u32;
+
+    for i in 0u64.. {
+        let hash = Sha256::digest(format!("{}:{}", nonce, i));
+        let mut bits = 0;
+        for byte in hash {
+            if byte == 0 {
+                bits += 8;
+            } else {
+                bits += byte.leading_zeros();
+                break;
+            }
+        }
+        if bits >= difficulty {
+            return Ok((nonce, i.to_string()));
+        }
+    }
+    unreachable!()
+}
+
+/// Inserts a verified user straight into the database, skipping signup and its rate
+/// limit, and logs them in.
+pub async fn create_user(schema: &TestSchema, db: &Db, name: &str) -> Result<TestUser> {
+    let id = cuid2::cuid();
+    let password = Argon2::default()
+        .hash_password(PASSWORD.as_bytes(), &SaltString::generate(&mut OsRng))
+        .map_err(|e| anyhow::anyhow!("{e}"))?
+        .to_string();
+    db.query(
+        "CREATE type::thing('user', $id) SET username = $name, display_name = $name, \
+        created_at = time::now(), modified_at = time::now(), admin = false, bot = false, \
+        karma = 0, email_verified = true",
+    )
+    .query(
+        "CREATE user_secret SET user = type::thing('user', $id), email = $email, \
+        password = $password, banned = false",
+    )
+    .bind(("id", id.clone()))
+    .bind(("name", format!("{name}_{}", &id[..6])))
+    .bind(("email", format!("{id}@example.com")))
+    .bind(("password", password))
+    .await?
+    .check()?;
+
+    let cookies = Cookies::default();
+    let res = schema
+        .execute(
+            Request::new(
+                "mutation($email: String!, $password: String!) { login(email: $email, password: $password) { id } }",
+            )
+            .variables(Variables::from_json(serde_json::json!({
+                "email": format!("{id}@example.com"),
+                "password": PASSWORD,
+            })))
+            .data(cookies.clone())
+            .data(Auth::default()),
+        )
+        .await;
+    anyhow::ensure!(res.errors.is_empty(), "login failed: {:?}", res.errors);
+
+    Ok(TestUser { id, cookies })
+}
+
+/// Runs the request with the user's session.
+pub async fn execute_as(schema: &TestSchema, user: &TestUser, request: Request) -> Response {
+    schema
+        .execute(request.data(user.cookies.clone()).data(Auth::default()))
+        .await
+}
+
+/// Runs the request as `user` and returns `data.<field>.id`.
+async fn create(
+    schema: &TestSchema,
+    user: &TestUser,
+    request: Request,
+    field: &str,
+) -> Result<String> {
+    let res = execute_as(schema, user, request).await;
+    anyhow::ensure!(res.errors.is_empty(), "{field} failed: {:?}", res.errors);
+    let data: serde_json::Value = from_value(res.data)?;
+
+    Ok(data[field]["id"].as_str().expect("id").to_string())
+}
+
+/// Creates a public forum owned by `owner`, returns its id.
+pub async fn create_forum(schema: &TestSchema, owner: &TestUser) -> Result<String> {
+    let name = format!("f_{}", &cuid2::cuid()[..8]);
+    create(
+        schema,
+        owner,
+        Request::new("mutation($name: String!) { createForum(name: $name) { id } }")
+            .variables(Variables::from_json(serde_json::json!({ "name": name }))),
+        "createForum",
+    )
+    .await
+}
+
+/// Creates a text post in the forum, returns its id.
+pub async fn create_post(schema: &TestSchema, poster: &TestUser, forum: &str) -> Result<String> {
+    create(
+        schema,
+        poster,
+        Request::new(
+            r#"mutation($forum: Key!) { createPost(forum: $forum, title: "Test post", tags: [], content: "Hello", attachments: []) { id } }"#,
+        )
+        .variables(Variables::from_json(serde_json::json!({ "forum": forum }))),
+        "createPost",
+    )
+    .await
+}